@@ -0,0 +1,146 @@
+use crate::{storage, time, tag, pnch, note, error::GlobalError};
+
+/// Old pnchs moved out of the pnchs database to keep it small.
+///
+/// There is one archive per year, with the same format as the pnchs database. Archived pnchs are
+/// only loaded when asked for, so they can be listed but not edited. The notes of the archived
+/// pnchs are moved with them, to a notes file next to the archive.
+pub struct Archive {
+    pub year: u16,
+    pub pnchs: pnch::Pnchs,
+}
+
+impl Archive {
+    const FILE_PREFIX: &'static str = "pnchs-archive-";
+    const FILE_SUFFIX: &'static str = ".db";
+    const NOTES_FILE_SUFFIX: &'static str = ".notes.db";
+
+    pub fn file_name(year: u16) -> String {
+        format!("{}{year}{}", Self::FILE_PREFIX, Self::FILE_SUFFIX)
+    }
+
+    /// The name of the file with the notes of the pnchs archived in `year`.
+    pub fn notes_file_name(year: u16) -> String {
+        format!("{}{year}{}", Self::FILE_PREFIX, Self::NOTES_FILE_SUFFIX)
+    }
+
+    /// The notes of the archived pnchs, empty when the archive has none.
+    fn notes(storage: &storage::Storage, year: u16) -> Result<note::Notes, GlobalError> {
+        let file = Self::notes_file_name(year);
+        match storage.dir().join(&file).exists() {
+            true => note::Notes::load_file(storage, &file),
+            false => Ok(note::Notes::default()),
+        }
+    }
+
+    /// The years with an archive, sorted.
+    pub fn years(storage: &storage::Storage) -> Result<Vec<u16>, GlobalError> {
+        let entries = match std::fs::read_dir(storage.dir()) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(_) => return Err(GlobalError::fs("load", "archives")),
+        };
+        let mut years = entries
+            .filter_map(|entry| {
+                let name = entry.ok()?.file_name().into_string().ok()?;
+                name.strip_prefix(Self::FILE_PREFIX)?
+                    .strip_suffix(Self::FILE_SUFFIX)?
+                    .parse::<u16>()
+                    .ok()
+            })
+            .collect::<Vec<_>>();
+        years.sort();
+        Ok(years)
+    }
+
+    pub fn load(storage: &storage::Storage, year: u16, tags: &tag::Tags) -> Result<Self, GlobalError> {
+        Ok(Self {
+            year,
+            pnchs: pnch::Pnchs::load_file(storage, &Self::file_name(year), tags)?,
+        })
+    }
+
+    /// Load every archive.
+    pub fn load_all(storage: &storage::Storage, tags: &tag::Tags) -> Result<Vec<Self>, GlobalError> {
+        Self::years(storage)?
+            .into_iter()
+            .map(|year| Self::load(storage, year, tags))
+            .collect()
+    }
+
+    /// Save the archive, with the ids of the notes of its pnchs updated like for the pnchs
+    /// database, see `Notes::remap`.
+    pub fn save(&self, storage: &storage::Storage) -> Result<(), GlobalError> {
+        self.pnchs.save_file(storage, &Self::file_name(self.year))?;
+        match storage.dir().join(Self::notes_file_name(self.year)).exists() {
+            true => note::Notes::remap_file(storage, &Self::notes_file_name(self.year), &self.pnchs.saved_ids()),
+            false => Ok(()),
+        }
+    }
+
+    /// Add the archived pnchs from `from` to `to` to the pnchs. Their ids follow the ids of the
+    /// pnchs, so they don't collide, and the pnchs are sorted again. Their notes are added to
+    /// `notes`, with the same ids, when given.
+    pub fn extend(pnchs: &mut pnch::Pnchs, mut notes: Option<&mut note::Notes>, storage: &storage::Storage, tags: &tag::Tags, from: &time::Date, to: &time::Date) -> Result<(), GlobalError> {
+        let years = Self::years(storage)?
+            .into_iter()
+            .filter(|year| (from.year()..=to.year()).contains(year));
+        for year in years {
+            let archived_notes = match notes {
+                Some(_) => Self::notes(storage, year)?,
+                None => note::Notes::default(),
+            };
+            for mut pnch in Self::load(storage, year, tags)?.pnchs.0 {
+                let id = pnchs.0.len() as u32;
+                if let Some(notes) = notes.as_deref_mut() {
+                    archived_notes.copy_to(pnch.id, id, notes);
+                }
+                pnch.id = id;
+                pnchs.0.push(pnch);
+            }
+        }
+        pnchs.0.sort();
+        Ok(())
+    }
+
+    /// Move the pnchs before `before` to the archive of their year and return the number of pnchs
+    /// moved to each archive.
+    ///
+    /// The archived pnchs are removed from `pnchs`, which must be saved after the archives are
+    /// written. A pnch is never lost: if the pnchs are not saved, they stay in the pnchs database
+    /// and archiving them again does not add them twice to the archive. Their notes are copied
+    /// to the notes of the archive, and removed from the notes database when the pnchs are saved.
+    pub fn archive(pnchs: &mut pnch::Pnchs, before: &time::Date, storage: &storage::Storage, tags: &tag::Tags) -> Result<Vec<(u16, usize)>, GlobalError> {
+        let notes = note::Notes::load(storage)?;
+        let (old, new): (Vec<_>, Vec<_>) = std::mem::take(&mut pnchs.0)
+            .into_iter()
+            .partition(|pnch| pnch.date < *before);
+        pnchs.0 = new;
+        let old = pnch::Pnchs(old);
+
+        let mut counts = Vec::new();
+        for group in old.group_by(|pnch| pnch.date.year()) {
+            let mut archive = Self::load(storage, group.key, tags)?;
+            let archived = archive.pnchs.0.iter().map(Vec::from).collect::<Vec<_>>();
+            let added = group.pnchs
+                .iter()
+                .filter(|pnch| !archived.contains(&Vec::from(**pnch)))
+                .collect::<Vec<_>>();
+            counts.push((group.key, group.pnchs.len()));
+            // The ids of the pnchs added to the archive follow the ids of the archived pnchs,
+            // until the archive is saved.
+            let mut archived_notes = Self::notes(storage, group.key)?;
+            let before = archived_notes.len();
+            for pnch in added {
+                let id = archive.pnchs.0.len() as u32;
+                notes.copy_to(pnch.id, id, &mut archived_notes);
+                archive.pnchs.0.push(pnch::Pnch { id, ..(*pnch).clone() });
+            }
+            if archived_notes.len() != before {
+                archived_notes.save_file(storage, &Self::notes_file_name(group.key))?;
+            }
+            archive.save(storage)?;
+        }
+        Ok(counts)
+    }
+}
@@ -1,65 +1,564 @@
-use std::{default, str::FromStr};
+use std::{default, fmt, str::FromStr};
 
-use crate::{storage, time, GlobalError};
+use crate::{format, storage, time, pnch, template, GlobalError};
 
+#[derive(Clone)]
 pub struct Config {
     pub print_color: bool,
     pub ls_default_period: time::Period,
+    pub relative_dates: bool,
+    pub work_days: time::Weekdays,
+    /// The duration to work each day. No target is set when it is zero.
+    pub day_target: time::Duration,
+    /// The first day of the weeks, used by `pnch week`.
+    pub week_start: time::Weekday,
+    /// The character separating the fields of the csv format.
+    pub csv_delimiter: pnch::Delimiter,
+    /// The pager of the outputs that do not fit in the terminal.
+    pub pager: Pager,
+    /// The template used by `pnch ls --format template` without `--template`.
+    pub template: Option<template::Template>,
+    /// Count the open entries in the totals until now, like `--include-open-duration`.
+    pub include_open_in_totals: bool,
+    /// Print the weekday after the dates of the listings.
+    pub show_weekday: bool,
+    /// The clock used to print the times of the listings and of `pnch status`.
+    pub time_format: time::TimeFormat,
+    /// How the durations of `pnch ls` and `pnch report` are rounded.
+    pub round: time::Rounding,
+    /// Record the seconds of the times.
+    pub precision: time::Precision,
+    /// How the durations are printed.
+    pub duration_format: time::DurationFormat,
+    /// The timezone of the current time, `PNCH_TZ` has precedence over it.
+    pub timezone: time::Timezone,
+    /// The work days that are not worked, like vacations and holidays.
+    pub days_off: time::DaysOff,
+    /// The warning that the local timezone could not be found was printed. It is not a key, it is
+    /// only printed once.
+    pub utc_fallback_warned: bool,
+}
+
+/// The pager of the outputs that do not fit in the terminal.
+#[derive(Clone, PartialEq)]
+pub enum Pager {
+    /// `$PAGER`, or `less -FRX` when it is not set.
+    Auto,
+    /// Never use a pager.
+    Off,
+    /// A command run by the shell.
+    Command(String),
+}
+
+impl Pager {
+    /// The maximum size of the command in bytes.
+    const COMMAND_SIZE: usize = 64;
+    /// The pager used when `$PAGER` is not set. The colors are kept and nothing is paged when the
+    /// output fits in the terminal.
+    const DEFAULT_COMMAND: &'static str = "less -FRX";
+
+    /// The command to run, `None` when no pager should be used.
+    pub fn command(&self) -> Option<String> {
+        let command = match self {
+            Self::Auto => std::env::var("PAGER")
+                .unwrap_or_else(|_| String::from(Self::DEFAULT_COMMAND)),
+            Self::Off => return None,
+            Self::Command(command) => command.clone(),
+        };
+        match command.trim() {
+            "" | "cat" => None,
+            _ => Some(command),
+        }
+    }
+
+    fn to_bytes(&self) -> [u8; Self::COMMAND_SIZE] {
+        let mut buffer = [0; Self::COMMAND_SIZE];
+        let value = match self {
+            Self::Auto => "",
+            Self::Off => "off",
+            Self::Command(command) => command,
+        };
+        buffer[..value.len()].copy_from_slice(value.as_bytes());
+        buffer
+    }
+
+    fn from_bytes(buffer: &[u8]) -> Result<Self, GlobalError> {
+        let value = buffer
+            .iter()
+            .copied()
+            .filter(|&c| c != 0)
+            .collect::<Vec<u8>>();
+        Self::from_str(&String::from_utf8(value)?)
+    }
+}
+
+impl FromStr for Pager {
+    type Err = GlobalError;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim() {
+            "" | "auto" => Ok(Self::Auto),
+            "off" => Ok(Self::Off),
+            _ if value.len() > Self::COMMAND_SIZE => {
+                Err(GlobalError::parse("`auto`, `off` or a command of at most 64 bytes"))
+            }
+            command => Ok(Self::Command(command.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for Pager {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Auto => write!(f, "auto"),
+            Self::Off => write!(f, "off"),
+            Self::Command(command) => write!(f, "{command}"),
+        }
+    }
+}
+
+/// A configuration key that can be read and written from the CLI.
+///
+/// Every key is defined once in `Config::KEYS`, so reading and writing keys can't drift out of
+/// sync.
+struct Key {
+    name: &'static str,
+    get: fn(&Config) -> String,
+    set: fn(&mut Config, &str) -> Result<(), GlobalError>,
+}
+
+/// The current and default values of a key.
+pub struct Value {
+    pub key: &'static str,
+    pub value: String,
+    pub default: String,
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} = {} (default: {})", self.key, self.value, self.default)
+    }
+}
+
+/// A change made to the value of a key.
+pub struct Change {
+    pub key: &'static str,
+    pub old: String,
+    pub new: String,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} -> {}", self.key, self.old, self.new)
+    }
 }
 
 impl Config {
-    const CONFIG_FILE_NAME: &'static str = "config.db";
+    pub const CONFIG_FILE_NAME: &'static str = "config.db";
+    /// The format of the config. The version 1 saved the unit of the default period of `pnch ls`
+    /// with its count, the version 0 saved it as a number of days.
+    pub const FORMAT: format::Format = format::Format::new(*b"PCFG", 1, 1, "config", Self::migrate);
 
     /// size of the print color field
     pub const PRINT_COLOR_SIZE: usize = 1;
     /// size of the ls default period field
-    const LS_DEFAULT_PERIOD_SIZE: usize = 4;
+    const LS_DEFAULT_PERIOD_SIZE: usize = time::Period::SIZE;
+    /// size of the ls default period field in the version 0 of the format, a number of days
+    const V0_LS_DEFAULT_PERIOD_SIZE: usize = 4;
+    /// size of the relative dates field
+    const RELATIVE_DATES_SIZE: usize = 1;
+    /// size of the work days field
+    const WORK_DAYS_SIZE: usize = 1;
+    /// size of the day target field
+    const DAY_TARGET_SIZE: usize = 2;
+    /// size of the week start field
+    const WEEK_START_SIZE: usize = 1;
+    /// size of the csv delimiter field
+    const CSV_DELIMITER_SIZE: usize = 1;
+    /// size of the pager field
+    const PAGER_SIZE: usize = Pager::COMMAND_SIZE;
+    /// size of the template field
+    const TEMPLATE_SIZE: usize = 128;
+    /// size of the include open in totals field
+    const INCLUDE_OPEN_IN_TOTALS_SIZE: usize = 1;
+    /// size of the show weekday field
+    const SHOW_WEEKDAY_SIZE: usize = 1;
+    /// size of the time format field
+    const TIME_FORMAT_SIZE: usize = 1;
+    /// size of the round field
+    const ROUND_SIZE: usize = 2;
+    /// size of the precision field
+    const PRECISION_SIZE: usize = 1;
+    /// size of the duration format field
+    const DURATION_FORMAT_SIZE: usize = 1;
+    /// size of the timezone field
+    const TIMEZONE_SIZE: usize = time::Timezone::SIZE;
+    /// size of the days off field
+    const DAYS_OFF_SIZE: usize = 256;
+    /// size of the utc fallback warned field
+    const UTC_FALLBACK_WARNED_SIZE: usize = 1;
     /// total size of the config
-    const SIZE: usize = Self::PRINT_COLOR_SIZE + Self::LS_DEFAULT_PERIOD_SIZE;
+    const SIZE: usize = Self::PRINT_COLOR_SIZE + Self::LS_DEFAULT_PERIOD_SIZE
+        + Self::RELATIVE_DATES_SIZE + Self::WORK_DAYS_SIZE + Self::DAY_TARGET_SIZE
+        + Self::WEEK_START_SIZE + Self::CSV_DELIMITER_SIZE + Self::PAGER_SIZE
+        + Self::TEMPLATE_SIZE + Self::INCLUDE_OPEN_IN_TOTALS_SIZE + Self::SHOW_WEEKDAY_SIZE
+        + Self::TIME_FORMAT_SIZE + Self::ROUND_SIZE + Self::PRECISION_SIZE
+        + Self::DURATION_FORMAT_SIZE + Self::TIMEZONE_SIZE + Self::DAYS_OFF_SIZE
+        + Self::UTC_FALLBACK_WARNED_SIZE;
 
-    pub fn load() -> Result<Self, GlobalError> {
-        let buffer = storage::load(Self::CONFIG_FILE_NAME)?;
-        if buffer.len() == 0 {
+    /// Every known configuration key.
+    const KEYS: [Key; 17] = [
+        Key {
+            name: "csv-delimiter",
+            get: |config| config.csv_delimiter.to_string(),
+            set: |config, value| {
+                config.csv_delimiter = pnch::Delimiter::from_str(value)?;
+                Ok(())
+            },
+        },
+        Key {
+            name: "day-target",
+            get: |config| config.day_target.to_hh_mm(),
+            set: |config, value| {
+                let day_target = time::Duration::from_str(value)?;
+                if day_target.minutes >= 24 * 60 {
+                    return Err(GlobalError::parse("a duration shorter than a day, like `7:30`"));
+                }
+                config.day_target = day_target;
+                Ok(())
+            },
+        },
+        Key {
+            name: "days-off",
+            get: |config| config.days_off.to_string(),
+            set: |config, value| {
+                let days_off = time::DaysOff::from_str(value)?;
+                if days_off.to_string().len() > Self::DAYS_OFF_SIZE {
+                    return Err(GlobalError::parse("days off of at most 256 bytes"));
+                }
+                config.days_off = days_off;
+                Ok(())
+            },
+        },
+        Key {
+            name: "duration-format",
+            get: |config| config.duration_format.to_string(),
+            set: |config, value| {
+                config.duration_format = time::DurationFormat::from_str(value)?;
+                Ok(())
+            },
+        },
+        Key {
+            name: "include-open-in-totals",
+            get: |config| config.include_open_in_totals.to_string(),
+            set: |config, value| {
+                config.include_open_in_totals = bool::from_str(value)
+                    .map_err(|_| GlobalError::parse("one of `true` or `false`"))?;
+                Ok(())
+            },
+        },
+        Key {
+            name: "ls-default-period",
+            get: |config| config.ls_default_period.to_string(),
+            set: |config, value| {
+                config.ls_default_period = time::Period::from_str(value)?;
+                Ok(())
+            },
+        },
+        Key {
+            name: "pager",
+            get: |config| config.pager.to_string(),
+            set: |config, value| {
+                config.pager = Pager::from_str(value)?;
+                Ok(())
+            },
+        },
+        Key {
+            name: "precision",
+            get: |config| config.precision.to_string(),
+            set: |config, value| {
+                config.precision = time::Precision::from_str(value)?;
+                Ok(())
+            },
+        },
+        Key {
+            name: "print-color",
+            get: |config| config.print_color.to_string(),
+            set: |config, value| {
+                config.print_color = bool::from_str(value)
+                    .map_err(|_| GlobalError::parse("one of `true` or `false`"))?;
+                Ok(())
+            },
+        },
+        Key {
+            name: "relative-dates",
+            get: |config| config.relative_dates.to_string(),
+            set: |config, value| {
+                config.relative_dates = bool::from_str(value)
+                    .map_err(|_| GlobalError::parse("one of `true` or `false`"))?;
+                Ok(())
+            },
+        },
+        Key {
+            name: "round",
+            get: |config| config.round.to_string(),
+            set: |config, value| {
+                config.round = time::Rounding::from_str(value)?;
+                Ok(())
+            },
+        },
+        Key {
+            name: "show-weekday",
+            get: |config| config.show_weekday.to_string(),
+            set: |config, value| {
+                config.show_weekday = bool::from_str(value)
+                    .map_err(|_| GlobalError::parse("one of `true` or `false`"))?;
+                Ok(())
+            },
+        },
+        Key {
+            name: "template",
+            get: |config| config.template.as_ref().map(|template| template.to_string()).unwrap_or_default(),
+            set: |config, value| {
+                if value.len() > Self::TEMPLATE_SIZE {
+                    return Err(GlobalError::parse("a template of at most 128 bytes"));
+                }
+                config.template = match value {
+                    "" => None,
+                    value => Some(template::Template::from_str(value)?),
+                };
+                Ok(())
+            },
+        },
+        Key {
+            name: "time-format",
+            get: |config| config.time_format.to_string(),
+            set: |config, value| {
+                config.time_format = time::TimeFormat::from_str(value)?;
+                Ok(())
+            },
+        },
+        Key {
+            name: "timezone",
+            get: |config| config.timezone.to_string(),
+            set: |config, value| {
+                config.timezone = time::Timezone::from_str(value)?;
+                Ok(())
+            },
+        },
+        Key {
+            name: "week-start",
+            get: |config| time::short_weekday_name(config.week_start),
+            set: |config, value| {
+                config.week_start = time::parse_weekday(value)?;
+                Ok(())
+            },
+        },
+        Key {
+            name: "work-days",
+            get: |config| config.work_days.to_string(),
+            set: |config, value| {
+                config.work_days = time::Weekdays::from_str(value)?;
+                Ok(())
+            },
+        },
+    ];
+
+    pub fn load(storage: &storage::Storage) -> Result<Self, GlobalError> {
+        let mut buffer = Self::FORMAT.load(storage, Self::CONFIG_FILE_NAME)?;
+        if buffer.is_empty() {
             return Ok(Self::default());
-        } else if buffer.len() != Self::SIZE {
+        } else if buffer.len() > Self::SIZE {
             return Err(GlobalError::wrong_byte_len("config", buffer.len(), Self::SIZE));
         }
+        // New fields are always added at the end of the config. A config saved by an older
+        // version is shorter and the missing fields are filled with their default value.
+        let default_buffer = Vec::from(&Self::default());
+        buffer.extend_from_slice(&default_buffer[buffer.len()..]);
+
         let print_color = buffer[0] != 0;
-        let ls_default_period_bytes = buffer[1..5]
+        let ls_default_period_bytes = buffer[1..6]
             .try_into()
             .expect("The size was checked before");
-        let ls_default_period_in_days = u32::from_le_bytes(ls_default_period_bytes);
+        let ls_default_period = time::Period::from_bytes(ls_default_period_bytes);
+        let relative_dates = buffer[6] != 0;
+        let work_days = time::Weekdays::from_byte(buffer[7]);
+        let day_target_bytes = buffer[8..10]
+            .try_into()
+            .expect("The size was checked before");
+        let day_target = time::Duration { minutes: u16::from_le_bytes(day_target_bytes) as i64, seconds: 0 };
+        let week_start = time::Weekday::Monday.nth_next(buffer[10] % 7);
+        let csv_delimiter = pnch::Delimiter::from_char(buffer[11] as char)
+            .unwrap_or(pnch::Delimiter::Comma);
+        let pager = Pager::from_bytes(&buffer[12..12 + Self::PAGER_SIZE])?;
+        let template_start = 12 + Self::PAGER_SIZE;
+        let template_bytes = buffer[template_start..template_start + Self::TEMPLATE_SIZE]
+            .iter()
+            .copied()
+            .filter(|&c| c != 0)
+            .collect::<Vec<u8>>();
+        let template = match String::from_utf8(template_bytes)? {
+            template if template.is_empty() => None,
+            template => Some(template::Template::from_str(&template)?),
+        };
+        let include_open_in_totals = buffer[template_start + Self::TEMPLATE_SIZE] != 0;
+        let show_weekday = buffer[template_start + Self::TEMPLATE_SIZE + 1] != 0;
+        let time_format = time::TimeFormat::from_byte(buffer[template_start + Self::TEMPLATE_SIZE + 2]);
+        let round_start = template_start + Self::TEMPLATE_SIZE + 3;
+        let round = time::Rounding::from_bytes([buffer[round_start], buffer[round_start + 1]]);
+        let precision = time::Precision::from_byte(buffer[round_start + Self::ROUND_SIZE]);
+        let duration_format = time::DurationFormat::from_byte(buffer[round_start + Self::ROUND_SIZE + 1]);
+        let timezone_start = round_start + Self::ROUND_SIZE + 2;
+        let timezone = time::Timezone::from_le_bytes([buffer[timezone_start], buffer[timezone_start + 1]]);
+        let days_off_start = timezone_start + Self::TIMEZONE_SIZE;
+        let days_off_bytes = buffer[days_off_start..days_off_start + Self::DAYS_OFF_SIZE]
+            .iter()
+            .copied()
+            .filter(|&c| c != 0)
+            .collect::<Vec<u8>>();
+        let days_off = time::DaysOff::from_str(&String::from_utf8(days_off_bytes)?)?;
+        let utc_fallback_warned = buffer[days_off_start + Self::DAYS_OFF_SIZE] != 0;
         Ok(Self {
-            ls_default_period: time::Period::Days(ls_default_period_in_days),
+            ls_default_period,
             print_color,
+            relative_dates,
+            work_days,
+            day_target,
+            week_start,
+            csv_delimiter,
+            pager,
+            template,
+            include_open_in_totals,
+            show_weekday,
+            time_format,
+            round,
+            precision,
+            duration_format,
+            timezone,
+            days_off,
+            utc_fallback_warned,
         })
     }
 
-    pub fn save(&self) -> Result<(), GlobalError> {
-        let path = storage::build_path(Self::CONFIG_FILE_NAME)?;
-        let mut content: Vec<u8>= Vec::new();
-        content.push(self.print_color.into());
-        content.extend_from_slice(&self.ls_default_period
-            .as_days()
-            .to_le_bytes());
-        std::fs::write(path, content)
-            .map_err(|_| GlobalError::fs("save", "config"))?;
-        Ok(())
+    pub fn save(&self, storage: &storage::Storage) -> Result<(), GlobalError> {
+        storage.save(Self::CONFIG_FILE_NAME, &Self::FORMAT.with_header(&Vec::from(self)), "config")
     }
 
-    pub fn try_set(&mut self, key: &str, value: &str) -> Result<(), GlobalError> {
-        match key {
-            "ls-default-period" => {
-                self.ls_default_period = time::Period::from_str(value)?;
-                Ok(())
-            }
-            "print-color" => {
-                self.print_color = bool::from_str(value)
-                    .map_err(|_| GlobalError::parse("one of `true` or `false`"))?;
-                Ok(())
-            }
-            _ => Err(GlobalError::config_invalid_key(key))
+    /// Convert a config saved in the version 0 of the format, whose default period of `pnch ls`
+    /// was a number of days, to the current version. The period is kept as days.
+    fn migrate(_: &storage::Storage, _: &str, _: u16, records: &[u8]) -> Result<Vec<u8>, GlobalError> {
+        let period_end = Self::PRINT_COLOR_SIZE + Self::V0_LS_DEFAULT_PERIOD_SIZE;
+        let Some(days_bytes) = records.get(Self::PRINT_COLOR_SIZE..period_end) else {
+            return Err(GlobalError::wrong_byte_len("config", records.len(), period_end));
+        };
+        let days = u32::from_le_bytes(days_bytes.try_into().expect("the range has the size of a u32"));
+        let mut migrated = Vec::with_capacity(records.len() + Self::LS_DEFAULT_PERIOD_SIZE - Self::V0_LS_DEFAULT_PERIOD_SIZE);
+        migrated.extend_from_slice(&records[..Self::PRINT_COLOR_SIZE]);
+        migrated.extend_from_slice(&time::Period::Days(days).to_bytes());
+        migrated.extend_from_slice(&records[period_end..]);
+        Ok(migrated)
+    }
+
+    /// Comma separated list of every valid key, used in hints.
+    pub fn key_names() -> String {
+        Self::KEYS
+            .iter()
+            .map(|key| format!("`{}`", key.name))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// The current and default value of every key.
+    pub fn values(&self) -> Vec<Value> {
+        let default = Self::default();
+        Self::KEYS
+            .iter()
+            .map(|key| Value {
+                key: key.name,
+                value: (key.get)(self),
+                default: (key.get)(&default),
+            })
+            .collect()
+    }
+
+    fn key(key: &str) -> Result<&'static Key, GlobalError> {
+        Self::KEYS
+            .iter()
+            .find(|k| k.name == key)
+            .ok_or_else(|| GlobalError::config_invalid_key(key))
+    }
+
+    /// The current value of a key.
+    pub fn get(&self, key: &str) -> Result<String, GlobalError> {
+        Ok((Self::key(key)?.get)(self))
+    }
+
+    /// Restore a key, or every key when `key` is `None`, to its default value and return the
+    /// updated config with the list of changes.
+    pub fn reset(&self, key: Option<&str>) -> Result<(Self, Vec<Change>), GlobalError> {
+        let keys = match key {
+            Some(key) => vec![Self::key(key)?],
+            None => Self::KEYS.iter().collect(),
+        };
+        let default = Self::default();
+        let pairs = keys
+            .into_iter()
+            .map(|key| (key.name.to_owned(), (key.get)(&default)))
+            .collect::<Vec<_>>();
+        self.with_changes(&pairs)
+    }
+
+    /// Apply every `(key, value)` pair to a copy of the config and return it with the list of
+    /// changes.
+    ///
+    /// Every pair is validated before anything is returned, so a single invalid pair means that
+    /// none of them are applied.
+    pub fn with_changes(&self, pairs: &[(String, String)]) -> Result<(Self, Vec<Change>), GlobalError> {
+        let mut config = self.clone();
+        let mut changes = Vec::new();
+        for (key, value) in pairs {
+            let key = Self::key(key)?;
+            let old = (key.get)(&config);
+            (key.set)(&mut config, value)?;
+            changes.push(Change {
+                key: key.name,
+                old,
+                new: (key.get)(&config),
+            });
+        }
+        Ok((config, changes))
+    }
+}
+
+impl From<&Config> for Vec<u8> {
+    fn from(config: &Config) -> Self {
+        let mut buffer = Vec::with_capacity(Config::SIZE);
+        buffer.push(config.print_color.into());
+        buffer.extend_from_slice(&config.ls_default_period.to_bytes());
+        buffer.push(config.relative_dates.into());
+        buffer.push(config.work_days.to_byte());
+        buffer.extend_from_slice(&(config.day_target.minutes as u16).to_le_bytes());
+        buffer.push(config.week_start.number_days_from_monday());
+        buffer.push(config.csv_delimiter.as_char() as u8);
+        buffer.extend_from_slice(&config.pager.to_bytes());
+        let mut template = [0; Config::TEMPLATE_SIZE];
+        if let Some(value) = &config.template {
+            let value = value.to_string();
+            template[..value.len()].copy_from_slice(value.as_bytes());
         }
+        buffer.extend_from_slice(&template);
+        buffer.push(config.include_open_in_totals.into());
+        buffer.push(config.show_weekday.into());
+        buffer.push(config.time_format.to_byte());
+        buffer.extend_from_slice(&config.round.to_bytes());
+        buffer.push(config.precision.to_byte());
+        buffer.push(config.duration_format.to_byte());
+        buffer.extend_from_slice(&config.timezone.to_le_bytes());
+        let mut days_off = [0; Config::DAYS_OFF_SIZE];
+        let value = config.days_off.to_string();
+        days_off[..value.len()].copy_from_slice(value.as_bytes());
+        buffer.extend_from_slice(&days_off);
+        buffer.push(config.utc_fallback_warned.into());
+        buffer
     }
 }
 
@@ -68,6 +567,113 @@ impl default::Default for Config {
         Self {
             print_color: true,
             ls_default_period: time::Period::Weeks(2),
+            relative_dates: false,
+            work_days: time::Weekdays::work_week(),
+            day_target: time::Duration::zero(),
+            week_start: time::Weekday::Monday,
+            csv_delimiter: pnch::Delimiter::Comma,
+            pager: Pager::Auto,
+            template: None,
+            include_open_in_totals: false,
+            show_weekday: true,
+            time_format: time::TimeFormat::H24,
+            round: time::Rounding::None,
+            precision: time::Precision::Minutes,
+            duration_format: time::DurationFormat::Clock,
+            timezone: time::Timezone::Local,
+            days_off: time::DaysOff::default(),
+            utc_fallback_warned: false,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(values: &[(&str, &str)]) -> Vec<(String, String)> {
+        values.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect()
+    }
+
+    #[test]
+    fn with_changes_lists_every_change() {
+        let (config, changes) = Config::default()
+            .with_changes(&pairs(&[("print-color", "false"), ("week-start", "sun")]))
+            .unwrap();
+        assert!(!config.print_color);
+        assert_eq!(config.week_start, time::Weekday::Sunday);
+        let changes = changes.iter().map(|change| change.to_string()).collect::<Vec<_>>();
+        assert_eq!(changes, ["print-color: true -> false", "week-start: mon -> sun"]);
+    }
+
+    #[test]
+    fn with_changes_fails_on_the_second_pair() {
+        let config = Config::default();
+        assert!(config.with_changes(&pairs(&[("print-color", "false"), ("not-a-key", "1")])).is_err());
+        assert!(config.with_changes(&pairs(&[("print-color", "false"), ("round", "7m sideways")])).is_err());
+    }
+
+    #[test]
+    fn reset_restores_the_defaults() {
+        let (config, _) = Config::default()
+            .with_changes(&pairs(&[("print-color", "false"), ("relative-dates", "true")]))
+            .unwrap();
+        let (reset, changes) = config.reset(Some("print-color")).unwrap();
+        assert!(reset.print_color);
+        assert!(reset.relative_dates);
+        assert_eq!(changes.len(), 1);
+        let (reset, _) = config.reset(None).unwrap();
+        assert!(!reset.relative_dates);
+    }
+
+    #[test]
+    fn saved_config_is_loaded_back() {
+        let storage = storage::Storage::temp("config-round-trip");
+        let (config, _) = Config::default()
+            .with_changes(&pairs(&[("pager", "more"), ("day-target", "7:30")]))
+            .unwrap();
+        config.save(&storage).unwrap();
+        let loaded = Config::load(&storage).unwrap();
+        assert_eq!(Vec::from(&loaded), Vec::from(&config));
+        assert_eq!(loaded.get("pager").unwrap(), "more");
+    }
+
+    #[test]
+    fn utc_fallback_warning_is_remembered() {
+        let storage = storage::Storage::temp("config-utc-fallback");
+        assert!(!Config::load(&storage).unwrap().utc_fallback_warned);
+        let config = Config { utc_fallback_warned: true, ..Config::default() };
+        config.save(&storage).unwrap();
+        let loaded = Config::load(&storage).unwrap();
+        assert!(loaded.utc_fallback_warned);
+        assert!(loaded.reset(None).unwrap().0.utc_fallback_warned);
+    }
+
+    #[test]
+    fn default_period_keeps_its_unit() {
+        let storage = storage::Storage::temp("config-period-unit");
+        let (config, _) = Config::default()
+            .with_changes(&pairs(&[("ls-default-period", "3m")]))
+            .unwrap();
+        config.save(&storage).unwrap();
+        assert_eq!(Config::load(&storage).unwrap().ls_default_period, time::Period::Months(3));
+    }
+
+    #[test]
+    fn config_without_header_is_migrated() {
+        let storage = storage::Storage::temp("config-v0");
+        let (config, _) = Config::default()
+            .with_changes(&pairs(&[("print-color", "false"), ("pager", "more")]))
+            .unwrap();
+        let current = Vec::from(&config);
+        let v0 = [&current[..1], &21u32.to_le_bytes(), &current[1 + time::Period::SIZE..]].concat();
+        storage.save(Config::CONFIG_FILE_NAME, &v0, "config").unwrap();
+
+        let loaded = Config::load(&storage).unwrap();
+        assert_eq!(loaded.ls_default_period, time::Period::Days(21));
+        assert!(!loaded.print_color);
+        assert_eq!(loaded.get("pager").unwrap(), "more");
+        assert_eq!(storage.load(&format::Format::backup_file_name(Config::CONFIG_FILE_NAME, 0)).unwrap(), v0);
+        assert_eq!(Config::FORMAT.version(&storage, Config::CONFIG_FILE_NAME).unwrap(), 1);
+    }
+}
@@ -0,0 +1,165 @@
+use crate::{storage, time, tag, pnch, error::GlobalError};
+use colored::*;
+
+/// A problem found in the databases.
+pub struct Finding {
+    /// The id of the pnch with the problem, when the problem is about a single pnch.
+    pub id: Option<u32>,
+    pub problem: String,
+    /// How to fix the problem.
+    pub fix: String,
+    /// The problem is fixed by `pnch doctor --fix`.
+    pub fixable: bool,
+}
+
+impl Finding {
+    fn new(id: Option<u32>, problem: String, fix: String) -> Self {
+        Self { id, problem, fix, fixable: false }
+    }
+
+    fn fixable(id: Option<u32>, problem: String, fix: String) -> Self {
+        Self { id, problem, fix, fixable: true }
+    }
+}
+
+impl std::fmt::Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.id {
+            Some(id) => writeln!(f, "  #{id} > {}", self.problem)?,
+            None => writeln!(f, "  {}", self.problem)?,
+        }
+        write!(f, "    {} {}", "fix:".green(), self.fix)
+    }
+}
+
+/// Check the consistency of the databases and return every problem found.
+///
+/// The pnchs are expected to be loaded and sorted, the other checks read the databases directly
+/// because the problems they look for are hidden once the databases are loaded.
+pub fn check(storage: &storage::Storage, tags: &tag::Tags, pnchs: &pnch::Pnchs, today: &time::Date) -> Result<Vec<Finding>, GlobalError> {
+    let mut findings = Vec::new();
+
+    let trailing_bytes = pnch::Pnchs::stat(storage)?.trailing_bytes;
+    if trailing_bytes > 0 {
+        findings.push(Finding::fixable(
+            None,
+            format!("The entries database ends with {trailing_bytes} bytes that do not form a complete entry."),
+            String::from("`pnch doctor --fix` drops them."),
+        ));
+    }
+    let trailing_bytes = tag::Tags::trailing_bytes(storage)?;
+    if trailing_bytes > 0 {
+        findings.push(Finding::fixable(
+            None,
+            format!("The tags database ends with {trailing_bytes} bytes that do not form a complete tag."),
+            String::from("`pnch doctor --fix` drops them."),
+        ));
+    }
+    for (id, tag_id) in pnch::Pnchs::unknown_tags(storage, tags)? {
+        findings.push(Finding::new(
+            Some(id),
+            format!("The entry references the tag id {tag_id}, which does not exist."),
+            format!("`pnch edit --id {id} \"tag/description\"`"),
+        ));
+    }
+
+    findings.extend(future_dated(pnchs, today));
+    for pnch in pnchs.0.iter() {
+        if !pnch._in.is_valid() {
+            findings.push(Finding::fixable(
+                Some(pnch.id),
+                format!("The start time {} is not a valid time.", pnch._in),
+                format!("`pnch doctor --fix` changes it to {}.", pnch._in.clamped()),
+            ));
+        }
+        // The end of a pnch can be on the next day.
+        if let Some(out) = pnch.out.filter(|out| !out.is_valid_out()) {
+            findings.push(Finding::fixable(
+                Some(pnch.id),
+                format!("The end time {out} is not a valid time."),
+                format!("`pnch doctor --fix` changes it to {}.", out.clamped_out()),
+            ));
+        }
+        if let Some(out) = pnch.out.filter(|_| pnch.is_inconsistent()) {
+            findings.push(Finding::new(
+                Some(pnch.id),
+                format!("The entry ends at {out}, before it starts at {}, it is not counted in the totals.", pnch._in),
+                format!("`pnch edit --id {} --out hh:mm`", pnch.id),
+            ));
+        }
+    }
+
+    for pair in pnchs.0.windows(2) {
+        let (previous, next) = (&pair[0], &pair[1]);
+        match previous.out {
+            Some(out) if previous.date == next.date && out > next._in => {
+                findings.push(Finding::new(
+                    Some(next.id),
+                    format!("The entry starts at {}, before the entry #{} ends at {out}.", next._in, previous.id),
+                    format!("`pnch edit --id {} --in {out}`", next.id),
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    let open = pnchs.0
+        .iter()
+        .filter(|pnch| pnch.out.is_none())
+        .collect::<Vec<_>>();
+    if let Some((last, others)) = open.split_last() {
+        for pnch in others {
+            findings.push(Finding::new(
+                Some(pnch.id),
+                format!("The entry is open, but only the entry #{} should be.", last.id),
+                format!("`pnch edit --id {} --out hh:mm`", pnch.id),
+            ));
+        }
+    }
+    Ok(findings)
+}
+
+/// The pnchs dated after `today`, see `pnch ls --check`. They are most likely typos, and they are
+/// never listed by the default period of `pnch ls`.
+pub fn future_dated(pnchs: &pnch::Pnchs, today: &time::Date) -> Vec<Finding> {
+    pnchs.0
+        .iter()
+        .filter(|pnch| pnch.date > *today)
+        .map(|pnch| Finding::new(
+            Some(pnch.id),
+            format!("The entry is dated in the future, on {}.", pnch.date),
+            format!("`pnch edit --id {} --date yyyy-mm-dd`", pnch.id),
+        ))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_dated_in_the_future_are_found() {
+        let storage = storage::Storage::temp("doctor-future");
+        let tags = tag::Tags::load(&storage).unwrap();
+        let today = "2024-05-13".parse::<time::Date>().unwrap();
+        let clock = time::Clock::fixed(&today, "9:00".parse().unwrap(), time::Offset::from_minutes(0));
+        let mut pnchs = pnch::Pnchs(Vec::new());
+        for (date, description) in [("2024-05-13", "today"), ("2024-05-14", "tomorrow")] {
+            let mut pnch = pnch::Pnch::new(pnchs.0.len() as u32, &clock, "9:00".parse().unwrap(), None, Some(description.to_owned()));
+            pnch.date = date.parse().unwrap();
+            pnch.out = Some("10:00".parse().unwrap());
+            pnchs.0.push(pnch);
+        }
+
+        let findings = future_dated(&pnchs, &today);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].id, Some(1));
+        assert_eq!(findings[0].problem, "The entry is dated in the future, on 2024-05-14.");
+        assert_eq!(findings[0].fix, "`pnch edit --id 1 --date yyyy-mm-dd`");
+        assert!(!findings[0].fixable);
+
+        let findings = check(&storage, &tags, &pnchs, &today).unwrap();
+        assert_eq!(findings.iter().map(|finding| finding.id).collect::<Vec<_>>(), vec![Some(1)]);
+        assert!(future_dated(&pnchs, &"2024-05-14".parse().unwrap()).is_empty());
+    }
+}
@@ -1,4 +1,4 @@
-use crate::time;
+use crate::{config, time, tag};
 use std::{fmt, error};
 use colored::*;
 
@@ -27,7 +27,7 @@ impl GlobalError {
     }
 
     pub fn desc_only_tag(tag: String) -> Self {
-        let mut error = format!("You must specify a description with your tag.\n");
+        let mut error = String::from("You must specify a description with your tag.\n");
         error.push_str(&format!("    tag: {tag}\n    description: not specified"));
         let hint = String::from("To specify a description, add content after the first forward slash in `pnch in \"my tag/my desription\"`");
         Self {
@@ -43,6 +43,27 @@ impl GlobalError {
         }
     }
 
+    pub fn format_too_new(typ: &str, version: u16, supported: u16) -> Self {
+        Self {
+            error: Some(format!("The {typ} database is in the format version {version}, but this version of pnch only reads the versions up to {supported}.")),
+            hint: Some(String::from("The database was written by a newer version of pnch, update pnch to read it."))
+        }
+    }
+
+    pub fn fs_not_replaced(typ: &str) -> Self {
+        Self {
+            error: Some(format!("Could not replace the {typ} database, it was left as it was.")),
+            hint: Some(String::from("The changes were not saved. Check that the data directory can be written to."))
+        }
+    }
+
+    pub fn transaction_interrupted(file: &str) -> Self {
+        Self {
+            error: Some(format!("Could not finish saving the changes, `{file}` was not replaced.")),
+            hint: Some(String::from("The changes are saved the next time pnch runs. Check that the data directory can be written to."))
+        }
+    }
+
     pub fn desc_already_specified(tag: &str, description: &str) -> Self {
         let mut error = String::from("A tag and message are already link to the entry.\n");
         error.push_str(&format!("    tag: {tag}"));
@@ -70,6 +91,13 @@ impl GlobalError {
         }
     }
 
+    pub fn pnch_reopen_not_today(id: u32, date: &time::Date) -> Self {
+        Self {
+            error: Some(format!("The entry #{id} is dated {date}, only an entry of today can be reopened.")),
+            hint: Some(format!("To update the out time of the entry, use `pnch edit --id {id} --out hh:mm`")),
+        }
+    }
+
     pub fn pnch_not_exists() -> Self {
         Self {
             error: Some(String::from("No pnch exists.")),
@@ -77,6 +105,83 @@ impl GlobalError {
         }
     }
 
+    pub fn pnch_id_not_exists(id: u32) -> Self {
+        Self {
+            error: Some(format!("The entry #{id} does not exist.")),
+            hint: Some(String::from("To list the entries with their id, use `pnch ls`")),
+        }
+    }
+
+    pub fn pnch_ids_not_exist(ids: &str) -> Self {
+        Self {
+            error: Some(format!("None of the entries {ids} exist.")),
+            hint: Some(String::from("To list the entries with their id, use `pnch ls`")),
+        }
+    }
+
+    pub fn pnch_merge_same(id: u32) -> Self {
+        Self {
+            error: Some(format!("The entry #{id} cannot be merged with itself.")),
+            hint: None,
+        }
+    }
+
+    pub fn pnch_merge_open(id: u32) -> Self {
+        Self {
+            error: Some(format!("The entry #{id} is open and cannot be merged with a later entry.")),
+            hint: None,
+        }
+    }
+
+    pub fn pnch_merge_dates(first: u32, second: u32) -> Self {
+        Self {
+            error: Some(format!("The entries #{first} and #{second} are not on the same date.")),
+            hint: Some(String::from("Only entries of the same day can be merged.")),
+        }
+    }
+
+    pub fn pnch_merge_overlap(first: u32, second: u32) -> Self {
+        Self {
+            error: Some(format!("The entry #{second} starts before the entry #{first} ends.")),
+            hint: Some(format!("To fix the start time first, use `pnch edit --id {second} --in hh:mm`")),
+        }
+    }
+
+    pub fn pnch_merge_tags(first: u32, second: u32) -> Self {
+        Self {
+            error: Some(format!("The entries #{first} and #{second} have different tags.")),
+            hint: Some(String::from("To merge them anyway and keep the tag of the first entry, use `--force`")),
+        }
+    }
+
+    pub fn pnch_merge_not_adjacent(first: u32, second: u32) -> Self {
+        Self {
+            error: Some(format!("Other entries are between the entries #{first} and #{second}.")),
+            hint: Some(String::from("To merge them anyway and keep the entries in between, use `--force`")),
+        }
+    }
+
+    pub fn break_already_started(since: time::Time) -> Self {
+        Self {
+            error: Some(format!("A break is already started since {since}.")),
+            hint: Some(String::from("To end the break, use `pnch resume`")),
+        }
+    }
+
+    pub fn break_not_started() -> Self {
+        Self {
+            error: Some(String::from("No break was started.")),
+            hint: Some(String::from("To start a break in the open entry, use `pnch break`")),
+        }
+    }
+
+    pub fn note_too_long(max: usize) -> Self {
+        Self {
+            error: Some(format!("The note is too long, a note can be at most {max} bytes.")),
+            hint: Some(String::from("Split the note in many notes, with multiple `pnch note ...`")),
+        }
+    }
+
     pub fn pnch_not_open() -> Self {
         Self {
             error: Some(String::from("No pnch seems to be opened.")),
@@ -84,6 +189,13 @@ impl GlobalError {
         }
     }
 
+    pub fn output_write(path: &std::path::Path) -> Self {
+        Self {
+            error: Some(format!("Could not write the output to `{}`.", path.display())),
+            hint: Some(String::from("Check that the directory exists and that the file can be written.")),
+        }
+    }
+
     pub fn formatting(typ: &str) -> Self {
         Self {
             error: Some(format!("Could not format data with the formatting option `{typ}`")),
@@ -91,17 +203,160 @@ impl GlobalError {
         }
     }
 
+    pub fn pattern_invalid(pattern: &str, reason: &str) -> Self {
+        Self {
+            error: Some(format!("The regular expression `{pattern}` is not valid, {reason}.")),
+            hint: Some(String::from("To search for the text as is, remove `--regex`")),
+        }
+    }
+
+    pub fn template_unknown_placeholder(name: &str, names: &str) -> Self {
+        Self {
+            error: Some(format!("The placeholder `{{{name}}}` of the template does not exist.")),
+            hint: Some(format!("The placeholders are {names}. To print a brace, double it like `{{{{`.")),
+        }
+    }
+
+    pub fn template_unclosed_placeholder(template: &str) -> Self {
+        Self {
+            error: Some(format!("A placeholder of the template `{template}` is not closed.")),
+            hint: Some(String::from("Close the placeholder with `}`, or double the brace like `{{` to print it.")),
+        }
+    }
+
+    pub fn rm_no_filter() -> Self {
+        Self {
+            error: Some(String::from("No filter was specified, every entry would be removed.")),
+            hint: Some(String::from("To remove the entries of a period, use `pnch rm --from yyyy-mm-dd --to yyyy-mm-dd`")),
+        }
+    }
+
+    pub fn ls_empty_days_unsorted() -> Self {
+        Self {
+            error: Some(String::from("The empty days can only be shown between entries sorted by date.")),
+            hint: Some(String::from("Remove `--sort` or use `--sort date`.")),
+        }
+    }
+
     pub fn ls_uncomplete_range() -> Self {
         Self {
-            error: Some(format!("The specified range was not complete.")),
+            error: Some(String::from("The specified range was not complete.")),
             hint: Some(String::from("When defining a range both the `--from DATE` and `--to DATE` should be specified.")),
         }
     }
 
+    pub fn ls_template_missing() -> Self {
+        Self {
+            error: Some(String::from("The template format needs a template.")),
+            hint: Some(String::from("To specify one, use `--template \"{date} {in}-{out} {description}\"` or set a default with `pnch config template \"...\"`")),
+        }
+    }
+
     pub fn pnch_out_before_in(_in: time::Time, out: time::Time) -> Self {
         Self {
             error: Some(format!("The `out` time cannot be before the `in` time. (in: {_in}, out: {out})")),
-            hint: None
+            hint: Some(String::from("When the entry ends after midnight, close it with `pnch out --time hh:mm --next-day`")),
+        }
+    }
+
+    pub fn pnch_split_open() -> Self {
+        Self {
+            error: Some(String::from("An open entry cannot be split.")),
+            hint: Some(String::from("To split the open entry when pnching out, use `pnch out --split ...`")),
+        }
+    }
+
+    pub fn pnch_split_out_of_range(_in: time::Time, out: time::Time, at: time::Time) -> Self {
+        Self {
+            error: Some(format!("The entry cannot be split at {at}. (in: {_in}, out: {out})")),
+            hint: Some(String::from("The time of the split must be strictly between the `in` and `out` times.")),
+        }
+    }
+
+    pub fn split_wrong_duration(specified: time::Duration, total: time::Duration) -> Self {
+        Self {
+            error: Some(format!("The parts of the split add up to {specified}, but the entry lasts {total}.")),
+            hint: Some(String::from("The parts must add up to the duration of the entry, or one of the parts must be `rest`.")),
+        }
+    }
+
+    pub fn time_before_midnight(ago: time::Duration, now: time::Time) -> Self {
+        Self {
+            error: Some(format!("Going back {ago} from {now} would cross midnight.")),
+            hint: Some(String::from("For a time before midnight, specify it as `hh:mm` and set the date with `pnch edit --date yyyy-mm-dd`")),
+        }
+    }
+
+    pub fn date_ambiguous(date: &str, format_hint: &str) -> Self {
+        Self {
+            error: Some(format!("The year of the date {date} cannot be found, it must have 4 digits.")),
+            hint: Some(format!("The format should be {format_hint}")),
+        }
+    }
+
+    pub fn date_not_in_calendar(date: &str, format_hint: &str) -> Self {
+        Self {
+            error: Some(format!("The date {date} does not exist in the calendar.")),
+            hint: Some(format!("The format should be {format_hint}, with a month from 1 to 12 and a day of the month")),
+        }
+    }
+
+    /// Tell where the record that could not be decoded is in its file.
+    pub fn in_record(self, file: &str, index: usize, size: usize) -> Self {
+        let location = format!("It is the entry #{index} of `{file}`, from byte {} to {}.", index * size, (index + 1) * size);
+        Self {
+            error: Some(match self.error {
+                Some(error) => format!("{error}\n  {location}"),
+                None => location,
+            }),
+            hint: Some(String::from("The file is probably corrupted. Fix or remove the bytes of the entry, or restore the file from a backup.")),
+        }
+    }
+
+    /// Tell which row of an imported file could not be read.
+    pub fn in_row(self, file: &str, row: usize) -> Self {
+        let location = format!("It is the row {row} of `{file}`.");
+        Self {
+            error: Some(match self.error {
+                Some(error) => format!("{error}\n  {location}"),
+                None => location,
+            }),
+            hint: self.hint,
+        }
+    }
+
+    pub fn import_read(path: &std::path::Path) -> Self {
+        Self {
+            error: Some(format!("Could not read the file `{}`.", path.display())),
+            hint: Some(String::from("Check that the file exists, that it can be read and that it is utf-8.")),
+        }
+    }
+
+    pub fn import_columns(count: usize, columns: &[&str]) -> Self {
+        Self {
+            error: Some(format!("The row has {count} columns instead of {}.", columns.len())),
+            hint: Some(format!("The columns should be the ones of `pnch ls --format csv`: {}", columns.join(", "))),
+        }
+    }
+
+    pub fn import_open() -> Self {
+        Self {
+            error: Some(String::from("The entry of the row is open, it has no end time.")),
+            hint: Some(String::from("Only closed entries can be imported, add the end time of the entry.")),
+        }
+    }
+
+    pub fn date_in_future(date: &time::Date, today: &time::Date) -> Self {
+        Self {
+            error: Some(format!("The date {date} is in the future (today is {today}).")),
+            hint: Some(String::from("To use a date in the future anyway, add `--force`")),
+        }
+    }
+
+    pub fn time_in_future(time: time::Time, now: time::Time) -> Self {
+        Self {
+            error: Some(format!("The time {time} is in the future (it is {}).", now.with_seconds(0))),
+            hint: Some(String::from("To use a time in the future anyway, add `--allow-future`")),
         }
     }
 
@@ -115,7 +370,81 @@ impl GlobalError {
     pub fn config_invalid_key(key: &str) -> Self {
         Self {
             error: Some(format!("`{key}` is not a valid configuration key")),
-            hint: Some(String::from("Valid keys are `print-color` and `ls-default-period`"))
+            hint: Some(format!("Valid keys are {}", config::Config::key_names()))
+        }
+    }
+
+    pub fn tag_not_exists(tag: &str) -> Self {
+        Self {
+            error: Some(format!("The tag `{tag}` does not exist.")),
+            hint: Some(String::from("To list existing tags, use `pnch tags`")),
+        }
+    }
+
+    pub fn tag_no_match(tag: &str, suggestions: &[&tag::Tag]) -> Self {
+        let hint = match suggestions {
+            [] => String::from("To list existing tags, use `pnch tags`"),
+            suggestions => {
+                let names = suggestions
+                    .iter()
+                    .map(|tag| format!("`{}`", tag.tag))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!("Did you mean {names}? To list existing tags, use `pnch tags`")
+            }
+        };
+        Self {
+            error: Some(format!("No tag starts with `{tag}`.")),
+            hint: Some(hint),
+        }
+    }
+
+    pub fn tag_already_exists(tag: &str) -> Self {
+        Self {
+            error: Some(format!("The tag `{tag}` already exists.")),
+            hint: Some(format!("To move all the entries of a tag to `{tag}`, use `pnch tags merge <tag> {tag}`")),
+        }
+    }
+
+    pub fn tag_same(tag: &str) -> Self {
+        Self {
+            error: Some(format!("The entries of the tag `{tag}` cannot be moved to the same tag.")),
+            hint: Some(String::from("Specify a different tag to move the entries to.")),
+        }
+    }
+
+    pub fn tag_empty() -> Self {
+        Self {
+            error: Some(String::from("The name of a tag cannot be empty.")),
+            hint: Some(String::from("Specify a name with at least one character that is not a space.")),
+        }
+    }
+
+    pub fn tag_too_long(tag: &str, max: usize) -> Self {
+        Self {
+            error: Some(format!("The tag `{tag}` is too long.")),
+            hint: Some(format!("A tag can be at most {max} bytes long.")),
+        }
+    }
+
+    pub fn profile_invalid(profile: &str) -> Self {
+        Self {
+            error: Some(format!("`{profile}` is not a valid profile name.")),
+            hint: Some(String::from("A profile name can only contain letters, numbers, `-` and `_`.")),
+        }
+    }
+
+    pub fn config_missing_value(key: &str) -> Self {
+        Self {
+            error: Some(format!("No value was specified for the configuration key `{key}`")),
+            hint: Some(String::from("Keys and values are specified in pairs: `pnch config set key value [key value]...`"))
+        }
+    }
+
+    pub fn prompt() -> Self {
+        Self {
+            error: Some(String::from("Could not read the answer.")),
+            hint: Some(String::from("To answer yes without being asked, use `--yes`."))
         }
     }
 }
@@ -123,7 +452,7 @@ impl GlobalError {
 impl From<std::string::FromUtf8Error> for GlobalError {
     fn from(_error: std::string::FromUtf8Error) -> Self {
         Self {
-            error: Some(format!("Could not decode a string from the database.")),
+            error: Some(String::from("Could not decode a string from the database.")),
             hint: Some(String::from("This is probably a bug, you should report it to the bug tracker."))
         }
     }
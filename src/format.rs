@@ -0,0 +1,168 @@
+use std::io::Read;
+use crate::{storage, error::GlobalError};
+
+/// The format of a database, like the pnchs, the tags and the notes databases.
+///
+/// A database starts with a header: magic bytes naming the database, followed by the version of
+/// its format. The first databases had no header, they are the version 0 of the format. They are
+/// detected by their length, a multiple of the size of their records. A database in an older
+/// version is migrated when it is loaded, after a copy of it is kept next to it with a suffix
+/// naming its version, like `.v0.bak`.
+pub struct Format {
+    /// The bytes starting the database.
+    magic: [u8; Self::MAGIC_SIZE],
+    /// The version of the format written by this version of pnch.
+    version: u16,
+    /// size of a record of the version 0 in bytes, 1 for records of different sizes
+    v0_record_size: usize,
+    /// The name of the database in the messages.
+    what: &'static str,
+    /// Convert the records of a database in an older version, given the storage, the name of the
+    /// database and its version, to the current version.
+    migrate: Migrate,
+}
+
+/// See `Format::migrate`.
+pub type Migrate = fn(&storage::Storage, &str, u16, &[u8]) -> Result<Vec<u8>, GlobalError>;
+
+impl Format {
+    /// size of the magic bytes
+    const MAGIC_SIZE: usize = 4;
+    /// size of the version field
+    const VERSION_SIZE: usize = 2;
+    /// size of the header in bytes
+    const HEADER_SIZE: usize = Self::MAGIC_SIZE + Self::VERSION_SIZE;
+
+    pub const fn new(magic: [u8; Self::MAGIC_SIZE], version: u16, v0_record_size: usize, what: &'static str, migrate: Migrate) -> Self {
+        Self { magic, version, v0_record_size, what, migrate }
+    }
+
+    /// The migration of a database whose records did not change between the versions.
+    pub fn same_records(_: &storage::Storage, _: &str, _: u16, records: &[u8]) -> Result<Vec<u8>, GlobalError> {
+        Ok(records.to_vec())
+    }
+
+    /// The version of the format of a database and its records, without the header.
+    fn split<'a>(&self, buffer: &'a [u8]) -> Result<(u16, &'a [u8]), GlobalError> {
+        let Some(rest) = buffer.strip_prefix(&self.magic) else {
+            return Ok((0, buffer));
+        };
+        let (version_bytes, records) = rest
+            .split_at_checked(Self::VERSION_SIZE)
+            .ok_or_else(|| GlobalError::wrong_byte_len("header", buffer.len(), Self::HEADER_SIZE))?;
+        let version = u16::from_le_bytes(version_bytes.try_into().expect("split_at_checked returns the right size"));
+        if version > self.version {
+            return Err(GlobalError::format_too_new(self.what, version, self.version));
+        }
+        Ok((version, records))
+    }
+
+    /// The records of a database, without its header. A database in an older version of the
+    /// format is migrated first.
+    ///
+    /// A database without a header whose length is not a multiple of the size of its records is
+    /// damaged, its records are migrated but not saved, for `pnch doctor`.
+    pub fn load(&self, storage: &storage::Storage, file: &str) -> Result<Vec<u8>, GlobalError> {
+        let buffer = storage.load(file)?;
+        let (version, records) = self.split(&buffer)?;
+        if version == self.version || records.is_empty() {
+            return Ok(records.to_vec());
+        }
+        let migrated = (self.migrate)(storage, file, version, records)?;
+        if version == 0 && records.len() % self.v0_record_size != 0 {
+            return Ok(migrated);
+        }
+        let backup = Self::backup_file_name(file, version);
+        storage.save(&backup, &buffer, self.what)?;
+        storage.save(file, &self.with_header(&migrated), self.what)?;
+        eprintln!("The {} database was migrated to the format version {}, a copy of it was kept in `{backup}`.",
+            self.what, self.version);
+        Ok(migrated)
+    }
+
+    /// The name of the copy of a database kept when it is migrated from `version`.
+    pub fn backup_file_name(file: &str, version: u16) -> String {
+        format!("{file}.v{version}.bak")
+    }
+
+    /// The records with the header of the current version before them.
+    pub fn with_header(&self, records: &[u8]) -> Vec<u8> {
+        let mut buffer = Vec::with_capacity(Self::HEADER_SIZE + records.len());
+        buffer.extend_from_slice(&self.magic);
+        buffer.extend_from_slice(&self.version.to_le_bytes());
+        buffer.extend_from_slice(records);
+        buffer
+    }
+
+    /// The size of the header at the start of `file`, zero for a database without a header. The
+    /// file is read from its start.
+    pub fn header_size(&self, file: &mut std::fs::File) -> std::io::Result<usize> {
+        let mut magic = [0; Self::MAGIC_SIZE];
+        match file.read_exact(&mut magic) {
+            Ok(()) if magic == self.magic => Ok(Self::HEADER_SIZE),
+            Ok(()) => Ok(0),
+            Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => Ok(0),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// The version of the format of a database, without migrating it. An empty database has no
+    /// version yet, it is written in the current version.
+    pub fn version(&self, storage: &storage::Storage, file: &str) -> Result<u16, GlobalError> {
+        let buffer = storage.load(file)?;
+        match self.split(&buffer)? {
+            (0, []) => Ok(self.version),
+            (version, _) => Ok(version),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FORMAT: Format = Format::new(*b"TEST", 2, 4, "test", Format::same_records);
+
+    #[test]
+    fn database_without_header_is_migrated() {
+        let storage = storage::Storage::temp("format-v0");
+        storage.save("test.db", b"abcdefgh", "test").unwrap();
+        assert_eq!(FORMAT.load(&storage, "test.db").unwrap(), b"abcdefgh");
+        assert_eq!(storage.load("test.db").unwrap(), FORMAT.with_header(b"abcdefgh"));
+        assert_eq!(storage.load("test.db.v0.bak").unwrap(), b"abcdefgh");
+        assert_eq!(FORMAT.version(&storage, "test.db").unwrap(), 2);
+    }
+
+    #[test]
+    fn damaged_database_without_header_is_not_saved() {
+        let storage = storage::Storage::temp("format-v0-damaged");
+        storage.save("test.db", b"abcdefghi", "test").unwrap();
+        assert_eq!(FORMAT.load(&storage, "test.db").unwrap(), b"abcdefghi");
+        assert_eq!(storage.load("test.db").unwrap(), b"abcdefghi");
+        assert!(!storage.dir().join("test.db.v0.bak").exists());
+    }
+
+    #[test]
+    fn older_version_is_migrated() {
+        fn upper(_: &storage::Storage, _: &str, version: u16, records: &[u8]) -> Result<Vec<u8>, GlobalError> {
+            assert_eq!(version, 1);
+            Ok(records.to_ascii_uppercase())
+        }
+        let format = Format::new(*b"TEST", 2, 4, "test", upper);
+        let storage = storage::Storage::temp("format-v1");
+        let v1 = [&b"TEST"[..], &1u16.to_le_bytes(), b"abcd"].concat();
+        storage.save("test.db", &v1, "test").unwrap();
+        assert_eq!(format.load(&storage, "test.db").unwrap(), b"ABCD");
+        assert_eq!(storage.load("test.db").unwrap(), format.with_header(b"ABCD"));
+        assert_eq!(storage.load("test.db.v1.bak").unwrap(), v1);
+    }
+
+    #[test]
+    fn newer_version_is_an_error() {
+        let storage = storage::Storage::temp("format-too-new");
+        let v3 = [&b"TEST"[..], &3u16.to_le_bytes(), b"abcd"].concat();
+        storage.save("test.db", &v3, "test").unwrap();
+        assert!(FORMAT.load(&storage, "test.db").is_err());
+        assert_eq!(storage.load("test.db").unwrap(), v3);
+    }
+}
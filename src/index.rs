@@ -0,0 +1,126 @@
+use crate::{storage, time, tag, pnch, error::GlobalError};
+
+/// The total of the pnchs of a day.
+pub struct DayTotal {
+    pub date: time::Date,
+    /// Total duration of the closed pnchs of the day.
+    pub duration: time::Duration,
+    /// Number of pnchs of the day, including the open ones.
+    pub count: u32,
+}
+
+impl DayTotal {
+    /// size of the date field in bytes
+    const DATE_SIZE: usize = time::Date::SIZE;
+    /// size of the duration field in bytes
+    const DURATION_SIZE: usize = 4;
+    /// size of the count field in bytes
+    const COUNT_SIZE: usize = 4;
+    /// total size of a day total in bytes
+    const SIZE: usize = Self::DATE_SIZE + Self::DURATION_SIZE + Self::COUNT_SIZE;
+}
+
+impl std::convert::TryFrom<&[u8]> for DayTotal {
+    type Error = GlobalError;
+    fn try_from(buffer: &[u8]) -> Result<Self, Self::Error> {
+        if buffer.len() != Self::SIZE {
+            return Err(GlobalError::wrong_byte_len("day total", buffer.len(), Self::SIZE));
+        }
+        let (date_bytes, buffer) = buffer.split_at(Self::DATE_SIZE);
+        let (duration_bytes, count_bytes) = buffer.split_at(Self::DURATION_SIZE);
+        let duration_bytes = duration_bytes
+            .try_into()
+            .expect("split_at panics if not correct size");
+        let count_bytes = count_bytes
+            .try_into()
+            .expect("split_at panics if not correct size");
+        Ok(Self {
+            date: date_bytes.try_into()?,
+            duration: time::Duration { minutes: i32::from_le_bytes(duration_bytes) as i64, seconds: 0 },
+            count: u32::from_le_bytes(count_bytes),
+        })
+    }
+}
+
+impl From<&DayTotal> for Vec<u8> {
+    fn from(total: &DayTotal) -> Self {
+        let mut buffer = Vec::with_capacity(DayTotal::SIZE);
+        buffer.extend_from_slice(&total.date.to_le_bytes());
+        buffer.extend_from_slice(&(total.duration.minutes as i32).to_le_bytes());
+        buffer.extend_from_slice(&total.count.to_le_bytes());
+        buffer
+    }
+}
+
+/// An index of the total of each day, so commands that only need the totals per day don't have to
+/// load every pnch.
+///
+/// The index is written every time the pnchs are saved. It starts with the number of pnchs in the
+/// pnchs database when it was written and it is rebuilt from the pnchs instead of being trusted
+/// when that number does not match the database anymore, or when the index is missing.
+pub struct DayIndex(Vec<DayTotal>);
+
+impl DayIndex {
+    pub const INDEX_FILE_NAME: &'static str = "days.idx";
+    /// size of the header containing the number of pnchs
+    const HEADER_SIZE: usize = 4;
+
+    pub fn build(pnchs: &pnch::Pnchs) -> Self {
+        Self(pnchs
+            .group_by(|pnch| pnch.date.clone())
+            .into_iter()
+            .map(|group| DayTotal {
+                duration: group.duration(),
+                count: group.pnchs.len() as u32,
+                date: group.key,
+            })
+            .collect())
+    }
+
+    /// Load the index, rebuilding it if it does not match the pnchs database.
+    pub fn load(storage: &storage::Storage, tags: &tag::Tags) -> Result<Self, GlobalError> {
+        let buffer = storage.load(Self::INDEX_FILE_NAME)?;
+        let records = pnch::Pnchs::count(storage)?;
+        match buffer.split_at_checked(Self::HEADER_SIZE) {
+            Some((header, totals)) if totals.len() % DayTotal::SIZE == 0 => {
+                let header = header.try_into().expect("split_at_checked returns the right size");
+                if u32::from_le_bytes(header) as usize == records {
+                    return Ok(Self(totals
+                        .chunks_exact(DayTotal::SIZE)
+                        .map(DayTotal::try_from)
+                        .collect::<Result<Vec<_>, GlobalError>>()?));
+                }
+            }
+            _ => {}
+        }
+        let pnchs = pnch::Pnchs::load(storage, tags)?;
+        let index = Self::build(&pnchs);
+        index.save(storage, pnchs.0.len())?;
+        Ok(index)
+    }
+
+    /// Save the index of a pnchs database containing `records` pnchs.
+    pub fn save(&self, storage: &storage::Storage, records: usize) -> Result<(), GlobalError> {
+        let mut content = (records as u32).to_le_bytes().to_vec();
+        content.extend(self.0.iter().flat_map(Vec::from));
+        storage.save(Self::INDEX_FILE_NAME, &content, "index")
+    }
+
+    /// Remove the index, so it is rebuilt the next time it is loaded.
+    pub fn invalidate(storage: &storage::Storage) -> Result<(), GlobalError> {
+        let path = storage.build_path(Self::INDEX_FILE_NAME)?;
+        match std::fs::remove_file(path) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                Err(GlobalError::fs("remove", "index"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn get(&self, date: &time::Date) -> Option<&DayTotal> {
+        self.0
+            .binary_search_by(|total| total.date.cmp(date))
+            .ok()
+            .map(|idx| &self.0[idx])
+    }
+}
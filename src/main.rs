@@ -1,51 +1,299 @@
-///! Track your time working on projects directly from the CLI. Categorize and add a description to
-///! what you did and later export your timesheet to different formats.
+//! Track your time working on projects directly from the CLI. Categorize and add a description to
+//! what you did and later export your timesheet to different formats.
 
 mod config;
 mod time;
 mod error;
 mod tag;
 mod pnch;
+mod index;
+mod doctor;
+mod archive;
+mod pattern;
+mod report;
+mod note;
+mod template;
+mod format;
 
 use clap::{Parser, Subcommand, Args};
+use colored::*;
 use error::GlobalError;
-use std::{fs, io::Read};
+use std::{fs, io::{IsTerminal, Read, Write}, process::ExitCode};
 
-const APP_NAME: &'static str = "pnch";
+const APP_NAME: &str = "pnch";
 
 pub mod storage {
     use super::*;
+    use std::{cell::RefCell, path::PathBuf, rc::Rc};
 
-    /// Get a file path for a file that is in the app storage.
-    pub fn build_path(file: &str) -> Result<String, GlobalError> {
-        let mut path = directories::BaseDirs::new()
-            .map_or(Err(GlobalError::fs("load", file)), |base_dirs| {
-                let mut path = base_dirs.data_dir().to_owned();
-                path.push(APP_NAME);
-                fs::create_dir_all(path.clone())
-                    .map_err(|_| GlobalError::fs("create dir", file))?;
-                Ok(path)
-            })?;
-        path.push(file);
-        match path.to_str() {
-            Some(path) => Ok(path.to_string()),
-            _ => return Err(GlobalError::fs("load", file))
-        }
-    }
-
-    /// Load the content from a file a returns it.
-    pub fn load(file: &str) -> Result<Vec<u8>, GlobalError> {
-        let path = build_path(file)?;
-        let mut buffer = Vec::new();
-        std::fs::OpenOptions::new()
-            .write(true)
-            .create(true)
-            .read(true)
-            .open(path)
-            .map_err(|_| GlobalError::fs("load", file))?
-            .read_to_end(&mut buffer)
-            .map_err(|_| GlobalError::fs("load", file))?;
-        Ok(buffer)
+    /// Where the databases are stored.
+    ///
+    /// Every profile has its own databases. The storage is resolved once per invocation, so a
+    /// command only ever reads and writes the databases of a single profile.
+    #[derive(Clone)]
+    pub struct Storage {
+        dir: PathBuf,
+        /// The profile of the databases, `None` for the default storage.
+        profile: Option<String>,
+        /// The files saved during a transaction, see `Storage::transaction`.
+        staged: Option<Rc<RefCell<Vec<String>>>>,
+    }
+
+    impl Storage {
+        const PROFILES_DIR: &'static str = "profiles";
+        /// The list of the files of a transaction being committed.
+        const JOURNAL_FILE_NAME: &'static str = "transaction.journal";
+
+        /// The storage of a profile. Without a profile, the default storage is returned.
+        pub fn new(profile: Option<&str>) -> Result<Self, GlobalError> {
+            let dir = directories::BaseDirs::new()
+                .map(|base_dirs| base_dirs.data_dir().join(APP_NAME))
+                .ok_or_else(|| GlobalError::fs("find", "storage"))?;
+            let storage = Self::in_dir(dir, profile)?;
+            storage.recover()?;
+            Ok(storage)
+        }
+
+        /// The storage of a profile, where `dir` is the default storage.
+        pub fn in_dir(mut dir: PathBuf, profile: Option<&str>) -> Result<Self, GlobalError> {
+            if let Some(profile) = profile {
+                let is_valid = profile
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+                if profile.is_empty() || !is_valid {
+                    return Err(GlobalError::profile_invalid(profile));
+                }
+                dir.push(Self::PROFILES_DIR);
+                dir.push(profile);
+            }
+            Ok(Self { dir, profile: profile.map(str::to_owned), staged: None })
+        }
+
+        /// An empty storage in the temporary directory, for the tests. `name` must be unique
+        /// among the tests, since they run in parallel.
+        #[cfg(test)]
+        pub fn temp(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("{APP_NAME}-test-{}-{name}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            Self { dir, profile: None, staged: None }
+        }
+
+        pub fn dir(&self) -> &std::path::Path {
+            &self.dir
+        }
+
+        pub fn profile(&self) -> Option<&str> {
+            self.profile.as_deref()
+        }
+
+        /// Get a file path for a file that is in the storage.
+        pub fn build_path(&self, file: &str) -> Result<String, GlobalError> {
+            fs::create_dir_all(&self.dir)
+                .map_err(|_| GlobalError::fs("create dir", file))?;
+            match self.dir.join(file).to_str() {
+                Some(path) => Ok(path.to_string()),
+                _ => Err(GlobalError::fs("load", file))
+            }
+        }
+
+        /// Load the content from a file a returns it.
+        pub fn load(&self, file: &str) -> Result<Vec<u8>, GlobalError> {
+            let path = self.build_path(file)?;
+            let path = match self.is_staged(file) {
+                true => Self::tmp_path(&path),
+                false => PathBuf::from(path),
+            };
+            let mut buffer = Vec::new();
+            std::fs::OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .read(true)
+                .open(path)
+                .map_err(|_| GlobalError::fs("load", file))?
+                .read_to_end(&mut buffer)
+                .map_err(|_| GlobalError::fs("load", file))?;
+            Ok(buffer)
+        }
+
+        /// Replace the content of a file that is in the storage. `typ` names the file in the
+        /// errors.
+        ///
+        /// The content is written to a temporary file next to it and synced to the disk before
+        /// replacing the file, so a crash or a full disk leaves the file as it was. During a
+        /// transaction, the file is only replaced when the transaction is committed.
+        pub fn save(&self, file: &str, content: &[u8], typ: &str) -> Result<(), GlobalError> {
+            let path = self.build_path(file)?;
+            let tmp = Self::tmp_path(&path);
+            let written = fs::File::create(&tmp).and_then(|mut tmp| {
+                tmp.write_all(content)?;
+                tmp.sync_all()
+            });
+            if written.is_err() {
+                let _ = fs::remove_file(&tmp);
+                return Err(GlobalError::fs("save", typ));
+            }
+            if let Some(staged) = &self.staged {
+                let mut staged = staged.borrow_mut();
+                if !staged.iter().any(|staged| staged == file) {
+                    staged.push(file.to_string());
+                }
+                return Ok(());
+            }
+            if fs::rename(&tmp, path).is_err() {
+                let _ = fs::remove_file(&tmp);
+                return Err(GlobalError::fs_not_replaced(typ));
+            }
+            self.sync_dir();
+            Ok(())
+        }
+
+        /// Run `f` with a storage where the saved files are replaced together, once `f` succeeds.
+        /// When `f` fails, none of the files are replaced.
+        ///
+        /// The files are staged next to the files they replace. Before they are moved in place,
+        /// their names are written to a journal, so a crash while they are moved is finished by
+        /// `Storage::recover` the next time pnch runs.
+        pub fn transaction<T>(&self, f: impl FnOnce(&Self) -> Result<T, GlobalError>) -> Result<T, GlobalError> {
+            let staged = Rc::new(RefCell::new(Vec::new()));
+            let result = f(&Self { staged: Some(staged.clone()), ..self.clone() });
+            let files = staged.take();
+            if result.is_err() {
+                for file in files {
+                    let _ = fs::remove_file(self.dir.join(format!("{file}.tmp")));
+                }
+                return result;
+            }
+            self.save(Self::JOURNAL_FILE_NAME, files.join("\n").as_bytes(), "transaction")?;
+            self.commit(&files)?;
+            result
+        }
+
+        /// Finish the transaction interrupted while its files were moved in place, if any.
+        pub fn recover(&self) -> Result<(), GlobalError> {
+            let Ok(journal) = fs::read_to_string(self.dir.join(Self::JOURNAL_FILE_NAME)) else {
+                return Ok(());
+            };
+            let files = journal.lines().map(String::from).collect::<Vec<_>>();
+            self.commit(&files)
+        }
+
+        /// Move the staged files in place, then remove the journal. The files already moved
+        /// before an interruption have no staged file anymore and are skipped.
+        fn commit(&self, files: &[String]) -> Result<(), GlobalError> {
+            for file in files {
+                let path = self.dir.join(file);
+                let tmp = Self::tmp_path(&path);
+                if tmp.exists() && fs::rename(&tmp, &path).is_err() {
+                    return Err(GlobalError::transaction_interrupted(file));
+                }
+            }
+            self.sync_dir();
+            fs::remove_file(self.dir.join(Self::JOURNAL_FILE_NAME))
+                .map_err(|_| GlobalError::transaction_interrupted(Self::JOURNAL_FILE_NAME))?;
+            self.sync_dir();
+            Ok(())
+        }
+
+        fn is_staged(&self, file: &str) -> bool {
+            self.staged
+                .as_ref()
+                .is_some_and(|staged| staged.borrow().iter().any(|staged| staged == file))
+        }
+
+        /// The temporary file a file is written to before it replaces it.
+        fn tmp_path(path: impl AsRef<std::path::Path>) -> PathBuf {
+            let mut tmp = path.as_ref().as_os_str().to_owned();
+            tmp.push(".tmp");
+            PathBuf::from(tmp)
+        }
+
+        /// The renames are only on the disk once the directory is synced.
+        fn sync_dir(&self) {
+            let _ = fs::File::open(&self.dir).and_then(|dir| dir.sync_all());
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn failed_write_leaves_the_file_intact() {
+            let storage = Storage::temp("save-failed-write");
+            storage.save("pnchs.db", b"old", "pnchs").unwrap();
+            // The temporary file cannot be created over a directory.
+            fs::create_dir(storage.dir().join("pnchs.db.tmp")).unwrap();
+            assert!(storage.save("pnchs.db", b"new", "pnchs").is_err());
+            assert_eq!(storage.load("pnchs.db").unwrap(), b"old");
+        }
+
+        #[test]
+        fn failed_rename_leaves_the_target_intact() {
+            let storage = Storage::temp("save-failed-rename");
+            // A file cannot replace a directory that is not empty.
+            let target = storage.dir().join("pnchs.db");
+            fs::create_dir_all(target.join("entry")).unwrap();
+            assert!(storage.save("pnchs.db", b"new", "pnchs").is_err());
+            assert!(target.join("entry").is_dir());
+            assert!(!storage.dir().join("pnchs.db.tmp").exists());
+        }
+
+        #[test]
+        fn transaction_replaces_the_files_together() {
+            let storage = Storage::temp("transaction-commit");
+            storage.save("a.db", b"old", "a").unwrap();
+            storage.save("b.db", b"old", "b").unwrap();
+            storage.transaction(|staging| {
+                staging.save("a.db", b"new", "a")?;
+                staging.save("b.db", b"new", "b")?;
+                assert_eq!(staging.load("a.db")?, b"new");
+                assert_eq!(fs::read(storage.dir().join("a.db")).unwrap(), b"old");
+                Ok(())
+            }).unwrap();
+            assert_eq!(storage.load("a.db").unwrap(), b"new");
+            assert_eq!(storage.load("b.db").unwrap(), b"new");
+            assert!(!storage.dir().join(Storage::JOURNAL_FILE_NAME).exists());
+        }
+
+        #[test]
+        fn failed_transaction_replaces_nothing() {
+            let storage = Storage::temp("transaction-failed");
+            storage.save("a.db", b"old", "a").unwrap();
+            storage.save("b.db", b"old", "b").unwrap();
+            let result = storage.transaction(|staging| {
+                staging.save("a.db", b"new", "a")?;
+                Err::<(), _>(GlobalError::fs("save", "b"))
+            });
+            assert!(result.is_err());
+            assert_eq!(storage.load("a.db").unwrap(), b"old");
+            assert_eq!(storage.load("b.db").unwrap(), b"old");
+            assert!(!storage.dir().join("a.db.tmp").exists());
+        }
+
+        #[test]
+        fn interrupted_transaction_is_recovered() {
+            let storage = Storage::temp("transaction-recover");
+            storage.save("a.db", b"old", "a").unwrap();
+            storage.save("b.db", b"old", "b").unwrap();
+            // Interrupted once `a.db` was replaced, but not `b.db` yet.
+            fs::write(storage.dir().join("a.db"), b"new").unwrap();
+            fs::write(storage.dir().join("b.db.tmp"), b"new").unwrap();
+            fs::write(storage.dir().join(Storage::JOURNAL_FILE_NAME), "a.db\nb.db").unwrap();
+            storage.recover().unwrap();
+            assert_eq!(storage.load("a.db").unwrap(), b"new");
+            assert_eq!(storage.load("b.db").unwrap(), b"new");
+            assert!(!storage.dir().join(Storage::JOURNAL_FILE_NAME).exists());
+        }
+
+        #[test]
+        fn save_replaces_the_file() {
+            let storage = Storage::temp("save-replaces");
+            storage.save("pnchs.db", b"old", "pnchs").unwrap();
+            storage.save("pnchs.db", b"new", "pnchs").unwrap();
+            assert_eq!(storage.load("pnchs.db").unwrap(), b"new");
+            assert!(!storage.dir().join("pnchs.db.tmp").exists());
+        }
     }
 }
 
@@ -54,8 +302,15 @@ pub mod storage {
 pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
+
+    /// Use the databases of another profile for this command only. Every profile has its own
+    /// entries, tags and configuration.
+    #[arg(long, global = true)]
+    pub profile: Option<String>,
 }
 
+// The command is parsed once per invocation, the size of its largest variant doesn't matter.
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand, Debug)]
 pub enum Commands {
     /// Punch in. This starts a new entry and record the time that the command was called. A tag
@@ -71,40 +326,205 @@ pub enum Commands {
     /// description, one most specify it with the following format: "my_tag/The description of my
     /// task". Everything before the forward slash is a tag and everything afterwards is the
     /// description. For more information, use `pnch out --help`.
+    ///
+    /// When the entry has no description yet and none is specified, the tag and the description
+    /// are asked for in the terminal.
+    #[command(verbatim_doc_comment)]
+    Out {
+        #[command(flatten)]
+        entry: Entry,
+
+        /// Ask for the tag and the description, even when they are already known, to correct
+        /// them.
+        #[arg(long, conflicts_with = "split")]
+        interactive: bool,
+
+        /// Split the entry in consecutive entries with different tags when pnching out. The format
+        /// is `tag[:description]=duration,...`, for example `--split "ISSUE-1=1h30m,ISSUE-2=rest"`.
+        /// The durations can be specified as `1h30m`, `2h`, `90m` or `1:30` and one of them can
+        /// be `rest` to get the remaining time. Without `rest`, the durations must add up to the
+        /// duration of the entry. Parts without a description keep the description of the entry.
+        #[arg(long, alias = "split-tag", conflicts_with = "description", verbatim_doc_comment)]
+        split: Option<pnch::Split>,
+
+        /// The entry ends on the day after it started, past midnight. For example, an entry
+        /// started at 22:00 and closed with `--time 1:30 --next-day` lasts 3:30. It is listed
+        /// under the day it started, with its end time marked `+1`.
+        #[arg(long, conflicts_with = "split")]
+        next_day: bool,
+    },
+
+    /// Punch in or out, depending on whether an entry is open, for example from a keyboard
+    /// shortcut. The open entry is closed, see `pnch out`, or a new entry is opened, see `pnch
+    /// in`. The tag and description, if specified, go to the entry that is closed or opened. The
+    /// action taken is printed.
     #[command(verbatim_doc_comment)]
-    Out(Entry),
+    Toggle(Entry),
+
+    /// Take a break. The open entry is closed, even without a description, and a new entry with
+    /// the same tag and description is opened when the break ends with `pnch break --end` or
+    /// `pnch resume`.
+    #[command(verbatim_doc_comment)]
+    Break {
+        /// End the break instead. This is the same as `pnch resume`.
+        #[arg(long)]
+        end: bool,
+
+        /// Manually specify time. The format should be `hh:mm` where `hh` represent hours and
+        /// `mm` represent minutes. The default value is the current local time.
+        #[arg(long, allow_hyphen_values = true)]
+        time: Option<time::TimeArg>,
+    },
+
+    /// End a break started with `pnch break`, by opening a new entry with the tag and description
+    /// of the entry that was closed.
+    Resume {
+        /// Manually specify time. The format should be `hh:mm` where `hh` represent hours and
+        /// `mm` represent minutes. The default value is the current local time.
+        #[arg(long, allow_hyphen_values = true)]
+        time: Option<time::TimeArg>,
+    },
+
+    /// Attach a note to an entry, for what does not fit in the description. A note can span many
+    /// lines and an entry can have many notes. The notes are printed with
+    /// `pnch ls --format list --show-notes` and are deleted with their entry.
+    #[command(verbatim_doc_comment)]
+    Note {
+        /// The text of the note.
+        text: String,
+
+        /// Specify the id of the entry. The default is the open entry or, when no entry is open,
+        /// the last entry.
+        #[arg(long)]
+        id: Option<u32>,
+    },
+
+    /// Add a closed entry in a single command, for example to add an entry for a day that was
+    /// forgotten: `pnch add "ISSUE-123/The issue was fixed" --date 2023-05-02 --in 9:00 --out
+    /// 11:30`. The open entry, if any, is left untouched. A warning is printed when the new entry
+    /// overlaps existing entries.
+    #[command(verbatim_doc_comment)]
+    Add {
+        /// The tag and description of the entry, in the "my_tag/The description" format.
+        description: pnch::Description,
+
+        /// The date of the entry with the yyyy-mm-dd format. The default is today. A date in the
+        /// future is refused unless `--force` is specified.
+        #[arg(long)]
+        date: Option<time::RelativeDate>,
+
+        /// The start time of the entry in the `hh:mm` format.
+        #[arg(long)]
+        r#in: time::TimeArg,
+
+        /// The end time of the entry in the `hh:mm` format. It must be after the start time.
+        #[arg(long)]
+        out: time::TimeArg,
+
+        /// Allow dates in the future.
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Add the entries of a csv file, in the format of `pnch ls --format csv`, for example to move
+    /// entries from another profile. The tags of the entries are created when they do not exist.
+    /// The rows dated in the future are skipped unless `--force` is specified.
+    #[command(verbatim_doc_comment)]
+    Import {
+        /// The csv file to import.
+        file: std::path::PathBuf,
+
+        /// The character separating the fields. The default is the `csv-delimiter` config.
+        #[arg(long)]
+        delimiter: Option<pnch::Delimiter>,
+
+        /// Also import the rows dated in the future.
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Copy the tag, description and times of an entry to a new entry of today, for example for a
+    /// recurring meeting. When the entry is open, only its tag and description are copied and the
+    /// new entry is opened at the current time, see `pnch in`.
+    #[command(verbatim_doc_comment)]
+    Duplicate {
+        /// The id of the entry to copy.
+        id: u32,
+
+        /// The date of the new entry with the yyyy-mm-dd format. The default is today. A date in
+        /// the future is refused unless `--force` is specified.
+        #[arg(long)]
+        date: Option<time::RelativeDate>,
+
+        /// Allow dates in the future.
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Open a closed entry of today again, for example after pnching out too early. The entry keeps
+    /// its tag and description. It cannot be reopened while another entry is open.
+    #[command(verbatim_doc_comment)]
+    Reopen {
+        /// The id of the entry to reopen.
+        id: u32,
+    },
 
     /// Edit or add the tag and description for a currently opened pnch. For more information, use
     /// One most specify it with the following format: "my_tag/The description of my task".
     /// Everything before the forward slash is a tag and everything afterwards is the description.
     /// For more information, use `pnch edit --help`.
     #[command(verbatim_doc_comment)]
-    Edit {
-        /// The description is used to describe the entry. While specifying the description, it is also
-        /// possible to specify a tag. For example, a description could be specify as
-        /// "ISSUE-123/The issue is fixed". The tag is the value specified before the forward slash
-        /// (`/`) and the description is everything after. In the example above, "ISSUE-123" would be
-        /// the tag and "The issue was fixed" would be the description of the issue.
-        #[arg(verbatim_doc_comment)]
+    Edit(EditArgs),
+
+    /// Correct the most recent closed entry, without looking up its id. The open entry, if any, is
+    /// skipped. The fields are specified like with `pnch edit` and the changed fields are printed
+    /// with their old and new values.
+    #[command(verbatim_doc_comment)]
+    Amend {
+        /// The new tag and description, in the "my_tag/The description" format.
         description: Option<pnch::Description>,
 
-        /// Specify the id for the entry to edit. The id can be found when listing entries with
-        /// `pnch ls`
+        /// The new start time of the entry in the `hh:mm` format.
+        #[arg(long, allow_hyphen_values = true)]
+        r#in: Option<time::TimeArg>,
+
+        /// The new end time of the entry in the `hh:mm` format.
+        #[arg(long, allow_hyphen_values = true)]
+        out: Option<time::TimeArg>,
+    },
+
+    /// Split a closed entry in two at the specified time. The entry ends at this time and a new
+    /// entry starts at this time and ends when the entry ended. The tag and description of the new
+    /// entry are asked for, unless `--description` is specified. Without an answer, the new
+    /// entry keeps the tag and description of the entry.
+    #[command(verbatim_doc_comment)]
+    Split {
+        /// The id of the entry to split. The id can be found when listing entries with `pnch ls`.
+        id: u32,
+
+        /// The time where the entry is split in the `hh:mm` format. It must be strictly between
+        /// the start and the end of the entry.
         #[arg(long)]
-        id: Option<u32>,
+        at: time::TimeArg,
 
-        /// Specify the new start time of the entry to edit. The format should be `hh:mm` where
-        /// `hh` represent hours and `mm` represent minutes. The default value is the current local
-        /// time.
+        /// The tag and description of the new entry, in the "my_tag/The description" format.
         #[arg(long)]
-        r#in: Option<time::Time>,
+        description: Option<pnch::Description>,
+    },
+
+    /// Merge two entries of the same day in a single entry. The merged entry starts when the first
+    /// entry starts and ends when the second entry ends. The descriptions are concatenated when
+    /// they differ.
+    #[command(verbatim_doc_comment)]
+    Merge {
+        /// The ids of the entries to merge. The ids can be found when listing entries with
+        /// `pnch ls`.
+        first: u32,
+        second: u32,
 
-        /// Specify the new start time of the entry to edit. The format should be `hh:mm` where
-        /// `hh` represent hours and `mm` represent minutes. The default value is the current local
-        /// time. This option is only valid when `--id` is specified (When it is not specified,
-        /// simply use `pnch out --time ...`).
+        /// Merge entries with different tags, or with other entries between them.
         #[arg(long)]
-        out: Option<time::Time>,
+        force: bool,
     },
 
     /// List and print pnch entries. A filter can be added to only show a subset of pnchs. For
@@ -117,38 +537,214 @@ pub enum Commands {
     /// value, use `pnch config ls-default-period "28 days"`. For more information, use `pnch ls
     /// --help`.
     #[command(verbatim_doc_comment)]
-    Ls {
-        /// Get all pnchs since the specified date in the yyyy-mm-dd format
-        #[arg(long, short)]
-        since: Option<time::Date>,
-        /// Get all pnchs for the last n period. A period can be `days`, `weeks`, `months` or
-        /// `years`.
-        #[arg(long, short)]
-        last: Option<time::Period>,
-        /// Specify a range of dates in combination with the `to` flag. Date is specified with the
-        /// yyyy-mm-dd format.
-        #[arg(long, short)]
-        from: Option<time::Date>,
-        /// Specify a range of dates in combination with the `from` flag. Date is specified with the
-        /// yyyy-mm-dd format.
-        #[arg(long, short)]
-        to: Option<time::Date>,
-        /// Filter only entries from a specific tag
+    Ls(LsArgs),
+
+    /// List the entries of the current calendar week, with the total of each day, the total of
+    /// the week and the average per day. The weeks start on monday, this can be changed with `pnch
+    /// config week-start sun`.
+    #[command(verbatim_doc_comment)]
+    Week {
+        /// The week to list, relative to the current week. For example `--offset -1` lists the
+        /// previous week.
+        #[arg(long, default_value_t = 0, allow_hyphen_values = true)]
+        offset: i64,
+        /// Count the open entries until now, like `pnch ls --include-open-duration`.
+        #[arg(long)]
+        include_open_duration: bool,
+    },
+
+    /// Search the descriptions of the entries for a text, ignoring the case. The matching entries
+    /// are printed in a table with the matches highlighted. Every entry is searched, unless
+    /// filters are specified.
+    #[command(verbatim_doc_comment)]
+    Search {
+        /// The text to search for.
+        text: String,
+
+        /// Search for a regular expression instead. The supported syntax is `.`, `*`, `+`, `?`,
+        /// classes like `[a-z]`, `\d`, `\w`, `\s`, `^`, `$` and alternatives with `|`.
+        #[arg(long)]
+        regex: bool,
+
+        #[command(flatten)]
+        filter: Filter,
+    },
+
+    /// Print the open entry and the total of today, including the open entry. When a day target
+    /// is set with `pnch config day-target 7:30`, the time remaining to reach it is printed too.
+    ///
+    /// With `--watch`, the open entry and its elapsed time are then redrawn until the entry is
+    /// closed, for example by `pnch out` in another terminal, or until Ctrl-C.
+    #[command(verbatim_doc_comment, visible_alias = "today")]
+    Status {
+        /// Keep redrawing the open entry with its elapsed time.
+        #[arg(long)]
+        watch: bool,
+
+        /// The number of seconds between two redraws.
+        #[arg(long, default_value_t = 5, requires = "watch", value_parser = clap::value_parser!(u64).range(1..))]
+        interval: u64,
+    },
+
+    /// Print the total duration of each tag. With `--per-day`, print the total of each day
+    /// instead, with the difference with the day target when one is set. With `--group-by week`,
+    /// print the total of each week, starting on the `week-start` config, with its number of days
+    /// with entries and their average.
+    /// With `--group-by month`, print the total of each month, with its number of days with
+    /// entries and its top tag.
+    /// The same filters as `pnch ls` can be used.
+    #[command(verbatim_doc_comment)]
+    Report(ReportArgs),
+
+    /// Print summary statistics of the entries: the total duration, the number of entries and of
+    /// days with entries, the average duration per day, the longest entry and the current streak
+    /// of consecutive days with entries. The same filters as `pnch ls` can be used.
+    #[command(verbatim_doc_comment)]
+    Stats {
+        #[command(flatten)]
+        filter: Filter,
+    },
+
+    /// Remove every entry matching the filters, for example `pnch rm --from 2023-05-01 --to
+    /// 2023-05-07 --tag TEST`. The number of entries matching the filters is printed and a
+    /// confirmation is asked before removing them. At least one filter must be specified.
+    #[command(verbatim_doc_comment)]
+    Rm {
+        #[command(flatten)]
+        filter: Filter,
+
+        /// Remove the entries without asking for confirmation.
+        #[arg(long)]
+        yes: bool,
+    },
+
+    /// List every tag with its id, the number of entries using it and the date it was last used.
+    /// The most used tags are listed first. Tags can also be managed with the subcommands, for
+    /// more information, use `pnch tags --help`.
+    #[command(verbatim_doc_comment, args_conflicts_with_subcommands = true)]
+    Tags {
+        #[command(subcommand)]
+        command: Option<TagsCommands>,
+
+        /// Only list the tags that are not used by any entry.
+        #[arg(long)]
+        unused: bool,
+    },
+
+    /// Move the entries before a date out of the entries database, to an archive per year in the
+    /// same directory. The archived entries are listed with `pnch ls --include-archived`.
+    #[command(verbatim_doc_comment)]
+    Archive {
+        /// Archive the entries before this date, in the yyyy-mm-dd format.
+        #[arg(long)]
+        before: time::RelativeDate,
+    },
+
+    /// Permanently remove the entries before a date, for example once they are saved elsewhere.
+    /// Unlike `pnch archive`, the entries are not kept. The number of entries and their total
+    /// duration are printed and a confirmation is asked before removing them. The tags that are
+    /// not used anymore are listed, to be removed with `pnch tags rm`.
+    #[command(verbatim_doc_comment)]
+    Prune {
+        /// Remove the entries before this date, in the yyyy-mm-dd format.
+        #[arg(long)]
+        before: time::RelativeDate,
+
+        /// Remove the entries without asking for confirmation.
         #[arg(long)]
-        tag: Option<String>,
-        /// Specify how to format the output. The value should be one of `pretty` or `csv`. The
-        /// default is `pretty`.
+        yes: bool,
+
+        /// Only print the entries that would be removed.
+        #[arg(long, conflicts_with = "yes")]
+        dry_run: bool,
+    },
+
+    /// Print where the databases are stored, with statistics about the entries database.
+    Paths,
+
+    /// Check the databases for problems, like entries that overlap or reference a tag that does
+    /// not exist. Every problem is printed with a suggested fix and the index of the totals per
+    /// day is rebuilt. The exit code is 1 when problems are found.
+    Doctor {
+        /// Apply the safe fixes: drop the bytes at the end of a database that do not form a
+        /// complete record and clamp the times out of range. The other problems are left as is.
         #[arg(long)]
-        format: Option<pnch::Format>
+        fix: bool,
+
+        /// Apply the fixes without asking for confirmation.
+        #[arg(long, requires = "fix")]
+        yes: bool,
     },
+
+    /// Print or update the configuration. Without arguments, every key is printed with its current
+    /// and default value. Multiple keys can be set at once by passing key and value pairs, for
+    /// example `pnch config set print-color false ls-default-period "4 weeks"`. Nothing is saved
+    /// if any of the pairs is invalid. Use `--dry-run` to only print the changes.
+    #[command(verbatim_doc_comment, args_conflicts_with_subcommands = true)]
     Config {
+        #[command(subcommand)]
+        command: Option<ConfigCommands>,
+
+        /// Key and value pairs to set. This is the same as `pnch config set ...`.
+        pairs: Vec<String>,
+
+        /// Print the changes without saving them.
+        #[arg(long, global = true)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TagsCommands {
+    /// Rename a tag. Every entry with this tag is renamed.
+    Rename {
+        old: String,
+        new: String,
+    },
+
+    /// Remove a tag. The entries with this tag are left without a tag, unless `--reassign` is
+    /// specified.
+    Rm {
+        name: String,
+
+        /// Move the entries of the removed tag to this tag instead.
+        #[arg(long)]
+        reassign: Option<String>,
+    },
+
+    /// Merge a tag into another one. Every entry of the `src` tag is moved to the `dst` tag and
+    /// the `src` tag is removed.
+    Merge {
+        src: String,
+        dst: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommands {
+    /// Print every key with its current and default value.
+    Ls,
+
+    /// Print only the value of a key.
+    Get {
         key: String,
-        value: String,
-    }
+    },
+
+    /// Set one or more keys, specified as key and value pairs.
+    Set {
+        #[arg(required = true)]
+        pairs: Vec<String>,
+    },
+
+    /// Restore a key to its default value. Every key is restored when no key is specified.
+    Reset {
+        key: Option<String>,
+    },
 }
 
+/// The arguments of `pnch edit`.
 #[derive(Args, Debug)]
-pub struct Entry {
+pub struct EditArgs {
     /// The description is used to describe the entry. While specifying the description, it is also
     /// possible to specify a tag. For example, a description could be specify as
     /// "ISSUE-123/The issue is fixed". The tag is the value specified before the forward slash
@@ -157,124 +753,2432 @@ pub struct Entry {
     #[arg(verbatim_doc_comment)]
     description: Option<pnch::Description>,
 
-    /// Manually specify time. The format should be `hh:mm` where `hh` represent hours and
-    /// `mm` represent minutes. The default value is the current local time.
-    #[arg(long, verbatim_doc_comment, default_value_t)]
-    time: time::Time,
+    /// Add text at the end of the description, separated by `; `, instead of replacing it. The
+    /// tag is not changed.
+    #[arg(long, conflicts_with = "description")]
+    append: Option<String>,
+
+    /// Specify the id for the entry to edit. The id can be found when listing entries with
+    /// `pnch ls`
+    #[arg(long)]
+    id: Option<u32>,
+
+    /// Specify the new start time of the entry to edit. The format should be `hh:mm` where
+    /// `hh` represent hours and `mm` represent minutes. The default value is the current local
+    /// time. A time before now can also be specified, like `-15m`.
+    #[arg(long, allow_hyphen_values = true)]
+    r#in: Option<time::TimeArg>,
+
+    /// Specify the new start time of the entry to edit. The format should be `hh:mm` where
+    /// `hh` represent hours and `mm` represent minutes. The default value is the current local
+    /// time. This option is only valid when `--id` is specified (When it is not specified,
+    /// simply use `pnch out --time ...`).
+    #[arg(long, allow_hyphen_values = true)]
+    out: Option<time::TimeArg>,
+
+    /// Specify the new date of the entry to edit with the yyyy-mm-dd format. A date in the
+    /// future is refused unless `--force` is specified.
+    #[arg(long)]
+    date: Option<time::RelativeDate>,
+
+    /// Allow dates in the future, and times in the future for the open entry of today.
+    #[arg(long, alias = "allow-future")]
+    force: bool,
 }
 
-fn main() {
-    let args = Cli::parse();
-    if let Err(err) = run(args) {
-        eprintln!("{err}");
+/// The arguments of `pnch ls`.
+#[derive(Args, Debug)]
+pub struct LsArgs {
+    #[command(flatten)]
+    filter: Filter,
+    /// Specify how to format the output. The value should be one of `table`, `list`, `csv`,
+    /// `html`, `ics`, to import the entries in a calendar, `json`, with the notes of the entries,
+    /// or `template`. The default is `table`.
+    #[arg(long)]
+    format: Option<pnch::Format>,
+    /// The line printed for each entry with `--format template`, like `"{date} {in}-{out}
+    /// [{tag}] {description} ({duration})"`. The placeholders are `{id}`, `{date}`, `{in}`,
+    /// `{out}`, `{tag}`, `{description}`, `{duration}` and `{minutes}`. The default can be set
+    /// with `pnch config template "..."`.
+    #[arg(long, requires = "format")]
+    template: Option<template::Template>,
+    /// Do not print the line naming the columns of the csv format.
+    #[arg(long)]
+    no_header: bool,
+    /// The character separating the fields of the csv format, one of `,`, `;` or `\t`. The
+    /// default can be changed with `pnch config csv-delimiter ";"`.
+    #[arg(long)]
+    delimiter: Option<pnch::Delimiter>,
+    /// Write the entries to this file instead of printing them, for example with `--format
+    /// html` to open them in a browser. The parent directories are created and the colors are
+    /// disabled.
+    #[arg(long, conflicts_with_all = ["group_by", "ids_only", "missing_days"])]
+    output: Option<std::path::PathBuf>,
+    /// Group the entries under a heading with the total of the group. The only grouping is
+    /// `tag`, the entries without a tag are listed last. With the export formats, like csv,
+    /// html or ics, the entries are only reordered.
+    #[arg(long)]
+    group_by: Option<pnch::GroupBy>,
+    /// Only list the entries with this text in their description, ignoring the case. The
+    /// matches are highlighted.
+    #[arg(long, conflicts_with = "grep_regex")]
+    grep: Option<String>,
+    /// Like `--grep`, with a regular expression, see `pnch search --help`.
+    #[arg(long)]
+    grep_regex: Option<String>,
+    /// Only list the open entries. Unless a period is specified, the open entries of every date
+    /// are listed, to find an entry that was left open for days.
+    #[arg(long, conflicts_with = "closed")]
+    open: bool,
+    /// Only list the closed entries.
+    #[arg(long)]
+    closed: bool,
+    /// Only list the entries with these ids, like `120-130` or `5,9,12`. Unless a period is
+    /// specified, the entries of every date are listed.
+    #[arg(long)]
+    id: Option<pnch::IdSet>,
+    /// Only list the entries lasting at least this duration, like `1:00` or `90m`.
+    #[arg(long)]
+    min_duration: Option<time::Duration>,
+    /// Only list the entries lasting at most this duration, like `0:15` or `15m`.
+    #[arg(long)]
+    max_duration: Option<time::Duration>,
+    /// With `--min-duration` or `--max-duration`, also keep the open entries, their duration
+    /// being the time elapsed until now.
+    #[arg(long)]
+    include_open: bool,
+    /// Sort the entries by `date`, `duration` or `tag`. The default is `date`. The open
+    /// entries are listed last when sorting by duration.
+    #[arg(long, default_value = "date")]
+    sort: pnch::Sort,
+    /// Sort from the latest, longest or last tag instead. With the default sort, `--reverse`
+    /// lists the most recent entries first.
+    #[arg(long, visible_alias = "reverse")]
+    desc: bool,
+    /// Show the dates of the last week as `Today`, `Yesterday` or their weekday name, followed
+    /// by the date, like `Today (2024-05-13)`. The default can be changed with
+    /// `pnch config relative-dates true`.
+    #[arg(long, conflicts_with = "no_relative")]
+    relative_dates: bool,
+    /// Always show absolute dates, even if `relative-dates` is enabled in the config.
+    #[arg(long)]
+    no_relative: bool,
+    /// Instead of listing entries, list the work days of the period without any entry. The
+    /// work days can be changed with `pnch config work-days mon,tue,wed,thu,fri`, the days off
+    /// like vacations with `pnch config days-off 2024-05-20..2024-05-24`. The exit code is 1
+    /// when days are missing. Only the entries matching the other filters count, like `--grep`.
+    #[arg(long)]
+    missing_days: bool,
+    /// Instead of listing entries, check that no entry is dated in the future, like `pnch
+    /// doctor`. Every entry is checked, whatever the period. The exit code is 1 when entries
+    /// are found.
+    #[arg(long, conflicts_with_all = ["missing_days", "ids_only", "format", "output", "group_by", "total_only"])]
+    check: bool,
+    /// Also list the archived entries of the period, see `pnch archive --help`. The archived
+    /// entries cannot be edited.
+    #[arg(long)]
+    include_archived: bool,
+    /// Print the notes of each entry under it. Only the list format prints the notes.
+    #[arg(long)]
+    show_notes: bool,
+    /// Only print the id of each entry, one per line, for scripts. Nothing is printed when no
+    /// entry matches. The ids of archived entries cannot be used by the other commands, so
+    /// this cannot be combined with `--include-archived`.
+    #[arg(long, conflicts_with_all = ["format", "show_notes", "missing_days", "include_archived", "relative_dates"])]
+    ids_only: bool,
+    /// The width of the table. The default is the width of the terminal, or a fixed width
+    /// when the output is not a terminal.
+    #[arg(long)]
+    width: Option<usize>,
+    /// Print the whole text of the cells of the table, even when they are longer than their
+    /// column, instead of ending them with `…`.
+    #[arg(long)]
+    full: bool,
+    /// Print the total duration of each tag and its share of the whole under the entries.
+    #[arg(long, conflicts_with_all = ["output", "group_by"])]
+    summary: bool,
+    /// Also show the days of the period without entries, up to today. The entries must be
+    /// sorted by date.
+    #[arg(long, conflicts_with_all = ["desc", "group_by", "missing_days", "ids_only"])]
+    show_empty_days: bool,
+    /// Print the ISO week number, like `W20`, after the first date of each week. The weeks
+    /// are separated by a double line in the table.
+    #[arg(long)]
+    show_week: bool,
+    /// Print the UTC offset after the times of the table punched with another offset than the
+    /// current one, like `16:50 -05:00`, for example after a flight.
+    #[arg(long)]
+    show_offsets: bool,
+    /// Print everything at once instead of going through the pager when the entries do not fit
+    /// in the terminal. The pager can be changed with `pnch config pager "more"`.
+    #[arg(long)]
+    no_pager: bool,
+    /// Only print the total duration of the entries, like `6:45`, for scripts and prompts.
+    /// The open entries are not counted, unless `--include-open-duration` is set.
+    #[arg(long, conflicts_with_all = ["format", "output", "group_by", "show_notes", "missing_days", "ids_only"])]
+    total_only: bool,
+    /// With `--total-only`, print the total as a number of minutes instead.
+    #[arg(long, requires = "total_only")]
+    minutes: bool,
+    /// Count the open entries in the totals until now, or until the end of their day when
+    /// they were started on a previous day. The entries are still listed as open. The default
+    /// can be changed with `pnch config include-open-in-totals true`.
+    #[arg(long)]
+    include_open_duration: bool,
+    /// Round the durations to `5m`, `15m` or `30m`, to the nearest multiple or up with `15m up`.
+    /// Each duration is rounded before the totals are computed, the times are not changed. The
+    /// default is the `round` config, `--round none` prints the exact durations.
+    #[arg(long)]
+    round: Option<time::Rounding>,
+    /// Print each tag used by the entries once, with the first and last date it was used and
+    /// its number of entries. The entries without a tag are counted on a last `---` line.
+    #[arg(long, conflicts_with_all = ["format", "output", "group_by", "ids_only", "total_only", "missing_days", "summary"])]
+    distinct_tags: bool,
+    /// With `--distinct-tags`, only print the names of the tags, one per line.
+    #[arg(long, requires = "distinct_tags")]
+    quiet: bool,
+}
+
+impl LsArgs {
+    /// The period listed when no period filter is specified. Every date is listed when looking
+    /// for the open entries or for ids.
+    fn default_period(&self, config: &config::Config) -> Option<time::Period> {
+        match self.open || self.id.is_some() {
+            true => None,
+            false => Some(config.ls_default_period.clone()),
+        }
+    }
+
+    /// Whether the period is the only filter of the listing, so the days with pnchs can be found
+    /// in the index of the days. The index does not know about tags, archives, descriptions,
+    /// durations, ids or open pnchs.
+    fn filters_only_period(&self) -> bool {
+        let filter = &self.filter;
+        let by_tag = !filter.tag.is_empty() || filter.no_tag || !filter.exclude_tag.is_empty();
+        let by_pnch = self.open || self.closed || self.id.is_some() || self.min_duration.is_some()
+            || self.max_duration.is_some() || self.grep.is_some() || self.grep_regex.is_some();
+        !by_tag && !by_pnch && !self.include_archived
+    }
+
+    /// Only keep the pnchs matching the filters of the listing. The number of pnchs excluded by
+    /// `--exclude-tag` and the pattern of `--grep` or `--grep-regex` are returned with them.
+    fn select(&self, pnchs: pnch::Pnchs, config: &config::Config, clock: &time::Clock) -> Result<(pnch::Pnchs, usize, Option<pattern::Pattern>), GlobalError> {
+        if let Some(ids) = &self.id {
+            if !pnchs.0.iter().any(|pnch| ids.contains(pnch.id)) {
+                return Err(GlobalError::pnch_ids_not_exist(&ids.to_string()));
+            }
+        }
+        let default_period = self.default_period(config);
+        let included = self.filter.included(default_period.clone())?;
+        let excluded = pnchs.0
+            .iter()
+            .filter(|pnch| included(pnch) && self.filter.is_excluded(pnch))
+            .count();
+        let mut pnchs = self.filter.apply(pnchs, default_period.clone())?;
+        if self.open || self.closed {
+            pnchs.0.retain(|pnch| pnch.out.is_none() == self.open);
+        }
+        if let Some(ids) = &self.id {
+            pnchs.0.retain(|pnch| ids.contains(pnch.id));
+        }
+        if self.min_duration.is_some() || self.max_duration.is_some() {
+            let running = pnch::Running::at(clock);
+            pnchs.0.retain(|pnch| {
+                let duration = match (pnch.duration(), self.include_open) {
+                    (Some(duration), _) => duration,
+                    (None, true) => pnch.elapsed(&running),
+                    (None, false) => return false,
+                };
+                self.min_duration.is_none_or(|min| duration >= min) && self.max_duration.is_none_or(|max| duration <= max)
+            });
+        }
+        let pattern = match (&self.grep, &self.grep_regex) {
+            (Some(text), _) => Some(pattern::Pattern::substring(text)),
+            (_, Some(regex)) => Some(pattern::Pattern::regex(regex)?),
+            _ => None,
+        };
+        if let Some(pattern) = &pattern {
+            pnchs.0.retain(|pnch| match &pnch.description {
+                Some(description) => pattern.find(description).is_some(),
+                None => false,
+            });
+        }
+        Ok((pnchs, excluded, pattern))
     }
 }
 
-fn run(args: Cli) -> Result<(), GlobalError> {
-    let mut tags = tag::Tags::load()?;
-    let mut pnchs = pnch::Pnchs::load(&tags)?;
-    let mut config = config::Config::load()?;
+/// The arguments of `pnch report`.
+#[derive(Args, Debug)]
+pub struct ReportArgs {
+    #[command(flatten)]
+    filter: Filter,
 
-    match args.command {
-        Commands::In(Entry { description, time }) => {
-            let (tag, description) = description
-                .map(|d| (d.tag.map(|t| tags.get_or_insert(t)), Some(d.description)))
-                .unwrap_or_else(|| (None, None));
-            let id = pnchs.0.len();
-            pnchs._in(pnch::Pnch::new(id as u32, time, tag, description))?;
-            pnchs.save()?;
-            tags.save()?;
-            println!("You are now pnched in.");
+    /// Print the total of each day instead of each tag.
+    #[arg(long)]
+    per_day: bool,
+
+    /// Also print the work days without entries, which count as a deficit against the day
+    /// target. The work days can be changed with `pnch config work-days mon,tue,wed,thu,fri`,
+    /// the days off are not work days, see `pnch ls --help`.
+    #[arg(long, requires = "per_day")]
+    include_empty_days: bool,
+
+    /// Group the totals by `week` or by `month` instead of by tag.
+    #[arg(long, conflicts_with = "per_day")]
+    group_by: Option<report::GroupBy>,
+
+    /// Also print the weeks or the months without entries, with `--group-by`.
+    #[arg(long, requires = "group_by")]
+    show_empty: bool,
+
+    /// Print the report as csv, with the delimiter of the `csv-delimiter` config.
+    #[arg(long, requires = "group_by")]
+    csv: bool,
+
+    /// Write the report to this file instead of printing it. The parent directories are
+    /// created and the colors are disabled.
+    #[arg(long)]
+    output: Option<std::path::PathBuf>,
+
+    /// Count the open entries until now, like `pnch ls --include-open-duration`.
+    #[arg(long)]
+    include_open_duration: bool,
+
+    /// Round the durations, like `pnch ls --round`.
+    #[arg(long)]
+    round: Option<time::Rounding>,
+}
+
+/// Filters selecting a subset of the entries, shared by the commands working on many entries.
+#[derive(Args, Debug)]
+#[command(group(clap::ArgGroup::new("tag_filters").args(["tag", "exclude_tag"]).multiple(true)))]
+pub struct Filter {
+    /// Get all pnchs since the specified date in the yyyy-mm-dd format, or a relative date like
+    /// `yesterday`, `"last monday"` or `"3 days ago"`. A period like `"3 weeks"` or `3w` can also
+    /// be given, it is the same as `--last`.
+    #[arg(long, short)]
+    since: Option<time::SinceArg>,
+    /// Get all pnchs for the last n period. A period can be `days`, `weeks`, `months` or
+    /// `years`, like `2 weeks`, or shortened like `2w`.
+    #[arg(long, short)]
+    last: Option<time::Period>,
+    /// Specify a range of dates in combination with the `to` flag. Date is specified with the
+    /// yyyy-mm-dd format, or `today`, `yesterday` or `tomorrow`.
+    #[arg(long, short)]
+    from: Option<time::RelativeDate>,
+    /// Specify a range of dates in combination with the `from` flag. Date is specified with the
+    /// yyyy-mm-dd format, or `today`, `yesterday` or `tomorrow`.
+    #[arg(long, short)]
+    to: Option<time::RelativeDate>,
+    /// Get the pnchs of today only.
+    #[arg(long, conflicts_with_all = ["since", "last", "from", "to", "yesterday"])]
+    today: bool,
+    /// Get the pnchs of yesterday only.
+    #[arg(long, conflicts_with_all = ["since", "last", "from", "to", "this_week"])]
+    yesterday: bool,
+    /// Get the pnchs from the start of the current week (see the `week-start` config) to today.
+    #[arg(long, conflicts_with_all = ["since", "last", "from", "to", "today", "this_month"])]
+    this_week: bool,
+    /// Get the pnchs from the first day of the current month to today.
+    #[arg(long, conflicts_with_all = ["since", "last", "from", "to", "today", "yesterday"])]
+    this_month: bool,
+    /// Get the pnchs of a calendar month, in the yyyy-mm format.
+    #[arg(long, conflicts_with_all = ["since", "last", "from", "to", "today", "yesterday", "this_week", "this_month"])]
+    month: Option<time::YearMonth>,
+    /// Get the pnchs of a single day, in the yyyy-mm-dd format or `today`, `yesterday` or `tomorrow`.
+    #[arg(long, conflicts_with_all = ["since", "last", "from", "to", "today", "yesterday", "this_week", "this_month", "month"])]
+    on: Option<time::RelativeDate>,
+    /// Filter only entries from specific tags. The tags can be repeated, `--tag A --tag B`, or
+    /// separated by commas, `--tag A,B`. Every tag starting with the value matches, ignoring the
+    /// case, so `--tag PROJ` matches `PROJ-1234`.
+    #[arg(long, value_delimiter = ',')]
+    tag: Vec<String>,
+    /// Only match the tags with exactly the name given to `--tag` or `--exclude-tag`.
+    #[arg(long, requires = "tag_filters")]
+    tag_exact: bool,
+    /// The tags matched by each value of `--tag`, once resolved.
+    #[arg(skip)]
+    tag_matches: Vec<(String, Vec<String>)>,
+    /// Filter only entries without a tag.
+    #[arg(long, conflicts_with = "tag")]
+    no_tag: bool,
+    /// Filter out the entries of specific tags, after `--tag`. The entries without a tag are
+    /// kept. The tags can be repeated or separated by commas, and match by their start, like for
+    /// `--tag`.
+    #[arg(long, value_delimiter = ',')]
+    exclude_tag: Vec<String>,
+    /// The ranges of dates matched by the period filters, once resolved.
+    #[arg(skip)]
+    resolved: Vec<(time::Date, time::Date)>,
+    /// The day the relative dates and periods were resolved on.
+    #[arg(skip)]
+    resolved_on: Option<time::Date>,
+}
+
+impl Filter {
+    /// Find the ranges of dates matched by the period filters, relative to `today`. The shortcuts
+    /// like `--today` are replaced by the range of dates they stand for.
+    fn resolve_dates(&mut self, today: &time::Date, week_start: time::Weekday) -> Result<(), GlobalError> {
+        let range = if self.today {
+            Some((today.clone(), today.clone()))
+        } else if self.yesterday {
+            today.previous().map(|day| (day.clone(), day))
+        } else if self.this_week {
+            today.start_of_week(week_start).map(|first| (first, today.clone()))
+        } else if self.this_month {
+            Some((today.start_of_month(), today.clone()))
+        } else if let Some(day) = &self.on {
+            let day = day.to_date(today)?;
+            Some((day.clone(), day))
+        } else if let Some(month) = self.month {
+            Some((month.first_day(), month.last_day()))
+        } else if let (Some(from), Some(to)) = (&self.from, &self.to) {
+            Some((from.to_date(today)?, to.to_date(today)?))
+        } else {
+            None
+        };
+        if let Some(since) = &self.since {
+            self.resolved.push((since.to_date_since(today)?, time::Date::max()));
         }
-        Commands::Out(Entry { description, time }) => {
-            match pnchs.get_last() {
-                Some(pnch) => {
-                    let (tag, description) = description
-                        .map(|d| (d.tag.map(|t| tags.get_or_insert(t)), Some(d.description)))
-                        .unwrap_or_else(|| (None, None));
-                    pnch.out(time, tag, description)?;
-                    pnchs.save()?;
-                    tags.save()?;
-                    println!("You are now pnched out.");
-                }
-                _ => {
-                    return Err(GlobalError::pnch_not_open());
+        if let Some(last) = &self.last {
+            self.resolved.push((last.to_date_since(today), time::Date::max()));
+        }
+        self.resolved.extend(range);
+        self.resolved_on = Some(today.clone());
+        Ok(())
+    }
+
+    /// Replace the values of `--tag` and `--exclude-tag` by the names of the tags starting with
+    /// them, unless `--tag-exact` is set. A value matching no tag is an error, with the closest
+    /// tags as hint.
+    fn resolve_tags(&mut self, tags: &tag::Tags) -> Result<(), GlobalError> {
+        if self.tag_exact {
+            return Ok(());
+        }
+        let mut names = Vec::new();
+        for prefix in self.tag.iter() {
+            let matches = Self::tags_starting_with(tags, prefix)?;
+            for name in matches.iter() {
+                if !names.contains(name) {
+                    names.push(name.clone());
                 }
             }
+            self.tag_matches.push((prefix.clone(), matches));
         }
-        Commands::Edit { description, id, r#in, out } => {
-            let pnch = match id {
-                Some(id) => pnchs.get(id),
-                _ => pnchs.get_last(),
-            };
-            match pnch {
-                Some(pnch) => {
-                    if let Some(out) = out {
-                        pnch.out = Some(out);
-                    }
-                    if let Some(_in) = r#in {
-                        pnch._in = _in;
-                    }
-                    if let Some(description) = description {
-                        let tag = description.tag.map(|t| tags.get_or_insert(t));
-                        pnch.tag = tag;
-                        pnch.description = Some(description.description);
-                    }
-                    pnchs.save()?;
-                    tags.save()?;
-                    println!("The pnch was edited.");
-                }
-                _ => {
-                    return Err(GlobalError::pnch_not_open());
+        self.tag = names;
+        let mut excluded = Vec::new();
+        for prefix in self.exclude_tag.iter() {
+            for name in Self::tags_starting_with(tags, prefix)? {
+                if !excluded.contains(&name) {
+                    excluded.push(name);
                 }
             }
         }
-        Commands::Ls { since, last, from, to, tag, format } => {
-            if from.is_some() && to.is_none() || from.is_none() && to.is_some() {
-                return Err(GlobalError::ls_uncomplete_range())
-            }
-            let since = since.unwrap_or(time::Date::min());
-            let last_as_since = last
-                .unwrap_or(config.ls_default_period)
-                .to_date_since_today();
-            let from = from.unwrap_or(time::Date::min());
-            let to = to.unwrap_or(time::Date::max());
-            let pnchs = pnch::Pnchs(pnchs
-                .0
-                .into_iter()
-                .filter(|pnch| {
-                    if pnch.date < since {
-                        return false;
-                    }
-                    if pnch.date < last_as_since {
-                        return false;
-                    }
-                    if pnch.date < from || pnch.date > to {
-                        return false;
-                    }
-                    return true;
-                })
-                .filter(|pnch| match (&pnch.tag, &tag) {
-                    (_, None) => true,
-                    (Some(pnch_tag), Some(filter_tag)) => {
-                        &pnch_tag.tag == filter_tag
-                    }
-                    _ => false
-
-                })
-                .collect::<Vec<_>>());
-
-            match format {
-                Some(pnch::Format::Csv) => println!("{}", pnchs.into_csv()?),
-                Some(pnch::Format::List) => println!("{pnchs}"),
-                _ => println!("{}", pnchs.into_table())
-            }
+        self.exclude_tag = excluded;
+        Ok(())
+    }
+
+    /// The names of the tags starting with `prefix`, ignoring the case.
+    fn tags_starting_with(tags: &tag::Tags, prefix: &str) -> Result<Vec<String>, GlobalError> {
+        let matches = tags.starting_with(prefix);
+        if matches.is_empty() {
+            return Err(GlobalError::tag_no_match(prefix, &tags.closest(prefix, 3)));
         }
-        Commands::Config { key, value } => {
-            config.try_set(&key, &value)?;
-            config.save()?;
-            println!("The config was updated.");
+        Ok(matches.into_iter().map(|tag| tag.tag.clone()).collect())
+    }
+
+    /// The values of `--tag` that matched other tags than themselves, with the tags they matched.
+    fn expanded_tags(&self) -> impl Iterator<Item = &(String, Vec<String>)> {
+        self.tag_matches
+            .iter()
+            .filter(|(prefix, matches)| matches.len() > 1 || matches[0] != *prefix)
+    }
+
+    /// The range of dates of `--this-week`, `--this-month` or `--month`, once resolved.
+    fn calendar_range(&self) -> Option<(&time::Date, &time::Date)> {
+        match self.this_week || self.this_month || self.month.is_some() {
+            true => self.resolved.first().map(|(first, last)| (first, last)),
+            false => None,
         }
     }
-    Ok(())
+
+    /// The period filters match a single day.
+    fn is_single_day(&self) -> bool {
+        matches!(&self.periods(None)[..], [(first, last)] if first == last)
+    }
+
+    /// No filter was specified.
+    fn is_empty(&self) -> bool {
+        self.resolved.is_empty() && self.tag.is_empty() && !self.no_tag && self.exclude_tag.is_empty()
+    }
+
+    /// The ranges of dates matched by the period filters, a pnch matches the filters when it is in
+    /// at least one of them. `default_period` is only used when no period filter is specified.
+    ///
+    /// The dates must be resolved first, see `Filter::resolve_dates`.
+    fn periods(&self, default_period: Option<time::Period>) -> Vec<(time::Date, time::Date)> {
+        if !self.resolved.is_empty() {
+            return self.resolved.clone();
+        }
+        let today = self.resolved_on.as_ref().expect("the dates are resolved before the filters are used");
+        let first = default_period
+            .map(|period| period.to_date_since(today))
+            .unwrap_or(time::Date::min());
+        vec![(first, time::Date::max())]
+    }
+
+    /// The first date matched by the period filters. `default_period` is only used when no period
+    /// filter is specified.
+    fn first_date(&self, default_period: Option<time::Period>) -> time::Date {
+        self.periods(default_period)
+            .into_iter()
+            .map(|(first, _)| first)
+            .min()
+            .expect("there is always a period")
+    }
+
+    /// The last date matched by the period filters.
+    fn last_date(&self) -> time::Date {
+        self.periods(None)
+            .into_iter()
+            .map(|(_, last)| last)
+            .max()
+            .expect("there is always a period")
+    }
+
+    /// A function returning whether a pnch matches the filters. `default_period` is only used
+    /// when no period filter is specified.
+    fn matcher(&self, default_period: Option<time::Period>) -> Result<impl Fn(&pnch::Pnch) -> bool + '_, GlobalError> {
+        let included = self.included(default_period)?;
+        Ok(move |pnch: &pnch::Pnch| included(pnch) && !self.is_excluded(pnch))
+    }
+
+    /// The pnch has one of the tags of `--exclude-tag`.
+    fn is_excluded(&self, pnch: &pnch::Pnch) -> bool {
+        pnch.tag.as_ref().is_some_and(|tag| self.exclude_tag.contains(&tag.tag))
+    }
+
+    /// Like `Filter::matcher`, without `--exclude-tag`.
+    fn included(&self, default_period: Option<time::Period>) -> Result<impl Fn(&pnch::Pnch) -> bool + '_, GlobalError> {
+        if self.from.is_some() && self.to.is_none() || self.from.is_none() && self.to.is_some() {
+            return Err(GlobalError::ls_uncomplete_range())
+        }
+        let periods = self.periods(default_period);
+        Ok(move |pnch: &pnch::Pnch| {
+            let in_period = periods
+                .iter()
+                .any(|(first, last)| pnch.date >= *first && pnch.date <= *last);
+            let has_tag = match &pnch.tag {
+                _ if self.tag.is_empty() => pnch.tag.is_none() || !self.no_tag,
+                Some(pnch_tag) => self.tag.contains(&pnch_tag.tag),
+                None => false,
+            };
+            in_period && has_tag
+        })
+    }
+
+    /// Only keep the pnchs matching the filters. `default_period` is only used when no period
+    /// filter is specified.
+    fn apply(&self, pnchs: pnch::Pnchs, default_period: Option<time::Period>) -> Result<pnch::Pnchs, GlobalError> {
+        let matches = self.matcher(default_period)?;
+        Ok(pnch::Pnchs(pnchs.0.into_iter().filter(|pnch| matches(pnch)).collect()))
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct Entry {
+    /// The description is used to describe the entry. While specifying the description, it is also
+    /// possible to specify a tag. For example, a description could be specify as
+    /// "ISSUE-123/The issue is fixed". The tag is the value specified before the forward slash
+    /// (`/`) and the description is everything after. In the example above, "ISSUE-123" would be
+    /// the tag and "The issue was fixed" would be the description of the issue.
+    #[arg(verbatim_doc_comment)]
+    description: Option<pnch::Description>,
+
+    /// Manually specify time. The format should be `hh:mm` where `hh` represent hours and
+    /// `mm` represent minutes. The default value is the current local time. A time before now
+    /// can also be specified, like `-15m`, `-1h` or `-1h30`.
+    #[arg(long, allow_hyphen_values = true, verbatim_doc_comment)]
+    time: Option<time::TimeArg>,
+
+    /// Allow a time in the future, to punch in or out ahead of time.
+    #[arg(long)]
+    allow_future: bool,
+}
+
+/// Group a flat list of `key value key value ...` arguments into pairs.
+fn into_pairs(values: Vec<String>) -> Result<Vec<(String, String)>, GlobalError> {
+    let mut values = values.into_iter();
+    let mut pairs = Vec::new();
+    while let Some(key) = values.next() {
+        match values.next() {
+            Some(value) => pairs.push((key, value)),
+            None => return Err(GlobalError::config_missing_value(&key)),
+        }
+    }
+    Ok(pairs)
+}
+
+/// The number of rows and columns of the terminal, or `None` when the output is not a terminal.
+///
+/// The size is asked to the terminal of the standard output. The `LINES` and `COLUMNS` variables
+/// are used when the terminal does not know its size.
+fn terminal_size() -> Option<(usize, usize)> {
+    if !std::io::stdout().is_terminal() {
+        return None;
+    }
+    let var = |name| std::env::var(name).ok().and_then(|value| value.parse().ok());
+    let (rows, columns) = window_size().unzip();
+    Some((rows.or_else(|| var("LINES"))?, columns.or_else(|| var("COLUMNS"))?))
+}
+
+/// The rows and columns of the window of the terminal of the standard output.
+#[cfg(unix)]
+fn window_size() -> Option<(usize, usize)> {
+    let mut size = libc::winsize { ws_row: 0, ws_col: 0, ws_xpixel: 0, ws_ypixel: 0 };
+    // SAFETY: `TIOCGWINSZ` only writes a `winsize` through the pointer, which points to `size`.
+    let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+    (result == 0 && size.ws_row > 0 && size.ws_col > 0).then_some((size.ws_row as usize, size.ws_col as usize))
+}
+
+#[cfg(not(unix))]
+fn window_size() -> Option<(usize, usize)> {
+    None
+}
+
+/// The number of columns of the terminal, or `None` when the output is not a terminal.
+fn terminal_width() -> Option<usize> {
+    terminal_size().map(|(_, columns)| columns)
+}
+
+/// Print a text through the pager when it does not fit in the terminal, like git does. The text
+/// is printed directly when the output is not a terminal or the pager cannot be started.
+fn print_paged(text: &str, pager: Option<String>) {
+    let fits = match terminal_size() {
+        Some((rows, _)) => text.lines().count() < rows,
+        None => true,
+    };
+    if let (false, Some(pager)) = (fits, pager) {
+        let child = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(&pager)
+            .stdin(std::process::Stdio::piped())
+            .spawn();
+        if let Ok(mut child) = child {
+            if let Some(mut stdin) = child.stdin.take() {
+                // The pager may be closed before reading everything.
+                let _ = stdin.write_all(text.as_bytes());
+            }
+            let _ = child.wait();
+            return;
+        }
+    }
+    println!("{text}");
+}
+
+/// Write a rendered output to a file, creating its parent directories. The text is written to a
+/// temporary file next to it first, so an existing file is only replaced once the whole text is
+/// written.
+fn write_output(path: &std::path::Path, text: &str) -> Result<(), GlobalError> {
+    let error = || GlobalError::output_write(path);
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        fs::create_dir_all(parent).map_err(|_| error())?;
+    }
+    let mut name = path.file_name().ok_or_else(error)?.to_os_string();
+    name.push(".tmp");
+    let tmp = path.with_file_name(name);
+    if fs::write(&tmp, text).and_then(|_| fs::rename(&tmp, path)).is_err() {
+        let _ = fs::remove_file(&tmp);
+        return Err(error());
+    }
+    Ok(())
+}
+
+/// Tell that no entry matches the filters. It is not an error, the filters were probably too
+/// strict.
+fn print_no_entries() {
+    println!("No entries match the given filters.");
+    println!("{}", "\nhint:".yellow());
+    println!("  To widen the period, use `--since`, `--last` or `--from` and `--to`.");
+}
+
+/// Warn about the entries ending before they start, which are not counted in the totals.
+fn warn_inconsistent(pnchs: &pnch::Pnchs) {
+    let ids = pnchs.inconsistent_ids();
+    if ids.is_empty() {
+        return;
+    }
+    let ids = ids.iter().map(|id| format!("#{id}")).collect::<Vec<_>>().join(", ");
+    eprintln!("{} The entries {ids} end before they start, they are not counted in the totals. To list them, use `pnch doctor`.",
+        "warning:".yellow());
+}
+
+/// The id of the entry `id` once the entries are saved, see `Pnchs::saved_ids`.
+fn saved_id(saved_ids: &[(u32, u32)], id: u32) -> u32 {
+    saved_ids
+        .iter()
+        .find_map(|&(old, new)| (old == id).then_some(new))
+        .unwrap_or(id)
+}
+
+/// Warn about the entries overlapping a new entry, with the ids they have once saved.
+fn warn_overlaps(overlaps: &[u32], saved_ids: &[(u32, u32)]) {
+    if overlaps.is_empty() {
+        return;
+    }
+    let ids = overlaps
+        .iter()
+        .map(|id| format!("#{}", saved_id(saved_ids, *id)))
+        .collect::<Vec<_>>()
+        .join(", ");
+    eprintln!("{} The entry overlaps the entries {ids}.", "warning:".yellow());
+}
+
+/// The entries whose id changes once the entries are saved, other than the new entry `new_id`,
+/// like `#4 is now #5, #6-#9 are now #7-#10`. The ids are the positions of the entries, so adding
+/// an entry before others renumbers them. The consecutive entries renumbered by the same offset
+/// are printed as a range.
+fn renumbered(saved_ids: &[(u32, u32)], new_id: Option<u32>) -> Option<String> {
+    let mut changed = saved_ids
+        .iter()
+        .filter(|&&(old, new)| old != new && Some(old) != new_id)
+        .copied()
+        .collect::<Vec<_>>();
+    changed.sort();
+    let mut runs: Vec<((u32, u32), (u32, u32))> = Vec::new();
+    for (old, new) in changed {
+        match runs.last_mut() {
+            Some((_, last)) if last.0 + 1 == old && last.1 + 1 == new => *last = (old, new),
+            _ => runs.push(((old, new), (old, new))),
+        }
+    }
+    let runs = runs
+        .into_iter()
+        .map(|(first, last)| match first == last {
+            true => format!("#{} is now #{}", first.0, first.1),
+            false => format!("#{}-#{} are now #{}-#{}", first.0, last.0, first.1, last.1),
+        })
+        .collect::<Vec<_>>();
+    (!runs.is_empty()).then(|| runs.join(", "))
+}
+
+/// Print the entries renumbered once the entries are saved, see `renumbered`.
+fn print_renumbered(saved_ids: &[(u32, u32)], new_id: Option<u32>) {
+    if let Some(renumbered) = renumbered(saved_ids, new_id) {
+        println!("The other entries were renumbered: {renumbered}.");
+    }
+}
+
+/// Ask a question and return the trimmed answer.
+fn ask(question: &str) -> Result<String, GlobalError> {
+    print!("{question} ");
+    std::io::stdout().flush().map_err(|_| GlobalError::prompt())?;
+    let mut answer = String::new();
+    std::io::stdin()
+        .read_line(&mut answer)
+        .map_err(|_| GlobalError::prompt())?;
+    Ok(answer.trim().to_owned())
+}
+
+/// Ask for the tag and the description of a pnch. The recent tags are listed to be picked by their
+/// number, and an empty answer keeps the `default` tag or description.
+fn ask_description_of(tags: &tag::Tags, pnchs: &pnch::Pnchs, default: Option<pnch::Description>) -> Result<pnch::Description, GlobalError> {
+    let recent = tags.recent(pnchs);
+    for (idx, tag) in recent.iter().take(9).enumerate() {
+        println!("  {} > {tag}", idx + 1);
+    }
+    let (default_tag, default_description) = match default {
+        Some(pnch::Description { tag, description }) => (tag, Some(description)),
+        None => (None, None),
+    };
+    let keep = |value: &Option<String>| value
+        .as_ref()
+        .map(|value| format!(" [{value}]"))
+        .unwrap_or_default();
+    let answer = ask(&format!("Tag, as a number from the list or a name{}:", keep(&default_tag)))?;
+    let tag = match answer.parse::<usize>() {
+        Ok(number) if (1..=recent.len().min(9)).contains(&number) => Some(recent[number - 1].tag.clone()),
+        _ if answer.is_empty() => default_tag,
+        _ => Some(answer),
+    };
+    let answer = ask(&format!("Description{}:", keep(&default_description)))?;
+    let description = match answer.is_empty() {
+        true => default_description.ok_or_else(GlobalError::desc_not_specified)?,
+        false => answer,
+    };
+    Ok(pnch::Description { tag, description })
+}
+
+/// Ask a yes or no question. Anything other than `y` or `yes` is a no.
+fn confirm(question: &str) -> Result<bool, GlobalError> {
+    let answer = ask(&format!("{question} [y/N]"))?;
+    Ok(matches!(answer.to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Redraw the open pnch with its elapsed time every `interval` seconds until it is closed.
+///
+/// The pnchs database is read again before each redraw, so a pnch closed by another invocation is
+/// noticed. Only the databases are read, so the watch can be interrupted at any time.
+fn watch_open(storage: &storage::Storage, tags: &tag::Tags, mut pnch: pnch::Pnch, interval: u64, config: &config::Config,
+    clock: &time::Clock) -> Result<(), GlobalError> {
+    let redraw = std::io::stdout().is_terminal();
+    loop {
+        let tag = pnch.tag.as_ref().map(|tag| tag.tag.as_str()).unwrap_or("---");
+        let line = format!("[{tag}] {} since {} ({})",
+            pnch.description.as_deref().unwrap_or_default(), pnch._in.format(config.time_format),
+            pnch.elapsed(&pnch::Running::at(clock)).format_long(config.duration_format));
+        match redraw {
+            true => print!("\r{line}\x1b[K"),
+            false => println!("{line}"),
+        }
+        std::io::stdout().flush().ok();
+        std::thread::sleep(std::time::Duration::from_secs(interval));
+
+        let mut pnchs = pnch::Pnchs::load(storage, tags)?;
+        if let Some(open) = pnchs.get_open() {
+            pnch = open.clone();
+            continue;
+        }
+        if redraw {
+            println!();
+        }
+        match pnchs.0.iter().find(|closed| closed.date == pnch.date && closed._in == pnch._in) {
+            Some(pnch::Pnch { out: Some(out), .. }) => println!("Punched out at {out}."),
+            _ => println!("The entry is not open anymore."),
+        }
+        return Ok(());
+    }
+}
+
+fn main() -> ExitCode {
+    let args = Cli::parse();
+    match run(args) {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+impl Commands {
+    /// The filters of the commands working on many entries.
+    fn filter_mut(&mut self) -> Option<&mut Filter> {
+        match self {
+            Self::Ls(LsArgs { filter, .. }) | Self::Report(ReportArgs { filter, .. }) | Self::Stats { filter }
+                | Self::Search { filter, .. } | Self::Rm { filter, .. } => Some(filter),
+            _ => None,
+        }
+    }
+
+}
+
+/// The databases and the config of the profile a command works on, with the clock of the
+/// timezone of the config.
+struct Context {
+    storage: storage::Storage,
+    tags: tag::Tags,
+    pnchs: pnch::Pnchs,
+    config: config::Config,
+    clock: time::Clock,
+}
+
+impl Context {
+    fn load(storage: storage::Storage) -> Result<Self, GlobalError> {
+        let tags = tag::Tags::load(&storage)?;
+        let pnchs = pnch::Pnchs::load(&storage, &tags)?;
+        let config = config::Config::load(&storage)?;
+        let clock = time::Clock::new(config.timezone)?;
+        Ok(Self { storage, tags, pnchs, config, clock })
+    }
+
+    /// Warn that the local timezone could not be found and that the times are in UTC. The
+    /// warning is only printed once, the config remembers it.
+    fn warn_utc_fallback(&mut self) -> Result<(), GlobalError> {
+        if !self.clock.is_utc_fallback() || self.config.utc_fallback_warned {
+            return Ok(());
+        }
+        eprintln!("{} The local timezone could not be found, the times are in UTC. To set it, use `pnch config timezone +02:00` or `{}`.",
+            "warning:".yellow(), time::Timezone::ENV_VAR);
+        self.config.utc_fallback_warned = true;
+        self.config.save(&self.storage)
+    }
+
+    /// The time of an argument, or the current time when it is not given, without its seconds
+    /// when they are not recorded.
+    fn time(&self, arg: Option<time::TimeArg>) -> Result<time::Time, GlobalError> {
+        let time = match arg {
+            Some(arg) => arg.resolve(&self.clock)?,
+            None => self.clock.now_with_seconds(),
+        };
+        Ok(self.config.precision.apply(time))
+    }
+
+    /// The date of an argument, relative to today.
+    fn date(&self, arg: Option<time::RelativeDate>) -> Result<Option<time::Date>, GlobalError> {
+        arg.map(|date| date.to_date(&self.clock.today())).transpose()
+    }
+}
+
+fn run(mut args: Cli) -> Result<ExitCode, GlobalError> {
+    let mut ctx = Context::load(storage::Storage::new(args.profile.as_deref())?)?;
+    ctx.warn_utc_fallback()?;
+    if let Some(profile) = &args.profile {
+        eprintln!("Using the profile `{profile}`.");
+    }
+    if !ctx.config.print_color {
+        colored::control::set_override(false);
+    }
+    if let Some(filter) = args.command.filter_mut() {
+        filter.resolve_dates(&ctx.clock.today(), ctx.config.week_start)?;
+        filter.resolve_tags(&ctx.tags)?;
+    }
+
+    match args.command {
+        Commands::In(entry) => cmd_in(ctx, entry),
+        Commands::Toggle(entry) => cmd_toggle(ctx, entry),
+        Commands::Out { entry, split: Some(split), .. } => cmd_out_split(ctx, entry, split),
+        Commands::Out { entry, split: None, interactive, next_day } => cmd_out(ctx, entry, interactive, next_day),
+        Commands::Break { end: false, time } => cmd_break(ctx, time),
+        Commands::Break { end: true, time } | Commands::Resume { time } => cmd_resume(ctx, time),
+        Commands::Note { text, id } => cmd_note(ctx, text, id),
+        Commands::Add { description, date, r#in, out, force } => cmd_add(ctx, description, date, r#in, out, force),
+        Commands::Import { file, delimiter, force } => cmd_import(ctx, &file, delimiter, force),
+        Commands::Reopen { id } => cmd_reopen(ctx, id),
+        Commands::Duplicate { id, date, force } => cmd_duplicate(ctx, id, date, force),
+        Commands::Edit(args) => cmd_edit(ctx, args),
+        Commands::Amend { description, r#in, out } => cmd_amend(ctx, description, r#in, out),
+        Commands::Split { id, at, description } => cmd_split(ctx, id, at, description),
+        Commands::Merge { first, second, force } => cmd_merge(ctx, first, second, force),
+        Commands::Ls(args) => cmd_ls(ctx, args),
+        Commands::Week { offset, include_open_duration } => cmd_week(ctx, offset, include_open_duration),
+        Commands::Search { text, regex, filter } => cmd_search(ctx, text, regex, filter),
+        Commands::Status { watch, interval } => cmd_status(ctx, watch, interval),
+        Commands::Report(args) => cmd_report(ctx, args),
+        Commands::Stats { filter } => cmd_stats(ctx, filter),
+        Commands::Rm { filter, yes } => cmd_rm(ctx, filter, yes),
+        Commands::Tags { command: Some(TagsCommands::Rename { old, new }), .. } => cmd_tags_rename(ctx, old, new),
+        Commands::Tags { command: Some(TagsCommands::Rm { name, reassign }), .. } => cmd_tags_rm(ctx, name, reassign),
+        Commands::Tags { command: Some(TagsCommands::Merge { src, dst }), .. } => cmd_tags_merge(ctx, src, dst),
+        Commands::Tags { command: None, unused } => cmd_tags(ctx, unused),
+        Commands::Archive { before } => cmd_archive(ctx, before),
+        Commands::Prune { before, yes, dry_run } => cmd_prune(ctx, before, yes, dry_run),
+        Commands::Paths => cmd_paths(ctx),
+        Commands::Doctor { fix, yes } => cmd_doctor(ctx, fix, yes),
+        Commands::Config { command, pairs, dry_run } => cmd_config(ctx, command, pairs, dry_run),
+    }
+}
+
+/// The tag and the description of an entry, the tag is created when it does not exist. A
+/// description too long to be saved is truncated, with a warning.
+fn entry_description(tags: &mut tag::Tags, description: Option<pnch::Description>) -> Result<(Option<tag::Tag>, Option<String>), GlobalError> {
+    let Some(pnch::Description { tag, mut description }) = description else {
+        return Ok((None, None));
+    };
+    let tag = tag.map(|tag| tags.get_or_insert(tag)).transpose()?;
+    if pnch::Pnch::truncate_description(&mut description) {
+        eprintln!("{} The description was too long and was truncated.", "warning:".yellow());
+    }
+    Ok((tag, Some(description)))
+}
+
+/// Punch in, see `pnch in`.
+fn cmd_in(ctx: Context, Entry { description, time, allow_future }: Entry) -> Result<ExitCode, GlobalError> {
+    let time = ctx.time(time)?;
+    let Context { storage, mut tags, mut pnchs, clock, .. } = ctx;
+    let today = clock.today();
+    time.check_not_future(&today, &today, clock.now_with_seconds(), allow_future)?;
+    let (tag, description) = entry_description(&mut tags, description)?;
+    let id = pnchs.0.len();
+    pnchs._in(pnch::Pnch::new(id as u32, &clock, time, tag, description))?;
+    pnchs.save(&storage)?;
+    tags.save(&storage)?;
+    println!("You are now pnched in.");
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Punch in or out, see `pnch toggle`.
+fn cmd_toggle(ctx: Context, Entry { description, time, allow_future }: Entry) -> Result<ExitCode, GlobalError> {
+    let time = ctx.time(time)?;
+    let Context { storage, mut tags, mut pnchs, clock, .. } = ctx;
+    let (tag, description) = entry_description(&mut tags, description)?;
+    let today = clock.today();
+    let now = clock.now_with_seconds();
+    match pnchs.get_open() {
+        Some(pnch) => {
+            time.check_not_future(&pnch.date, &today, now, allow_future)?;
+            pnch.out(time, clock.offset(), tag, description)?;
+            pnchs.save(&storage)?;
+            tags.save(&storage)?;
+            println!("You are now pnched out.");
+        }
+        None => {
+            time.check_not_future(&today, &today, now, allow_future)?;
+            let id = pnchs.0.len();
+            pnchs._in(pnch::Pnch::new(id as u32, &clock, time, tag, description))?;
+            pnchs.save(&storage)?;
+            tags.save(&storage)?;
+            println!("You are now pnched in.");
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Punch out and split the open entry, see `pnch out --split`.
+fn cmd_out_split(ctx: Context, Entry { time, allow_future, .. }: Entry, split: pnch::Split) -> Result<ExitCode, GlobalError> {
+    let time = ctx.time(time)?;
+    let Context { storage, mut tags, mut pnchs, clock, .. } = ctx;
+    if let Some(pnch) = pnchs.0.iter().rev().find(|pnch| pnch.out.is_none()) {
+        time.check_not_future(&pnch.date, &clock.today(), clock.now_with_seconds(), allow_future)?;
+    }
+    let count = pnchs.out_split(time, clock.offset(), &split, &mut tags)?;
+    pnchs.save(&storage)?;
+    tags.save(&storage)?;
+    println!("You are now pnched out. The entry was split in {count} entries.");
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Punch out, see `pnch out`.
+fn cmd_out(ctx: Context, Entry { mut description, time, allow_future }: Entry, interactive: bool, next_day: bool) -> Result<ExitCode, GlobalError> {
+    let time = ctx.time(time)?;
+    let Context { storage, mut tags, mut pnchs, clock, .. } = ctx;
+    let time = match next_day {
+        true => time.next_day(),
+        false => time,
+    };
+    let open = pnchs.0.iter().rev().find(|pnch| pnch.out.is_none());
+    if let Some(pnch) = open {
+        time.check_not_future(&pnch.date, &clock.today(), clock.now_with_seconds(), allow_future)?;
+    }
+    let known = match open {
+        Some(pnch::Pnch { description: Some(known), tag, .. }) => Some(pnch::Description {
+            tag: tag.as_ref().map(|tag| tag.tag.clone()),
+            description: known.clone(),
+        }),
+        Some(_) => None,
+        None => return Err(GlobalError::pnch_not_open()),
+    };
+    let ask_description = interactive
+        || (known.is_none() && description.is_none() && std::io::stdin().is_terminal());
+    let mut replaced = false;
+    if ask_description {
+        let default = description.or(known);
+        replaced = default.is_some();
+        description = Some(ask_description_of(&tags, &pnchs, default)?);
+    }
+    match pnchs.get_open() {
+        Some(pnch) => {
+            if replaced {
+                // The known description is corrected, instead of being specified twice.
+                pnch.description = None;
+            }
+            let (tag, description) = entry_description(&mut tags, description)?;
+            pnch.out(time, clock.offset(), tag, description)?;
+            pnchs.save(&storage)?;
+            tags.save(&storage)?;
+            println!("You are now pnched out.");
+        }
+        _ => {
+            return Err(GlobalError::pnch_not_open());
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Take a break, see `pnch break`.
+fn cmd_break(ctx: Context, time: Option<time::TimeArg>) -> Result<ExitCode, GlobalError> {
+    let time = ctx.time(time)?;
+    let Context { storage, tags, mut pnchs, clock, .. } = ctx;
+    if let Some(pnch::Break(pause)) = pnch::Break::load(&storage, &tags)? {
+        return Err(GlobalError::break_already_started(pause._in));
+    }
+    let pnch = pnchs.get_open().ok_or_else(GlobalError::pnch_not_open)?;
+    let offset = clock.offset();
+    if pnch.until(time, offset) < time::Duration::zero() {
+        return Err(GlobalError::pnch_out_before_in(pnch._in, time));
+    }
+    // The description is not required here, it can be added when pnching out after the
+    // break.
+    pnch.out = Some(time);
+    pnch.out_offset = offset;
+    let pause = pnch::Pnch::new(0, &clock, time, pnch.tag.clone(), pnch.description.clone());
+    pnch::Break(pause).save(&storage)?;
+    pnchs.save(&storage)?;
+    println!("You are now on a break. To end it, use `pnch resume`.");
+    Ok(ExitCode::SUCCESS)
+}
+
+/// End a break, see `pnch resume`.
+fn cmd_resume(ctx: Context, time: Option<time::TimeArg>) -> Result<ExitCode, GlobalError> {
+    let time = ctx.time(time)?;
+    let Context { storage, tags, mut pnchs, config, clock } = ctx;
+    let pnch::Break(pause) = pnch::Break::load(&storage, &tags)?
+        .ok_or_else(GlobalError::break_not_started)?;
+    let id = pnchs.0.len() as u32;
+    pnchs._in(pnch::Pnch { resumed: true, ..pnch::Pnch::new(id, &clock, time, pause.tag, pause.description) })?;
+    pnchs.save(&storage)?;
+    pnch::Break::clear(&storage)?;
+    println!("The break lasted {}. You are now pnched in again.", (time - pause._in).format_long(config.duration_format));
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Attach a note to an entry, see `pnch note`.
+fn cmd_note(ctx: Context, text: String, id: Option<u32>) -> Result<ExitCode, GlobalError> {
+    let Context { storage, mut pnchs, clock, .. } = ctx;
+    if text.len() > note::Note::MAX_TEXT_SIZE {
+        return Err(GlobalError::note_too_long(note::Note::MAX_TEXT_SIZE));
+    }
+    let pnch_id = match id {
+        Some(id) => pnchs.get(id).map(|pnch| pnch.id),
+        None => match pnchs.get_open() {
+            Some(pnch) => Some(pnch.id),
+            None => pnchs.get_last().map(|pnch| pnch.id),
+        },
+    };
+    let pnch_id = pnch_id.ok_or_else(|| match id {
+        Some(id) => GlobalError::pnch_id_not_exists(id),
+        None => GlobalError::pnch_not_exists(),
+    })?;
+    let mut notes = note::Notes::load(&storage)?;
+    notes.add(note::Note::new(pnch_id, &clock, text));
+    notes.save(&storage)?;
+    println!("The note was added to the entry #{pnch_id}.");
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Add a closed entry, see `pnch add`.
+fn cmd_add(ctx: Context, description: pnch::Description, date: Option<time::RelativeDate>, r#in: time::TimeArg, out: time::TimeArg,
+    force: bool) -> Result<ExitCode, GlobalError> {
+    let (r#in, out, date) = (ctx.time(Some(r#in))?, ctx.time(Some(out))?, ctx.date(date)?);
+    let Context { storage, mut tags, mut pnchs, clock, .. } = ctx;
+    let today = clock.today();
+    let date = date.unwrap_or_else(|| today.clone());
+    date.check_not_future(&today, force)?;
+    if out <= r#in {
+        return Err(GlobalError::pnch_out_before_in(r#in, out));
+    }
+    let tag = description.tag.map(|t| tags.get_or_insert(t)).transpose()?;
+    let new_id = pnchs.0.len() as u32;
+    let mut pnch = pnch::Pnch::new(new_id, &clock, r#in, tag, None);
+    let truncated = pnch.set_description(description.description);
+    pnch.date = date;
+    pnch.out = Some(out);
+    pnch.out_offset = pnch.in_offset;
+    let overlaps = pnchs.add(pnch);
+    let saved_ids = pnchs.saved_ids();
+    pnchs.save(&storage)?;
+    tags.save(&storage)?;
+    println!("The entry #{} was added.", saved_id(&saved_ids, new_id));
+    if truncated {
+        eprintln!("{} The description was too long and was truncated.", "warning:".yellow());
+    }
+    print_renumbered(&saved_ids, Some(new_id));
+    warn_overlaps(&overlaps, &saved_ids);
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Add the entries of a csv file, see `pnch import`.
+fn cmd_import(ctx: Context, file: &std::path::Path, delimiter: Option<pnch::Delimiter>, force: bool) -> Result<ExitCode, GlobalError> {
+    let Context { storage, mut tags, mut pnchs, config, clock } = ctx;
+    let csv = fs::read_to_string(file).map_err(|_| GlobalError::import_read(file))?;
+    let delimiter = delimiter.unwrap_or(config.csv_delimiter);
+    let (imported, skipped) = pnchs.read_csv(&csv, &file.display().to_string(), delimiter, &mut tags, &clock, force)?;
+    let before = pnchs.0.len() as u32;
+    let count = imported.len();
+    let mut overlaps = Vec::new();
+    for pnch in imported {
+        overlaps.extend(pnchs.add(pnch).into_iter().filter(|id| *id < before));
+    }
+    overlaps.sort();
+    overlaps.dedup();
+    let saved_ids = pnchs.saved_ids();
+    storage.transaction(|storage| {
+        pnchs.save(storage)?;
+        tags.save(storage)
+    })?;
+    println!("{count} entries were imported.");
+    let existing = saved_ids.iter().filter(|(old, _)| *old < before).copied().collect::<Vec<_>>();
+    print_renumbered(&existing, None);
+    if !overlaps.is_empty() {
+        let ids = overlaps.iter().map(|id| format!("#{}", saved_id(&saved_ids, *id))).collect::<Vec<_>>().join(", ");
+        eprintln!("{} The imported entries overlap the entries {ids}.", "warning:".yellow());
+    }
+    if skipped > 0 {
+        eprintln!("{} {skipped} rows skipped as future-dated, add `--force` to import them.", "warning:".yellow());
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Open a closed entry again, see `pnch reopen`.
+fn cmd_reopen(ctx: Context, id: u32) -> Result<ExitCode, GlobalError> {
+    let Context { storage, mut pnchs, clock, .. } = ctx;
+    let _in = pnchs.reopen(id, &clock.today())?._in;
+    pnchs.save(&storage)?;
+    println!("The entry was reopened, you are pnched in since {_in}.");
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Copy an entry to a new entry, see `pnch duplicate`.
+fn cmd_duplicate(ctx: Context, id: u32, date: Option<time::RelativeDate>, force: bool) -> Result<ExitCode, GlobalError> {
+    let date = ctx.date(date)?;
+    let Context { storage, mut pnchs, config, clock, .. } = ctx;
+    let source = pnchs.get(id).ok_or_else(|| GlobalError::pnch_id_not_exists(id))?.clone();
+    let today = clock.today();
+    let date = date.unwrap_or_else(|| today.clone());
+    date.check_not_future(&today, force)?;
+    let new_id = pnchs.0.len() as u32;
+    let mut pnch = pnch::Pnch::new(new_id, &clock, source._in, source.tag, source.description);
+    pnch.date = date;
+    let overlaps = match source.out {
+        Some(out) => {
+            pnch.out = Some(out);
+            pnch.in_offset = source.in_offset;
+            pnch.out_offset = source.out_offset;
+            pnchs.add(pnch)
+        }
+        None => {
+            pnch._in = config.precision.apply(clock.now_with_seconds());
+            pnchs._in(pnch)?;
+            Vec::new()
+        }
+    };
+    let saved_ids = pnchs.saved_ids();
+    pnchs.save(&storage)?;
+    println!("The entry #{id} was copied to the entry #{}.", saved_id(&saved_ids, new_id));
+    print_renumbered(&saved_ids, Some(new_id));
+    warn_overlaps(&overlaps, &saved_ids);
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Edit an entry, see `pnch edit`.
+fn cmd_edit(ctx: Context, EditArgs { description, append, id, r#in, out, date, force }: EditArgs) -> Result<ExitCode, GlobalError> {
+    let r#in = r#in.map(|time| ctx.time(Some(time))).transpose()?;
+    let out = out.map(|time| ctx.time(Some(time))).transpose()?;
+    let date = ctx.date(date)?;
+    let Context { storage, mut tags, mut pnchs, clock, .. } = ctx;
+    let pnch = match id {
+        Some(id) => pnchs.get(id),
+        _ => pnchs.get_last(),
+    };
+    match pnch {
+        Some(pnch) => {
+            // The times of the open entry of today are the ones that can be in the future.
+            let today = clock.today();
+            if pnch.out.is_none() && pnch.date == today {
+                let now = clock.now_with_seconds();
+                for time in r#in.iter().chain(out.iter()) {
+                    time.check_not_future(&pnch.date, &today, now, force)?;
+                }
+            }
+            pnch.set_times(r#in, out)?;
+            if let Some(date) = date {
+                date.check_not_future(&clock.today(), force)?;
+                pnch.date = date;
+            }
+            let mut truncated = false;
+            if let Some(description) = description {
+                let tag = description.tag.map(|t| tags.get_or_insert(t)).transpose()?;
+                pnch.tag = tag;
+                truncated = pnch.set_description(description.description);
+            }
+            if let Some(append) = append {
+                truncated = pnch.append_description(&append);
+            }
+            if truncated {
+                eprintln!("{} The description was too long and was truncated.", "warning:".yellow());
+            }
+            pnchs.save(&storage)?;
+            tags.save(&storage)?;
+            println!("The pnch was edited.");
+        }
+        _ => {
+            return Err(GlobalError::pnch_not_open());
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Correct the most recent closed entry, see `pnch amend`.
+fn cmd_amend(ctx: Context, description: Option<pnch::Description>, r#in: Option<time::TimeArg>, out: Option<time::TimeArg>) -> Result<ExitCode, GlobalError> {
+    let r#in = r#in.map(|time| ctx.time(Some(time))).transpose()?;
+    let out = out.map(|time| ctx.time(Some(time))).transpose()?;
+    let Context { storage, mut tags, mut pnchs, .. } = ctx;
+    let pnch = pnchs.get_last_closed().ok_or_else(GlobalError::pnch_not_exists)?;
+    let before = pnch.clone();
+    pnch.set_times(r#in, out)?;
+    let mut truncated = false;
+    if let Some(description) = description {
+        pnch.tag = description.tag.map(|t| tags.get_or_insert(t)).transpose()?;
+        truncated = pnch.set_description(description.description);
+    }
+    let show = |value: Option<String>| value.unwrap_or_else(|| String::from("---"));
+    let changes = [
+        ("in", Some(before._in.to_string()), Some(pnch._in.to_string())),
+        ("out", before.out.map(|out| out.to_string()), pnch.out.map(|out| out.to_string())),
+        ("tag", before.tag.map(|tag| tag.tag), pnch.tag.as_ref().map(|tag| tag.tag.clone())),
+        ("description", before.description, pnch.description.clone()),
+    ];
+    let changes = changes
+        .into_iter()
+        .filter(|(_, old, new)| old != new)
+        .collect::<Vec<_>>();
+    if changes.is_empty() {
+        println!("Nothing was changed in the entry #{}.", pnch.id);
+        return Ok(ExitCode::SUCCESS);
+    }
+    println!("The entry #{} was amended.", pnch.id);
+    for (name, old, new) in changes {
+        println!("  {name}: {} {} {}", show(old).red(), "->".dimmed(), show(new).green());
+    }
+    if truncated {
+        eprintln!("{} The description was too long and was truncated.", "warning:".yellow());
+    }
+    pnchs.save(&storage)?;
+    tags.save(&storage)?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Split an entry in two, see `pnch split`.
+fn cmd_split(ctx: Context, id: u32, at: time::TimeArg, description: Option<pnch::Description>) -> Result<ExitCode, GlobalError> {
+    let at = ctx.time(Some(at))?;
+    let Context { storage, mut tags, mut pnchs, .. } = ctx;
+    let description = match description {
+        Some(description) => Some(description),
+        None if std::io::stdin().is_terminal() => {
+            let answer = ask("Tag and description of the new entry (\"tag/description\", empty to keep the same):")?;
+            match answer.is_empty() {
+                true => None,
+                false => Some(answer.parse::<pnch::Description>()?),
+            }
+        }
+        None => None,
+    };
+    let pnch = pnchs.split(id, at)?;
+    let new_id = pnch.id;
+    if let Some(description) = description {
+        pnch.tag = description.tag.map(|t| tags.get_or_insert(t)).transpose()?;
+        pnch.description = Some(description.description);
+    }
+    let saved_ids = pnchs.saved_ids();
+    pnchs.save(&storage)?;
+    tags.save(&storage)?;
+    println!("The entry #{id} was split at {at}, the second part is the entry #{}.", saved_id(&saved_ids, new_id));
+    print_renumbered(&saved_ids, Some(new_id));
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Merge two entries, see `pnch merge`.
+fn cmd_merge(ctx: Context, first: u32, second: u32, force: bool) -> Result<ExitCode, GlobalError> {
+    let Context { storage, mut pnchs, .. } = ctx;
+    let (merged, truncated) = pnchs.merge(first, second, force)?;
+    let merged = merged.clone();
+    let (kept, removed) = match merged.id == first {
+        true => (first, second),
+        false => (second, first),
+    };
+    // The notes of the removed entry are kept with the merged entry.
+    storage.transaction(|storage| {
+        note::Notes::reassign(storage, removed, kept)?;
+        pnchs.save(storage)
+    })?;
+    println!("The entries were merged:\n{merged}");
+    if truncated {
+        eprintln!("{} The description was too long and was truncated.", "warning:".yellow());
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// List the entries, see `pnch ls`.
+fn cmd_ls(ctx: Context, args: LsArgs) -> Result<ExitCode, GlobalError> {
+    let Context { storage, tags, mut pnchs, config, clock } = ctx;
+    if args.check {
+        return print_future_dated(&pnchs, &clock);
+    }
+    let mut notes = match args.show_notes || matches!(args.format, Some(pnch::Format::Json)) {
+        true => Some(note::Notes::load(&storage)?),
+        false => None,
+    };
+    if args.include_archived {
+        let first = args.filter.first_date(Some(config.ls_default_period.clone()));
+        archive::Archive::extend(&mut pnchs, notes.as_mut(), &storage, &tags, &first, &args.filter.last_date())?;
+    }
+    let default_period = args.default_period(&config);
+    let indexed = args.filters_only_period();
+    let (mut pnchs, excluded, pattern) = args.select(pnchs, &config, &clock)?;
+    let LsArgs {
+        filter, format, template, no_header, delimiter, output, group_by, open, sort, desc,
+        relative_dates, no_relative, missing_days, ids_only, width,
+        full, summary, show_empty_days, show_week, show_offsets, no_pager, total_only, minutes,
+        include_open_duration, distinct_tags, quiet, round, ..
+    } = args;
+    pnchs = pnchs.rounded(round.unwrap_or(config.round));
+    if !ids_only {
+        warn_inconsistent(&pnchs);
+    }
+    let running = (include_open_duration || config.include_open_in_totals).then(|| pnch::Running::at(&clock));
+    // The pnchs counted in the totals, with the open pnchs punched out now when they are
+    // included.
+    let counted = |pnchs: &pnch::Pnchs| match &running {
+        Some(running) => pnchs.punched_out_now(running),
+        None => pnch::Pnchs(pnchs.0.clone()),
+    };
+    if total_only {
+        let total = counted(&pnchs).duration();
+        match minutes {
+            true => println!("{}", total.minutes),
+            false => println!("{}", total.format(config.duration_format)),
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+    if open && pnchs.0.is_empty() && !ids_only {
+        println!("No open entries.");
+        return Ok(ExitCode::SUCCESS);
+    }
+    pnchs.sort(sort, desc);
+
+    if show_empty_days && !matches!(sort, pnch::Sort::Date) {
+        return Err(GlobalError::ls_empty_days_unsorted());
+    }
+    if ids_only {
+        for pnch in pnchs.0.iter() {
+            println!("{}", pnch.id);
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+    if distinct_tags {
+        print_distinct_tags(&pnchs, quiet);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if missing_days {
+        return print_missing_days(&storage, &tags, &pnchs, &filter, &config, indexed, &clock);
+    }
+
+    let template = match format {
+        Some(pnch::Format::Template) => Some(template
+            .or(config.template.clone())
+            .ok_or_else(GlobalError::ls_template_missing)?),
+        _ => None,
+    };
+    let exported = matches!(format, Some(pnch::Format::Csv | pnch::Format::Html | pnch::Format::Ics | pnch::Format::Json | pnch::Format::Template));
+    // A file does not have colors or the width of the terminal.
+    if output.is_some() {
+        colored::control::set_override(false);
+    }
+    if pnchs.0.is_empty() && !show_empty_days && !exported && output.is_none() {
+        print_no_entries();
+        return Ok(ExitCode::SUCCESS);
+    }
+    let relative_dates = (config.relative_dates || relative_dates) && !no_relative;
+    let options = pnch::DisplayOptions {
+        notes,
+        highlight: pattern,
+        width: width.or_else(|| terminal_width().filter(|_| output.is_none())),
+        truncate: !full,
+        show_week,
+        single_day: filter.is_single_day(),
+        running: running.clone(),
+        empty_days: match show_empty_days {
+            true => {
+                let first = filter.first_date(default_period);
+                let last = std::cmp::min(filter.last_date(), clock.today());
+                time::Date::range(&first, &last)
+                    .filter(|date| pnchs.0.iter().all(|pnch| pnch.date != *date))
+                    .collect()
+            }
+            false => Vec::new(),
+        },
+        show_weekday: config.show_weekday,
+        time_format: config.time_format,
+        duration_format: config.duration_format,
+        offset: clock.offset().filter(|_| show_offsets),
+        ..pnch::DisplayOptions::new(clock.today(), relative_dates)
+    };
+    // The exports of grouped entries keep one entry per line, only ordered by tag.
+    let pnchs = match group_by {
+        Some(pnch::GroupBy::Tag) if exported => pnch::Pnchs(pnchs.by_tag().into_iter().flat_map(|(_, group)| group.0).collect()),
+        Some(pnch::GroupBy::Tag) => {
+            let options = pnch::DisplayOptions { summary: false, ..options };
+            for (tag, group) in pnchs.by_tag() {
+                let tag = tag.unwrap_or_else(|| String::from("---"));
+                println!("{} ({})", tag.bold(), counted(&group).duration().format_long(config.duration_format));
+                match format {
+                    Some(pnch::Format::List) => println!("{}", group.into_list(options.clone())),
+                    _ => println!("{}", group.into_table(options.clone())),
+                }
+            }
+            println!("total: {}", counted(&pnchs).duration().format_long(config.duration_format));
+            return Ok(ExitCode::SUCCESS);
+        }
+        None => pnchs,
+    };
+    let count = pnchs.0.len();
+    let tag_durations = match summary {
+        true => {
+            let counted = counted(&pnchs);
+            Some((counted.duration_by_tag(), counted.duration()))
+        }
+        false => None,
+    };
+    let rendered = match format {
+        Some(pnch::Format::Csv) => pnchs.into_csv(!no_header, delimiter.unwrap_or(config.csv_delimiter), config.time_format, config.duration_format)?,
+        Some(pnch::Format::Html) => pnchs.into_html(config.duration_format),
+        Some(pnch::Format::Json) => pnchs.into_json(options.notes.as_ref()),
+        Some(pnch::Format::Ics) => {
+            let (ics, skipped) = pnchs.into_ics(&clock, storage.profile());
+            if skipped > 0 {
+                eprintln!("{} {skipped} open entries were skipped.", "warning:".yellow());
+            }
+            ics
+        }
+        Some(pnch::Format::List) => pnchs.into_list(options).to_string(),
+        Some(pnch::Format::Template) => pnchs.into_template(
+            template.as_ref().expect("the template is resolved with the format"),
+            config.duration_format,
+        ),
+        _ => pnchs.into_table(options).to_string(),
+    };
+    match &output {
+        Some(path) => {
+            write_output(path, &rendered)?;
+            eprintln!("{count} entries were written to `{}`.", path.display());
+        }
+        None => {
+            if let (false, Some((first, last))) = (exported, filter.calendar_range()) {
+                println!("Entries from {first} to {last}");
+            }
+            // The exports are printed as is, to be piped to other tools.
+            match exported {
+                true => print!("{rendered}"),
+                false => print_paged(&rendered, config.pager.command().filter(|_| !no_pager)),
+            }
+        }
+    }
+    if !exported && output.is_none() {
+        if let Some((durations, total)) = &tag_durations {
+            print_tag_summary(durations, total, config.duration_format);
+        }
+        for (prefix, matches) in filter.expanded_tags() {
+            match &matches[..] {
+                [tag] => println!("`{prefix}` matched the tag {tag}."),
+                matches => println!("`{prefix}` matched the tags {}.", matches.join(", ")),
+            }
+        }
+        if filter.no_tag && count > 0 {
+            println!("{count} entries without a tag.");
+        }
+        if excluded > 0 {
+            println!("{excluded} entries were excluded by `--exclude-tag`.");
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print each tag used by the pnchs once, see `pnch ls --distinct-tags`.
+fn print_distinct_tags(pnchs: &pnch::Pnchs, quiet: bool) {
+    if pnchs.0.is_empty() && !quiet {
+        print_no_entries();
+    }
+    for (name, group) in pnchs.by_tag() {
+        let count = group.0.len();
+        let tag = group.0.first().and_then(|pnch| pnch.tag.as_ref());
+        let first = group.0.iter().map(|pnch| &pnch.date).min();
+        let last = group.0.iter().map(|pnch| &pnch.date).max();
+        match (name, tag, first.zip(last)) {
+            (Some(name), _, _) if quiet => println!("{name}"),
+            (Some(name), Some(tag), Some((first, last))) => {
+                println!("{} {first} to {last} ({count} entries)", tag.paint(&format!("{name:<24}")));
+            }
+            (None, _, _) if !quiet => println!("--- ({count} entries)"),
+            _ => {}
+        }
+    }
+}
+
+/// Print the work days of the period without any pnch, see `pnch ls --missing-days`.
+fn print_missing_days(storage: &storage::Storage, tags: &tag::Tags, pnchs: &pnch::Pnchs, filter: &Filter,
+    config: &config::Config, indexed: bool, clock: &time::Clock) -> Result<ExitCode, GlobalError> {
+    // The index is only used when the period is the only filter, otherwise the days are found in
+    // the filtered pnchs.
+    let (first, last, days) = match indexed {
+        true => {
+            let index = index::DayIndex::load(storage, tags)?;
+            missing_days(filter, config, clock, |date| index.get(date).is_some())
+        }
+        false => missing_days(filter, config, clock, |date| pnchs.0.iter().any(|pnch| pnch.date == *date)),
+    };
+    for day in days.iter() {
+        let weekday = day.weekday().map(|w| w.to_string()).unwrap_or_default();
+        println!("{day} {weekday}");
+    }
+    println!("{} work days without entries from {first} to {last}.", days.len());
+    match days.is_empty() {
+        true => Ok(ExitCode::SUCCESS),
+        false => Ok(ExitCode::FAILURE),
+    }
+}
+
+/// Print the pnchs dated in the future, see `pnch ls --check`.
+fn print_future_dated(pnchs: &pnch::Pnchs, clock: &time::Clock) -> Result<ExitCode, GlobalError> {
+    let findings = doctor::future_dated(pnchs, &clock.today());
+    for finding in findings.iter() {
+        println!("{finding}");
+    }
+    match findings.len() {
+        0 => {
+            println!("No entries are dated in the future.");
+            Ok(ExitCode::SUCCESS)
+        }
+        count => {
+            println!("\n{count} entries are dated in the future.");
+            Ok(ExitCode::FAILURE)
+        }
+    }
+}
+
+/// The work days without any pnch from the first date of `filter` to its last date, or to today,
+/// with the first and last dates. The days off of the config are not work days. See `pnch ls
+/// --missing-days` and `pnch report --include-empty-days`.
+fn missing_days(filter: &Filter, config: &config::Config, clock: &time::Clock,
+    tracked: impl Fn(&time::Date) -> bool) -> (time::Date, time::Date, Vec<time::Date>) {
+    let first = filter.first_date(Some(config.ls_default_period.clone()));
+    let last = std::cmp::min(filter.last_date(), clock.today());
+    let days = time::untracked_days(&first, &last, config.work_days, &config.days_off, tracked);
+    (first, last, days)
+}
+
+/// Print the total of each tag with its share of the whole, see `pnch ls --summary`.
+fn print_tag_summary(durations: &[(Option<String>, time::Duration)], total: &time::Duration, duration_format: time::DurationFormat) {
+    for (tag, duration) in durations {
+        let tag = tag.as_deref().unwrap_or("---");
+        let share = match total.minutes {
+            0 => 0,
+            total => (duration.minutes * 100 + total / 2) / total,
+        };
+        println!("{tag:<24} {:<22} {share:>3}%", duration.format_long(duration_format));
+    }
+}
+
+/// List the entries of a week, see `pnch week`.
+fn cmd_week(ctx: Context, offset: i64, include_open_duration: bool) -> Result<ExitCode, GlobalError> {
+    let Context { mut pnchs, config, clock, .. } = ctx;
+    let today = clock.today();
+    let (first, last) = today
+        .start_of_week(config.week_start)
+        .and_then(|start| start.add_days(offset * 7))
+        .and_then(|first| Some((first.clone(), first.add_days(6)?)))
+        .ok_or_else(|| GlobalError::parse("an offset that stays in the calendar"))?;
+    pnchs.0.retain(|pnch| first <= pnch.date && pnch.date <= last);
+    let running = (include_open_duration || config.include_open_in_totals).then(|| pnch::Running::at(&clock));
+    let stats = match &running {
+        Some(running) => pnchs.punched_out_now(running).stats(&today, config.duration_format),
+        None => pnchs.stats(&today, config.duration_format),
+    };
+    let options = pnch::DisplayOptions {
+        day_totals: true,
+        width: terminal_width(),
+        running,
+        show_weekday: config.show_weekday,
+        time_format: config.time_format,
+        duration_format: config.duration_format,
+        ..pnch::DisplayOptions::new(today, config.relative_dates)
+    };
+    println!("Week from {first} to {last}");
+    match pnchs.0.is_empty() {
+        true => println!("No entries this week."),
+        false => println!("{}", pnchs.into_table(options)),
+    }
+    println!("total: {}", stats.duration.format_long(config.duration_format));
+    println!("average per day: {}", stats.average.format_long(config.duration_format));
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Search the descriptions, see `pnch search`.
+fn cmd_search(ctx: Context, text: String, regex: bool, filter: Filter) -> Result<ExitCode, GlobalError> {
+    let Context { pnchs, config, clock, .. } = ctx;
+    let pattern = match regex {
+        true => pattern::Pattern::regex(&text)?,
+        false => pattern::Pattern::substring(&text),
+    };
+    let mut pnchs = filter.apply(pnchs, None)?;
+    pnchs.0.retain(|pnch| match &pnch.description {
+        Some(description) => pattern.find(description).is_some(),
+        None => false,
+    });
+    let options = pnch::DisplayOptions {
+        highlight: Some(pattern),
+        width: terminal_width(),
+        show_weekday: config.show_weekday,
+        time_format: config.time_format,
+        duration_format: config.duration_format,
+        ..pnch::DisplayOptions::new(clock.today(), config.relative_dates)
+    };
+    if pnchs.0.is_empty() {
+        print_no_entries();
+        return Ok(ExitCode::SUCCESS);
+    }
+    println!("{}", pnchs.into_table(options));
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print the open entry and the total of today, see `pnch status`.
+fn cmd_status(ctx: Context, watch: bool, interval: u64) -> Result<ExitCode, GlobalError> {
+    let Context { storage, tags, mut pnchs, config, clock } = ctx;
+    let running = pnch::Running::at(&clock);
+    let today = clock.today();
+    match pnchs.0.iter().rev().find(|pnch| pnch.out.is_none()) {
+        Some(pnch) => println!("You are pnched in since {} ({}).\n{}",
+            pnch._in.format(config.time_format), pnch.elapsed(&running).format_long(config.duration_format),
+            pnch.display(config.time_format, config.duration_format)),
+        None => match pnch::Break::load(&storage, &tags)? {
+            Some(pnch::Break(pause)) => {
+                println!("You are on a break since {} ({}).", pause._in.format(config.time_format),
+                    pause.elapsed(&running).format_long(config.duration_format));
+            }
+            None => println!("You are not pnched in."),
+        },
+    }
+    let total = pnchs.0
+        .iter()
+        .filter(|pnch| pnch.date == today)
+        .map(|pnch| pnch.elapsed(&running))
+        .sum::<time::Duration>();
+    println!("Today: {}", total.format_long(config.duration_format));
+    if config.day_target > time::Duration::zero() {
+        let remaining = config.day_target - total;
+        let target = config.day_target.format(config.duration_format);
+        match remaining > time::Duration::zero() {
+            true => println!("{} remaining to reach the day target of {target}.", remaining.format_long(config.duration_format)),
+            false => println!("The day target of {target} is reached."),
+        }
+    }
+    if let (true, Some(pnch)) = (watch, pnchs.get_open()) {
+        println!();
+        watch_open(&storage, &tags, pnch.clone(), interval, &config, &clock)?;
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print the totals, see `pnch report`.
+fn cmd_report(ctx: Context, ReportArgs { filter, per_day, include_empty_days, group_by, show_empty, csv, output, include_open_duration, round }: ReportArgs) -> Result<ExitCode, GlobalError> {
+    let Context { pnchs, config, clock, .. } = ctx;
+    if output.is_some() {
+        colored::control::set_override(false);
+    }
+    let mut pnchs = filter
+        .apply(pnchs, Some(config.ls_default_period.clone()))?
+        .rounded(round.unwrap_or(config.round));
+    warn_inconsistent(&pnchs);
+    if (include_open_duration || config.include_open_in_totals) && pnchs.has_open() {
+        pnchs = pnchs.punched_out_now(&pnch::Running::at(&clock));
+        eprintln!("The open entry is counted until now.");
+    }
+    let rendered = match group_by {
+        Some(report::GroupBy::Week) => {
+            let empty_weeks = show_empty.then(|| empty_period(&filter, &config, &clock));
+            let report = report::WeekReport::new(&pnchs, config.week_start, empty_weeks, config.duration_format);
+            match csv {
+                true => report.to_csv(config.csv_delimiter),
+                false => report.to_string(),
+            }
+        }
+        Some(report::GroupBy::Month) => {
+            let empty_months = show_empty.then(|| empty_period(&filter, &config, &clock));
+            let report = report::MonthReport::new(&pnchs, empty_months, config.duration_format);
+            match csv {
+                true => report.to_csv(config.csv_delimiter),
+                false => report.to_string(),
+            }
+        }
+        None if per_day => {
+            let empty_days = match include_empty_days {
+                true => missing_days(&filter, &config, &clock, |date| pnchs.0.iter().any(|pnch| pnch.date == *date)).2,
+                false => Vec::new(),
+            };
+            report::DayReport::new(&pnchs, config.day_target, empty_days, config.duration_format).to_string()
+        }
+        None => report::TagReport::new(&pnchs, config.duration_format).to_string(),
+    };
+    match &output {
+        Some(path) => {
+            write_output(path, &rendered)?;
+            eprintln!("The report was written to `{}`.", path.display());
+        }
+        None => print!("{rendered}"),
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// The first and last dates of the period of a report, until today, where the weeks or months
+/// without pnchs are added with `--show-empty`.
+fn empty_period(filter: &Filter, config: &config::Config, clock: &time::Clock) -> (time::Date, time::Date) {
+    (filter.first_date(Some(config.ls_default_period.clone())), std::cmp::min(filter.last_date(), clock.today()))
+}
+
+/// Print statistics, see `pnch stats`.
+fn cmd_stats(ctx: Context, filter: Filter) -> Result<ExitCode, GlobalError> {
+    let Context { pnchs, config, clock, .. } = ctx;
+    let pnchs = filter.apply(pnchs, Some(config.ls_default_period.clone()))?;
+    warn_inconsistent(&pnchs);
+    print!("{}", pnchs.stats(&clock.today(), config.duration_format));
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Remove the entries matching filters, see `pnch rm`.
+fn cmd_rm(ctx: Context, filter: Filter, yes: bool) -> Result<ExitCode, GlobalError> {
+    let Context { storage, pnchs, config, .. } = ctx;
+    if filter.is_empty() {
+        return Err(GlobalError::rm_no_filter());
+    }
+    let matches = filter.matcher(None)?;
+    let (removed, kept): (Vec<_>, Vec<_>) = pnchs.0.into_iter().partition(|pnch| matches(pnch));
+    let removed = pnch::Pnchs(removed);
+    if removed.0.is_empty() {
+        println!("No entries match the filters.");
+        return Ok(ExitCode::SUCCESS);
+    }
+    let count = removed.0.len();
+    let duration = removed.duration().format_long(config.duration_format);
+    println!("{count} entries match the filters, for a total of {duration}.");
+    if !yes && !confirm(&format!("Remove the {count} entries?"))? {
+        println!("Nothing was removed.");
+        return Ok(ExitCode::SUCCESS);
+    }
+    pnch::Pnchs(kept).save(&storage)?;
+    println!("{count} entries were removed, for a total of {duration}.");
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Rename a tag, see `pnch tags rename`.
+fn cmd_tags_rename(ctx: Context, old: String, new: String) -> Result<ExitCode, GlobalError> {
+    let Context { storage, mut tags, .. } = ctx;
+    if !tags.rename(&old, &new)? {
+        println!("The tag `{old}` already has this name, nothing was changed.");
+        return Ok(ExitCode::SUCCESS);
+    }
+    tags.save(&storage)?;
+    println!("The tag `{old}` was renamed to `{new}`.");
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Remove a tag, see `pnch tags rm`.
+fn cmd_tags_rm(ctx: Context, name: String, reassign: Option<String>) -> Result<ExitCode, GlobalError> {
+    let Context { storage, mut tags, mut pnchs, .. } = ctx;
+    let mut archives = archive::Archive::load_all(&storage, &tags)?;
+    let mut all = archives.iter_mut().map(|archive| &mut archive.pnchs).collect::<Vec<_>>();
+    all.push(&mut pnchs);
+    let count = tags.remove(&name, reassign.as_deref(), &mut all)?;
+    // The tag ids of the entries are only valid with the tags they were remapped for.
+    storage.transaction(|storage| {
+        for archive in archives.iter() {
+            archive.save(storage)?;
+        }
+        pnchs.save(storage)?;
+        tags.save(storage)
+    })?;
+    match reassign {
+        Some(reassign) => println!("{count} entries reassigned to `{reassign}`, 1 tag removed."),
+        None => println!("{count} entries untagged, 1 tag removed."),
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Merge a tag into another one, see `pnch tags merge`.
+fn cmd_tags_merge(ctx: Context, src: String, dst: String) -> Result<ExitCode, GlobalError> {
+    let Context { storage, mut tags, mut pnchs, .. } = ctx;
+    let mut archives = archive::Archive::load_all(&storage, &tags)?;
+    let mut all = archives.iter_mut().map(|archive| &mut archive.pnchs).collect::<Vec<_>>();
+    all.push(&mut pnchs);
+    let count = tags.remove(&src, Some(&dst), &mut all)?;
+    storage.transaction(|storage| {
+        for archive in archives.iter() {
+            archive.save(storage)?;
+        }
+        pnchs.save(storage)?;
+        tags.save(storage)
+    })?;
+    println!("{count} entries moved from `{src}` to `{dst}`.");
+    Ok(ExitCode::SUCCESS)
+}
+
+/// List the tags, see `pnch tags`.
+fn cmd_tags(ctx: Context, unused: bool) -> Result<ExitCode, GlobalError> {
+    let Context { tags, pnchs, .. } = ctx;
+    let usages = tags
+        .usage(&pnchs)
+        .into_iter()
+        .filter(|usage| !unused || usage.count == 0)
+        .collect::<Vec<_>>();
+    if usages.is_empty() {
+        println!("No tags were found.");
+    }
+    for usage in usages {
+        println!("{usage}");
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Archive the entries before a date, see `pnch archive`.
+fn cmd_archive(ctx: Context, before: time::RelativeDate) -> Result<ExitCode, GlobalError> {
+    let before = before.to_date(&ctx.clock.today())?;
+    let Context { storage, tags, mut pnchs, .. } = ctx;
+    let counts = storage.transaction(|storage| {
+        let counts = archive::Archive::archive(&mut pnchs, &before, storage, &tags)?;
+        pnchs.save(storage)?;
+        Ok(counts)
+    })?;
+    if counts.is_empty() {
+        println!("No entries before {before} to archive.");
+    }
+    for (year, count) in counts {
+        println!("{count} entries archived in {}.", archive::Archive::file_name(year));
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Remove the entries before a date, see `pnch prune`.
+fn cmd_prune(ctx: Context, before: time::RelativeDate, yes: bool, dry_run: bool) -> Result<ExitCode, GlobalError> {
+    let before = before.to_date(&ctx.clock.today())?;
+    let Context { storage, tags, pnchs, config, clock } = ctx;
+    let (removed, kept): (Vec<_>, Vec<_>) = pnchs.0.into_iter().partition(|pnch| pnch.date < before);
+    let (removed, kept) = (pnch::Pnchs(removed), pnch::Pnchs(kept));
+    if removed.0.is_empty() {
+        println!("No entries before {before} to remove.");
+        return Ok(ExitCode::SUCCESS);
+    }
+    let count = removed.0.len();
+    let duration = removed.duration().format_long(config.duration_format);
+    let archives = archive::Archive::load_all(&storage, &tags)?;
+    let unused = tags
+        .usage(&removed)
+        .into_iter()
+        .filter(|usage| usage.count > 0)
+        .map(|usage| usage.tag)
+        .filter(|tag| {
+            std::iter::once(&kept)
+                .chain(archives.iter().map(|archive| &archive.pnchs))
+                .all(|pnchs| pnchs.0.iter().all(|pnch| pnch.tag.as_ref() != Some(*tag)))
+        })
+        .collect::<Vec<_>>();
+    if dry_run {
+        let options = pnch::DisplayOptions {
+            width: terminal_width(),
+            show_weekday: config.show_weekday,
+            time_format: config.time_format,
+            duration_format: config.duration_format,
+            ..pnch::DisplayOptions::new(clock.today(), config.relative_dates)
+        };
+        println!("{}", removed.into_table(options));
+    }
+    println!("{count} entries before {before}, for a total of {duration}.");
+    if !unused.is_empty() {
+        let names = unused.iter().map(|tag| tag.to_string()).collect::<Vec<_>>().join(", ");
+        println!("These tags would not be used anymore: {names}. To remove them, use `pnch tags rm`.");
+    }
+    if dry_run {
+        println!("Dry run, nothing was removed.");
+        return Ok(ExitCode::SUCCESS);
+    }
+    if !yes && !confirm(&format!("Permanently remove the {count} entries?"))? {
+        println!("Nothing was removed.");
+        return Ok(ExitCode::SUCCESS);
+    }
+    kept.save(&storage)?;
+    println!("{count} entries were removed, for a total of {duration}.");
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print where the databases are stored, see `pnch paths`.
+fn cmd_paths(ctx: Context) -> Result<ExitCode, GlobalError> {
+    let Context { storage, .. } = ctx;
+    println!("storage: {}", storage.dir().display());
+    let files = [
+        ("entries", pnch::Pnchs::PNCHS_FILE_NAME),
+        ("tags", tag::Tags::TAGS_FILE_NAME),
+        ("config", config::Config::CONFIG_FILE_NAME),
+        ("index", index::DayIndex::INDEX_FILE_NAME),
+        ("break", pnch::Break::BREAK_FILE_NAME),
+        ("notes", note::Notes::NOTES_FILE_NAME),
+    ];
+    for (name, file) in files {
+        println!("  {name}: {}", storage.build_path(file)?);
+    }
+    println!();
+    print!("{}", pnch::Pnchs::stat(&storage)?);
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Check the databases, see `pnch doctor`.
+fn cmd_doctor(ctx: Context, fix: bool, yes: bool) -> Result<ExitCode, GlobalError> {
+    let Context { storage, tags, pnchs, clock, .. } = ctx;
+    println!("The entries database is in the format version {}, the tags database in the version {}, the notes database in the version {} and the config in the version {}.\n",
+        pnch::Pnchs::FORMAT.version(&storage, pnch::Pnchs::PNCHS_FILE_NAME)?,
+        tag::Tags::FORMAT.version(&storage, tag::Tags::TAGS_FILE_NAME)?,
+        note::Notes::FORMAT.version(&storage, note::Notes::NOTES_FILE_NAME)?,
+        config::Config::FORMAT.version(&storage, config::Config::CONFIG_FILE_NAME)?);
+    let findings = doctor::check(&storage, &tags, &pnchs, &clock.today())?;
+    for finding in findings.iter() {
+        println!("{finding}");
+    }
+    let fixable = findings.iter().filter(|finding| finding.fixable).count();
+    index::DayIndex::build(&pnchs).save(&storage, pnch::Pnchs::count(&storage)?)?;
+    if findings.is_empty() {
+        println!("No problems were found, the index was rebuilt.");
+        return Ok(ExitCode::SUCCESS);
+    }
+    println!("\n{} problems were found, {fixable} can be fixed with `pnch doctor --fix`.", findings.len());
+    if !fix || fixable == 0 {
+        return Ok(ExitCode::FAILURE);
+    }
+    if !yes && !confirm(&format!("Apply the {fixable} safe fixes?"))? {
+        println!("Nothing was fixed.");
+        return Ok(ExitCode::FAILURE);
+    }
+    pnch::Pnchs::repair(&storage)?;
+    tags.save(&storage)?;
+    index::DayIndex::load(&storage, &tags)?;
+    println!("{fixable} problems were fixed.");
+    if fixable < findings.len() {
+        return Ok(ExitCode::FAILURE);
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print or update the config, see `pnch config`.
+fn cmd_config(ctx: Context, command: Option<ConfigCommands>, pairs: Vec<String>, dry_run: bool) -> Result<ExitCode, GlobalError> {
+    let Context { storage, config, .. } = ctx;
+    let command = match command {
+        Some(command) => command,
+        None if pairs.is_empty() => ConfigCommands::Ls,
+        None => ConfigCommands::Set { pairs },
+    };
+    let (updated, changes) = match command {
+        ConfigCommands::Ls => {
+            for value in config.values() {
+                println!("{value}");
+            }
+            return Ok(ExitCode::SUCCESS);
+        }
+        ConfigCommands::Get { key } => {
+            println!("{}", config.get(&key)?);
+            return Ok(ExitCode::SUCCESS);
+        }
+        ConfigCommands::Set { pairs } => config.with_changes(&into_pairs(pairs)?)?,
+        ConfigCommands::Reset { key } => config.reset(key.as_deref())?,
+    };
+    for change in changes {
+        println!("{change}");
+    }
+    if dry_run {
+        println!("Dry run, the config was not updated.");
+    } else {
+        updated.save(&storage)?;
+        println!("The config was updated.");
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    /// The filter of `pnch ls` with `args`, resolved on `today`.
+    fn filter(args: &[&str], today: &str) -> Filter {
+        filter_with_week_start(args, today, time::Weekday::Monday)
+    }
+
+    /// Like `filter`, with the weeks starting on `week_start`.
+    fn filter_with_week_start(args: &[&str], today: &str, week_start: time::Weekday) -> Filter {
+        let Cli { command: Commands::Ls(ls), .. } = Cli::parse_from(["pnch", "ls"].iter().chain(args)) else {
+            panic!("not a `pnch ls`");
+        };
+        let mut filter = ls.filter;
+        let today = today.parse::<time::Date>().unwrap();
+        filter.resolve_dates(&today, week_start).unwrap();
+        filter
+    }
+
+    /// The arguments of `pnch ls` with `args`.
+    fn ls_args(args: &[&str]) -> LsArgs {
+        let Cli { command: Commands::Ls(ls), .. } = Cli::parse_from(["pnch", "ls"].iter().chain(args)) else {
+            panic!("not a `pnch ls`");
+        };
+        ls
+    }
+
+    fn dates(from: &str, to: &str) -> (time::Date, time::Date) {
+        (from.parse().unwrap(), to.parse().unwrap())
+    }
+
+    #[test]
+    fn filter_dates_are_resolved_on_the_given_day() {
+        assert_eq!(filter(&["--today"], "2024-05-15").periods(None), vec![dates("2024-05-15", "2024-05-15")]);
+        assert_eq!(filter(&["--this-week"], "2024-05-15").periods(None), vec![dates("2024-05-13", "2024-05-15")]);
+        assert_eq!(filter(&["--on", "yesterday"], "2024-05-15").periods(None), vec![dates("2024-05-14", "2024-05-14")]);
+        assert_eq!(filter(&["--since", "last monday"], "2024-05-13").periods(None), vec![("2024-05-06".parse().unwrap(), time::Date::max())]);
+        assert_eq!(filter(&["--from", "3 days ago", "--to", "today"], "2024-05-15").periods(None), vec![dates("2024-05-12", "2024-05-15")]);
+        assert_eq!(filter(&["--month", "2024-02"], "2024-05-15").periods(None), vec![dates("2024-02-01", "2024-02-29")]);
+    }
+
+    #[test]
+    fn this_week_depends_on_the_week_start() {
+        let this_week = |week_start| filter_with_week_start(&["--this-week"], "2024-05-19", week_start).periods(None);
+        assert_eq!(this_week(time::Weekday::Monday), vec![dates("2024-05-13", "2024-05-19")]);
+        assert_eq!(this_week(time::Weekday::Sunday), vec![dates("2024-05-19", "2024-05-19")]);
+        let this_week = |week_start| filter_with_week_start(&["--this-week"], "2024-05-18", week_start).periods(None);
+        assert_eq!(this_week(time::Weekday::Monday), vec![dates("2024-05-13", "2024-05-18")]);
+        assert_eq!(this_week(time::Weekday::Sunday), vec![dates("2024-05-12", "2024-05-18")]);
+
+        let storage = storage::Storage::temp("config-week-start");
+        let ctx = Context::load(storage.clone()).unwrap();
+        cmd_config(ctx, None, pairs(&["week-start", "sunday"]), false).unwrap();
+        let config = config::Config::load(&storage).unwrap();
+        assert_eq!(config.week_start, time::Weekday::Sunday);
+        assert!(config.values().iter().any(|value| value.to_string().contains("week-start") && value.to_string().contains("sun")));
+    }
+
+    #[test]
+    fn period_filters_are_unions_replacing_the_default_period() {
+        let default_period = || Some(time::Period::Weeks(2));
+        assert_eq!(filter(&["--since", "2023-01-01"], "2024-05-15").periods(default_period()), vec![("2023-01-01".parse().unwrap(), time::Date::max())]);
+        assert_eq!(filter(&[], "2024-05-15").periods(default_period()), vec![("2024-05-01".parse().unwrap(), time::Date::max())]);
+        assert_eq!(filter(&[], "2024-05-15").periods(None), vec![(time::Date::min(), time::Date::max())]);
+
+        let union = filter(&["--from", "2024-01-01", "--to", "2024-01-31", "--last", "1w"], "2024-05-15");
+        let matches = union.matcher(default_period()).unwrap();
+        let on = |date: &str| {
+            let clock = time::Clock::fixed(&date.parse().unwrap(), "10:00".parse().unwrap(), time::Offset::from_minutes(0));
+            pnch::Pnch::new(0, &clock, "9:00".parse().unwrap(), None, None)
+        };
+        assert!(matches(&on("2024-01-15")));
+        assert!(matches(&on("2024-05-10")));
+        assert!(!matches(&on("2024-03-01")));
+        assert!(!matches(&on("2024-05-01")));
+    }
+
+    fn add(storage: &storage::Storage, description: &str, r#in: &str, out: &str) {
+        add_on(storage, "2024-05-13", description, r#in, out);
+    }
+
+    fn add_on(storage: &storage::Storage, date: &str, description: &str, r#in: &str, out: &str) {
+        let ctx = Context::load(storage.clone()).unwrap();
+        cmd_add(ctx, description.parse().unwrap(), date.parse().ok(), r#in.parse().unwrap(), out.parse().unwrap(), false).unwrap();
+    }
+
+    fn note(storage: &storage::Storage, id: u32, text: &str) {
+        let ctx = Context::load(storage.clone()).unwrap();
+        cmd_note(ctx, String::from(text), Some(id)).unwrap();
+    }
+
+    /// The text of the notes of `file`, with the id of their pnch.
+    fn notes(storage: &storage::Storage, file: &str) -> Vec<(u32, String)> {
+        let notes = note::Notes::load_file(storage, file).unwrap();
+        (0..10)
+            .flat_map(|id| notes.of(id).map(move |note| (id, note.text.clone())).collect::<Vec<_>>())
+            .collect()
+    }
+
+    #[test]
+    fn future_punch_times_are_refused_unless_allowed() {
+        let storage = storage::Storage::temp("future-times");
+        let clock = time::Clock::fixed(&"2024-05-13".parse().unwrap(), "9:00".parse().unwrap(), time::Offset::from_minutes(0));
+        let ctx = || Context { clock, ..Context::load(storage.clone()).unwrap() };
+        let entry = |time: &str, allow_future| Entry { description: Some("A/work".parse().unwrap()), time: Some(time.parse().unwrap()), allow_future };
+        let out = |time: &str, allow_future| Entry { description: None, ..entry(time, allow_future) };
+        let edit = |r#in: &str, force| EditArgs { description: None, append: None, id: None, r#in: Some(r#in.parse().unwrap()), out: None, date: None, force };
+
+        assert!(cmd_in(ctx(), entry("23:59", false)).is_err());
+        assert!(ctx().pnchs.0.is_empty());
+        cmd_in(ctx(), entry("8:30", false)).unwrap();
+        assert!(cmd_edit(ctx(), edit("9:15", false)).is_err());
+        cmd_edit(ctx(), edit("8:45", false)).unwrap();
+        assert!(cmd_out(ctx(), out("9:30", false), false, false).is_err());
+        assert!(ctx().pnchs.0[0].out.is_none());
+        cmd_out(ctx(), out("9:30", true), false, false).unwrap();
+        let pnch = &ctx().pnchs.0[0];
+        assert_eq!((pnch._in.to_string(), pnch.out.map(|out| out.to_string())), (String::from("8:45"), Some(String::from("9:30"))));
+        // Pre-logging an entry is allowed with the flag.
+        cmd_in(ctx(), entry("10:00", true)).unwrap();
+        assert_eq!(ctx().pnchs.0.len(), 2);
+    }
+
+    #[test]
+    fn profiles_have_their_own_databases() {
+        let dir = storage::Storage::temp("profiles").dir().to_owned();
+        let work = storage::Storage::in_dir(dir.clone(), None).unwrap();
+        let personal = storage::Storage::in_dir(dir.clone(), Some("personal")).unwrap();
+        add(&work, "A/work", "9", "10");
+        add(&personal, "HOME/fix the bike", "10", "11");
+        add(&work, "B/review", "11", "12");
+        note(&personal, 0, "new tyres");
+        cmd_config(Context::load(personal.clone()).unwrap(), None, pairs(&["relative-dates", "true"]), false).unwrap();
+
+        let entries = |storage: &storage::Storage| Context::load(storage.clone()).unwrap().pnchs.0
+            .iter()
+            .map(|pnch| (pnch.tag.as_ref().unwrap().tag.clone(), pnch.description.clone().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(entries(&work), vec![(String::from("A"), String::from("work")), (String::from("B"), String::from("review"))]);
+        assert_eq!(entries(&personal), vec![(String::from("HOME"), String::from("fix the bike"))]);
+        assert_eq!(Context::load(work.clone()).unwrap().tags.get(0).map(|tag| tag.tag), Some(String::from("A")));
+        assert!(Context::load(personal.clone()).unwrap().tags.find("A").is_none());
+        assert_eq!(notes(&work, note::Notes::NOTES_FILE_NAME), vec![]);
+        assert_eq!(notes(&personal, note::Notes::NOTES_FILE_NAME), vec![(0, String::from("new tyres"))]);
+        assert!(!config::Config::load(&work).unwrap().relative_dates);
+        assert!(config::Config::load(&personal).unwrap().relative_dates);
+
+        assert_eq!(personal.dir(), dir.join("profiles").join("personal"));
+        for profile in ["", "../work", "my profile"] {
+            assert!(storage::Storage::in_dir(dir.clone(), Some(profile)).is_err(), "{profile}");
+        }
+        let cli = Cli::parse_from(["pnch", "in", "--profile", "personal", "HOME/fix the bike"]);
+        assert_eq!(cli.profile.as_deref(), Some("personal"));
+    }
+
+    #[test]
+    fn long_descriptions_are_truncated() {
+        let storage = storage::Storage::temp("add-long-description");
+        add(&storage, &format!("A/{}", "é".repeat(50)), "9", "10");
+        let pnchs = Context::load(storage.clone()).unwrap().pnchs;
+        assert_eq!(pnchs.0[0].description, Some("é".repeat(40)));
+
+        let ctx = Context::load(storage.clone()).unwrap();
+        cmd_in(ctx, Entry { description: Some(format!("A/{}", "x".repeat(100)).parse().unwrap()), time: None, allow_future: false }).unwrap();
+        let pnchs = Context::load(storage.clone()).unwrap().pnchs;
+        assert_eq!(pnchs.0[1].description, Some("x".repeat(80)));
+    }
+
+    #[test]
+    fn renumbered_entries_are_grouped_in_ranges() {
+        assert_eq!(renumbered(&[(0, 0), (1, 1)], None), None);
+        let saved_ids = [(0, 0), (3, 1), (1, 2), (2, 3), (4, 4), (5, 6), (6, 5)];
+        assert_eq!(renumbered(&saved_ids, Some(3)).as_deref(), Some("#1-#2 are now #2-#3, #5 is now #6, #6 is now #5"));
+    }
+
+    #[test]
+    fn split_renumbers_the_later_entries() {
+        let storage = storage::Storage::temp("split-ids");
+        add(&storage, "A/first", "9", "11");
+        add(&storage, "A/second", "11", "12");
+        add(&storage, "A/third", "12", "13");
+        let mut pnchs = Context::load(storage.clone()).unwrap().pnchs;
+        let new_id = pnchs.split(0, "10".parse().unwrap()).unwrap().id;
+        let saved_ids = pnchs.saved_ids();
+        assert_eq!(saved_id(&saved_ids, new_id), 1);
+        assert_eq!(renumbered(&saved_ids, Some(new_id)).as_deref(), Some("#1-#2 are now #2-#3"));
+
+        let ctx = Context::load(storage.clone()).unwrap();
+        cmd_split(ctx, 0, "10".parse().unwrap(), Some("A/first".parse().unwrap())).unwrap();
+        let pnchs = Context::load(storage.clone()).unwrap().pnchs;
+        let descriptions = pnchs.0.iter().map(|pnch| (pnch.id, pnch._in.to_string())).collect::<Vec<_>>();
+        assert_eq!(descriptions, vec![(0, "9:00".into()), (1, "10:00".into()), (2, "11:00".into()), (3, "12:00".into())]);
+    }
+
+    #[test]
+    fn added_entry_gets_its_saved_id() {
+        let storage = storage::Storage::temp("add-ids");
+        add(&storage, "A/first", "9", "10");
+        add(&storage, "A/second", "11", "12");
+        let mut pnchs = Context::load(storage.clone()).unwrap().pnchs;
+        let clock = time::Clock::fixed(&"2024-05-13".parse().unwrap(), "12:00".parse().unwrap(), time::Offset::from_minutes(0));
+        let mut pnch = pnch::Pnch::new(2, &clock, "9:30".parse().unwrap(), None, None);
+        pnch.out = Some("10:30".parse().unwrap());
+        let overlaps = pnchs.add(pnch);
+        let saved_ids = pnchs.saved_ids();
+        assert_eq!(saved_id(&saved_ids, 2), 1);
+        assert_eq!(overlaps.iter().map(|id| saved_id(&saved_ids, *id)).collect::<Vec<_>>(), vec![0]);
+        assert_eq!(renumbered(&saved_ids, Some(2)).as_deref(), Some("#1 is now #2"));
+    }
+
+    #[test]
+    fn merged_entry_keeps_the_notes_of_both() {
+        let storage = storage::Storage::temp("merge-notes");
+        add(&storage, "A/work", "9", "10");
+        add(&storage, "A/work", "10", "11");
+        add(&storage, "B/other", "12", "13");
+        note(&storage, 0, "first");
+        note(&storage, 1, "second");
+        note(&storage, 2, "other");
+        let ctx = Context::load(storage.clone()).unwrap();
+        cmd_merge(ctx, 1, 0, false).unwrap();
+        assert_eq!(notes(&storage, note::Notes::NOTES_FILE_NAME), vec![
+            (0, String::from("first")),
+            (0, String::from("second")),
+            (1, String::from("other")),
+        ]);
+    }
+
+    #[test]
+    fn archived_notes_move_with_their_entry() {
+        let storage = storage::Storage::temp("archive-notes");
+        add_on(&storage, "2024-05-15", "A/kept", "9", "10");
+        add_on(&storage, "2024-05-13", "A/archived", "9", "10");
+        note(&storage, 0, "archived");
+        note(&storage, 1, "kept");
+        let ctx = Context::load(storage.clone()).unwrap();
+        cmd_archive(ctx, "2024-05-14".parse().unwrap()).unwrap();
+        assert_eq!(notes(&storage, note::Notes::NOTES_FILE_NAME), vec![(0, String::from("kept"))]);
+        assert_eq!(notes(&storage, &archive::Archive::notes_file_name(2024)), vec![(0, String::from("archived"))]);
+
+        // An entry archived later, but earlier in the year, comes first in the archive.
+        add_on(&storage, "2024-05-01", "A/older", "9", "10");
+        note(&storage, 0, "older");
+        let ctx = Context::load(storage.clone()).unwrap();
+        cmd_archive(ctx, "2024-05-14".parse().unwrap()).unwrap();
+        assert_eq!(notes(&storage, &archive::Archive::notes_file_name(2024)), vec![
+            (0, String::from("older")),
+            (1, String::from("archived")),
+        ]);
+
+        let ctx = Context::load(storage.clone()).unwrap();
+        let (mut pnchs, mut notes) = (ctx.pnchs, note::Notes::load(&storage).unwrap());
+        archive::Archive::extend(&mut pnchs, Some(&mut notes), &storage, &ctx.tags, &time::Date::min(), &time::Date::max()).unwrap();
+        let archived = pnchs.0.iter().find(|pnch| pnch.description.as_deref() == Some("archived")).unwrap();
+        assert_eq!(notes.of(archived.id).map(|note| note.text.as_str()).collect::<Vec<_>>(), vec!["archived"]);
+    }
+
+    #[test]
+    fn failed_tag_removal_changes_no_database() {
+        let storage = storage::Storage::temp("tags-rm-failed");
+        add(&storage, "A/first", "9", "10");
+        add(&storage, "B/second", "10", "11");
+        let (pnchs, tags) = (storage.load(pnch::Pnchs::PNCHS_FILE_NAME).unwrap(), storage.load(tag::Tags::TAGS_FILE_NAME).unwrap());
+        // The tags cannot be staged, after the entries were.
+        fs::create_dir(storage.dir().join(format!("{}.tmp", tag::Tags::TAGS_FILE_NAME))).unwrap();
+        let ctx = Context::load(storage.clone()).unwrap();
+        assert!(cmd_tags_rm(ctx, String::from("A"), None).is_err());
+        assert_eq!(storage.load(pnch::Pnchs::PNCHS_FILE_NAME).unwrap(), pnchs);
+        assert_eq!(storage.load(tag::Tags::TAGS_FILE_NAME).unwrap(), tags);
+
+        fs::remove_dir(storage.dir().join(format!("{}.tmp", tag::Tags::TAGS_FILE_NAME))).unwrap();
+        let ctx = Context::load(storage.clone()).unwrap();
+        cmd_tags_rm(ctx, String::from("A"), None).unwrap();
+        let ctx = Context::load(storage.clone()).unwrap();
+        let tags = ctx.pnchs.0.iter().map(|pnch| pnch.tag.as_ref().map(|tag| tag.tag.clone())).collect::<Vec<_>>();
+        assert_eq!(tags, vec![None, Some(String::from("B"))]);
+    }
+
+    #[test]
+    fn config_set_with_an_invalid_key_saves_nothing() {
+        let storage = storage::Storage::temp("config-invalid-key");
+        let ctx = Context::load(storage.clone()).unwrap();
+        cmd_config(ctx, None, pairs(&["print-color", "false"]), false).unwrap();
+        let before = storage.load(config::Config::CONFIG_FILE_NAME).unwrap();
+
+        let ctx = Context::load(storage.clone()).unwrap();
+        let set = ConfigCommands::Set { pairs: pairs(&["relative-dates", "true", "not-a-key", "1"]) };
+        assert!(cmd_config(ctx, Some(set), Vec::new(), false).is_err());
+
+        assert_eq!(storage.load(config::Config::CONFIG_FILE_NAME).unwrap(), before);
+        let config = config::Config::load(&storage).unwrap();
+        assert!(!config.relative_dates);
+        assert!(!config.print_color);
+    }
+
+    #[test]
+    fn config_set_with_an_invalid_value_saves_nothing() {
+        let storage = storage::Storage::temp("config-invalid-value");
+        let ctx = Context::load(storage.clone()).unwrap();
+        assert!(cmd_config(ctx, None, pairs(&["print-color", "false", "week-start", "someday"]), false).is_err());
+        assert!(storage.load(config::Config::CONFIG_FILE_NAME).unwrap().is_empty());
+    }
+
+    #[test]
+    fn config_dry_run_saves_nothing() {
+        let storage = storage::Storage::temp("config-dry-run");
+        let ctx = Context::load(storage.clone()).unwrap();
+        cmd_config(ctx, None, pairs(&["print-color", "false", "relative-dates", "true"]), true).unwrap();
+        assert!(storage.load(config::Config::CONFIG_FILE_NAME).unwrap().is_empty());
+
+        let ctx = Context::load(storage.clone()).unwrap();
+        cmd_config(ctx, Some(ConfigCommands::Reset { key: None }), Vec::new(), true).unwrap();
+        assert!(storage.load(config::Config::CONFIG_FILE_NAME).unwrap().is_empty());
+    }
+
+    #[test]
+    fn missing_days_skip_the_weekends_and_the_days_off() {
+        let storage = storage::Storage::temp("missing-days");
+        let ctx = Context::load(storage.clone()).unwrap();
+        cmd_config(ctx, None, pairs(&["days-off", "2024-05-15..2024-05-17"]), false).unwrap();
+        add(&storage, "A", "9:00", "10:00");
+
+        let ctx = Context::load(storage.clone()).unwrap();
+        let clock = time::Clock::fixed(&"2024-05-23".parse().unwrap(), "12:00".parse().unwrap(), time::Offset::from_minutes(0));
+        let filter = filter(&["--from", "2024-05-13", "--to", "2024-05-26"], "2024-05-23");
+        let (first, last, days) = missing_days(&filter, &ctx.config, &clock, |date| ctx.pnchs.0.iter().any(|pnch| pnch.date == *date));
+        assert_eq!((first, last), dates("2024-05-13", "2024-05-23"));
+        assert_eq!(days, ["2024-05-14", "2024-05-20", "2024-05-21", "2024-05-22", "2024-05-23"].map(|date| date.parse().unwrap()));
+    }
+
+    #[test]
+    fn missing_days_use_the_index_only_when_the_period_is_the_only_filter() {
+        assert!(ls_args(&["--missing-days", "--last", "2w"]).filters_only_period());
+        for args in [&["--grep", "review"][..], &["--min-duration", "1h"], &["--max-duration", "1h"], &["--id", "3"],
+            &["--open"], &["--closed"], &["--tag", "A"], &["--no-tag"], &["--exclude-tag", "A"], &["--include-archived"]] {
+            assert!(!ls_args(&[&["--missing-days"], args].concat()).filters_only_period(), "{args:?}");
+        }
+    }
+
+    #[test]
+    fn excluded_tags_match_by_prefix_unless_exact() {
+        let storage = storage::Storage::temp("exclude-tag-prefix");
+        add(&storage, "MEET-1/standup", "9:00", "9:15");
+        add(&storage, "meeting/review", "10:00", "11:00");
+        add(&storage, "PROJ-1/code", "11:00", "12:00");
+        let ctx = Context::load(storage.clone()).unwrap();
+        let kept = |args: &[&str]| {
+            let mut filter = filter(args, "2024-05-15");
+            filter.resolve_tags(&ctx.tags).unwrap();
+            ctx.pnchs.0.iter()
+                .filter(|pnch| !filter.is_excluded(pnch))
+                .map(|pnch| pnch.description.clone().unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(kept(&["--exclude-tag", "meet"]), ["code"]);
+        assert_eq!(kept(&["--exclude-tag", "MEET-1", "--tag-exact"]), ["review", "code"]);
+        assert_eq!(kept(&["--exclude-tag", "meet", "--tag-exact"]), ["standup", "review", "code"]);
+
+        let mut unknown = filter(&["--exclude-tag", "OTHER"], "2024-05-15");
+        assert!(unknown.resolve_tags(&ctx.tags).is_err());
+        assert!(Cli::try_parse_from(["pnch", "ls", "--tag-exact"]).is_err());
+    }
+
+    #[test]
+    fn month_report_shows_the_empty_months() {
+        let storage = storage::Storage::temp("report-empty-months");
+        add(&storage, "A", "9:00", "10:00");
+        let ctx = Context::load(storage.clone()).unwrap();
+        let months = |empty_months| report::MonthReport::new(&ctx.pnchs, empty_months, config::Config::default().duration_format)
+            .months
+            .iter()
+            .map(|total| (total.month.to_string(), total.duration.minutes))
+            .collect::<Vec<_>>();
+        assert_eq!(months(None), [(String::from("2024-05-01"), 60)]);
+        assert_eq!(months(Some(dates("2024-01-31", "2024-06-15"))), [
+            ("2024-01-01", 0), ("2024-02-01", 0), ("2024-03-01", 0), ("2024-04-01", 0), ("2024-05-01", 60), ("2024-06-01", 0),
+        ].map(|(month, minutes)| (String::from(month), minutes)));
+    }
+
+    #[test]
+    fn import_adds_the_rows_and_skips_the_future_ones() {
+        let storage = storage::Storage::temp("import");
+        add(&storage, "Z/existing", "9:30", "9:45");
+        let file = storage.dir().join("entries.csv");
+        fs::write(&file, "tag,description,date,in,out,duration_minutes\nA,first,2024-05-13,9:00,10:00,60\n,later,2024-05-20,9:00,10:00,60\n").unwrap();
+        let clock = time::Clock::fixed(&"2024-05-15".parse().unwrap(), "12:00".parse().unwrap(), time::Offset::from_minutes(0));
+
+        let ctx = Context { clock, ..Context::load(storage.clone()).unwrap() };
+        cmd_import(ctx, &file, None, false).unwrap();
+        let ctx = Context::load(storage.clone()).unwrap();
+        let descriptions = ctx.pnchs.0.iter().map(|pnch| pnch.description.clone().unwrap()).collect::<Vec<_>>();
+        assert_eq!(descriptions, vec!["first", "existing"]);
+        assert!(ctx.tags.find("A").is_some());
+
+        let ctx = Context { clock, ..Context::load(storage.clone()).unwrap() };
+        cmd_import(ctx, &file, None, true).unwrap();
+        assert_eq!(Context::load(storage.clone()).unwrap().pnchs.0.len(), 4);
+    }
 }
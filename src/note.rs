@@ -0,0 +1,201 @@
+use crate::{format, storage, time, error::GlobalError};
+
+/// A free-form note attached to a pnch, for what doesn't fit in its description.
+#[derive(Clone)]
+pub struct Note {
+    /// The id of the pnch the note is attached to.
+    pub pnch_id: u32,
+    /// When the note was written.
+    pub date: time::Date,
+    pub time: time::Time,
+    pub text: String,
+}
+
+impl Note {
+    /// size of the pnch id field in bytes
+    const PNCH_ID_SIZE: usize = 4;
+    /// size of the text length field in bytes
+    const LEN_SIZE: usize = 2;
+    /// size of a note in bytes, without its text
+    const HEADER_SIZE: usize = Self::PNCH_ID_SIZE + time::Date::SIZE + time::Time::SIZE
+        + Self::LEN_SIZE;
+    /// maximum size of the text in bytes
+    pub const MAX_TEXT_SIZE: usize = u16::MAX as usize;
+
+    /// A note of `pnch_id` written at the current time of `clock`.
+    pub fn new(pnch_id: u32, clock: &time::Clock, text: String) -> Self {
+        Self {
+            pnch_id,
+            date: clock.today(),
+            time: clock.now(),
+            text,
+        }
+    }
+}
+
+impl From<&Note> for Vec<u8> {
+    fn from(note: &Note) -> Self {
+        let mut buffer = Vec::with_capacity(Note::HEADER_SIZE + note.text.len());
+        buffer.extend_from_slice(&note.pnch_id.to_le_bytes());
+        buffer.extend_from_slice(&note.date.to_le_bytes());
+        buffer.extend_from_slice(&note.time.to_le_bytes());
+        buffer.extend_from_slice(&(note.text.len() as u16).to_le_bytes());
+        buffer.extend_from_slice(note.text.as_bytes());
+        buffer
+    }
+}
+
+impl std::fmt::Display for Note {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "      {} {} > ", self.date, self.time)?;
+        let mut lines = self.text.lines();
+        if let Some(line) = lines.next() {
+            write!(f, "{line}")?;
+        }
+        for line in lines {
+            write!(f, "\n        {line}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Every note, in the order they were written.
+///
+/// Unlike pnchs, notes don't have a fixed size: each note starts with a header containing the size
+/// of its text.
+#[derive(Clone, Default)]
+pub struct Notes(Vec<Note>);
+
+impl Notes {
+    pub const NOTES_FILE_NAME: &'static str = "notes.db";
+    /// The format of the notes database.
+    pub const FORMAT: format::Format = format::Format::new(*b"PNOT", 1, 1, "notes", format::Format::same_records);
+
+    pub fn load(storage: &storage::Storage) -> Result<Self, GlobalError> {
+        Self::load_file(storage, Self::NOTES_FILE_NAME)
+    }
+
+    /// Load the notes of a file with the same format as the notes database, like the notes of an
+    /// archive.
+    pub fn load_file(storage: &storage::Storage, file: &str) -> Result<Self, GlobalError> {
+        let buffer = Self::FORMAT.load(storage, file)?;
+        let mut notes = Vec::new();
+        let mut rest = &buffer[..];
+        while !rest.is_empty() {
+            let (header, text) = rest
+                .split_at_checked(Note::HEADER_SIZE)
+                .ok_or_else(|| GlobalError::wrong_byte_len("note", rest.len(), Note::HEADER_SIZE))?;
+            let (pnch_id_bytes, header) = header.split_at(Note::PNCH_ID_SIZE);
+            let (date_bytes, header) = header.split_at(time::Date::SIZE);
+            let (time_bytes, len_bytes) = header.split_at(time::Time::SIZE);
+            let pnch_id_bytes = pnch_id_bytes
+                .try_into()
+                .expect("split_at panics if not correct size");
+            let len_bytes = len_bytes
+                .try_into()
+                .expect("split_at panics if not correct size");
+            let len = u16::from_le_bytes(len_bytes) as usize;
+            let (text, next) = text
+                .split_at_checked(len)
+                .ok_or_else(|| GlobalError::wrong_byte_len("note", text.len(), len))?;
+            notes.push(Note {
+                pnch_id: u32::from_le_bytes(pnch_id_bytes),
+                date: date_bytes.try_into()?,
+                time: time_bytes.try_into()?,
+                text: String::from_utf8(text.to_vec())?,
+            });
+            rest = next;
+        }
+        Ok(Self(notes))
+    }
+
+    pub fn save(&self, storage: &storage::Storage) -> Result<(), GlobalError> {
+        self.save_file(storage, Self::NOTES_FILE_NAME)
+    }
+
+    pub fn save_file(&self, storage: &storage::Storage, file: &str) -> Result<(), GlobalError> {
+        let content = self.0
+            .iter()
+            .flat_map(Vec::from)
+            .collect::<Vec<u8>>();
+        storage.save(file, &Self::FORMAT.with_header(&content), "notes")
+    }
+
+    pub fn add(&mut self, note: Note) {
+        self.0.push(note);
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// The notes of a pnch, in the order they were written.
+    pub fn of(&self, pnch_id: u32) -> impl Iterator<Item = &Note> {
+        self.0.iter().filter(move |note| note.pnch_id == pnch_id)
+    }
+
+    /// Copy the notes of the pnch `pnch_id` to `notes`, attached to the pnch `new_id`.
+    pub fn copy_to(&self, pnch_id: u32, new_id: u32, notes: &mut Self) {
+        for note in self.of(pnch_id) {
+            notes.add(Note { pnch_id: new_id, ..note.clone() });
+        }
+    }
+
+    /// Attach the notes of the pnch `from` to the pnch `to`, like when `from` is merged into `to`.
+    pub fn reassign(storage: &storage::Storage, from: u32, to: u32) -> Result<(), GlobalError> {
+        let mut notes = Self::load(storage)?;
+        if !notes.0.iter().any(|note| note.pnch_id == from) {
+            return Ok(());
+        }
+        for note in notes.0.iter_mut().filter(|note| note.pnch_id == from) {
+            note.pnch_id = to;
+        }
+        notes.save(storage)
+    }
+
+    /// Update the notes once the pnchs are saved.
+    ///
+    /// The id of a pnch is its position in the pnchs database, so the ids change every time the
+    /// pnchs are saved. `ids` maps the id each pnch had when it was loaded to its new id. The notes
+    /// of the pnchs that are not in `ids` anymore were deleted with their pnch.
+    pub fn remap(storage: &storage::Storage, ids: &[(u32, u32)]) -> Result<(), GlobalError> {
+        Self::remap_file(storage, Self::NOTES_FILE_NAME, ids)
+    }
+
+    /// Update the notes of `file` once the pnchs they are attached to are saved, see
+    /// `Notes::remap`.
+    pub fn remap_file(storage: &storage::Storage, file: &str, ids: &[(u32, u32)]) -> Result<(), GlobalError> {
+        let mut notes = Self::load_file(storage, file)?;
+        if notes.0.is_empty() {
+            return Ok(());
+        }
+        notes.0.retain_mut(|note| match ids.iter().find(|(old, _)| *old == note.pnch_id) {
+            Some((_, new)) => {
+                note.pnch_id = *new;
+                true
+            }
+            None => false,
+        });
+        notes.save_file(storage, file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notes_without_header_are_migrated() {
+        let storage = storage::Storage::temp("notes-v0");
+        let clock = time::Clock::fixed(&"2024-05-13".parse().unwrap(), "9:30".parse().unwrap(), time::Offset::from_minutes(120));
+        let v0 = [Vec::from(&Note::new(3, &clock, String::from("first"))), Vec::from(&Note::new(4, &clock, String::from("second")))].concat();
+        storage.save(Notes::NOTES_FILE_NAME, &v0, "notes").unwrap();
+
+        let notes = Notes::load(&storage).unwrap();
+        let texts = notes.0.iter().map(|note| (note.pnch_id, note.text.as_str())).collect::<Vec<_>>();
+        assert_eq!(texts, vec![(3, "first"), (4, "second")]);
+        assert_eq!(storage.load(Notes::NOTES_FILE_NAME).unwrap(), Notes::FORMAT.with_header(&v0));
+        assert_eq!(storage.load("notes.db.v0.bak").unwrap(), v0);
+        assert_eq!(Notes::FORMAT.version(&storage, Notes::NOTES_FILE_NAME).unwrap(), 1);
+    }
+}
@@ -0,0 +1,249 @@
+use crate::error::GlobalError;
+use colored::*;
+
+/// A case insensitive pattern searched in a text, either a plain substring or a regular
+/// expression.
+///
+/// The regular expressions support a small subset of the usual syntax: literal characters, `.`,
+/// the `*`, `+` and `?` repetitions, classes like `[a-z]` or `[^0-9]`, the `\d`, `\w` and `\s`
+/// classes, the `^` and `$` anchors and alternatives separated by `|`. Groups are not supported.
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    Substring(Vec<char>),
+    Regex(Vec<Vec<Piece>>),
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Char(char),
+    Any,
+    Class { negated: bool, items: Vec<ClassItem> },
+    Start,
+    End,
+}
+
+#[derive(Debug, Clone)]
+enum ClassItem {
+    Char(char),
+    Range(char, char),
+    Digit,
+    Word,
+    Space,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Repeat {
+    One,
+    ZeroOrOne,
+    ZeroOrMore,
+    OneOrMore,
+}
+
+/// A node of a regular expression with how many times it can be repeated.
+#[derive(Debug, Clone)]
+pub struct Piece {
+    node: Node,
+    repeat: Repeat,
+}
+
+fn eq_ignore_case(a: char, b: char) -> bool {
+    a == b || a.to_lowercase().eq(b.to_lowercase())
+}
+
+impl ClassItem {
+    fn matches(&self, c: char) -> bool {
+        match *self {
+            Self::Char(item) => eq_ignore_case(item, c),
+            Self::Range(from, to) => {
+                let lower = c.to_lowercase().next().unwrap_or(c);
+                let upper = c.to_uppercase().next().unwrap_or(c);
+                (from..=to).contains(&c) || (from..=to).contains(&lower) || (from..=to).contains(&upper)
+            }
+            Self::Digit => c.is_ascii_digit(),
+            Self::Word => c.is_alphanumeric() || c == '_',
+            Self::Space => c.is_whitespace(),
+        }
+    }
+}
+
+impl Node {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Self::Char(node) => eq_ignore_case(*node, c),
+            Self::Any => true,
+            Self::Class { negated, items } => items.iter().any(|item| item.matches(c)) != *negated,
+            Self::Start | Self::End => false,
+        }
+    }
+}
+
+impl Pattern {
+    pub fn substring(value: &str) -> Self {
+        Self::Substring(value.chars().collect())
+    }
+
+    pub fn regex(value: &str) -> Result<Self, GlobalError> {
+        let mut branches = vec![Vec::new()];
+        let mut chars = value.chars().peekable();
+        while let Some(c) = chars.next() {
+            let node = match c {
+                '|' => {
+                    branches.push(Vec::new());
+                    continue;
+                }
+                '.' => Node::Any,
+                '^' => Node::Start,
+                '$' => Node::End,
+                '\\' => match chars.next() {
+                    Some(escaped) => Self::escape(escaped),
+                    None => return Err(GlobalError::pattern_invalid(value, "it ends with `\\`")),
+                },
+                '[' => {
+                    let negated = chars.next_if_eq(&'^').is_some();
+                    let mut items = Vec::new();
+                    loop {
+                        let item = match chars.next() {
+                            Some(']') if !items.is_empty() => break,
+                            Some('\\') => match chars.next().map(Self::escape) {
+                                Some(Node::Class { items: escaped, .. }) => {
+                                    items.extend(escaped);
+                                    continue;
+                                }
+                                Some(Node::Char(c)) => c,
+                                _ => return Err(GlobalError::pattern_invalid(value, "a class is not closed")),
+                            },
+                            Some(c) => c,
+                            None => return Err(GlobalError::pattern_invalid(value, "a class is not closed")),
+                        };
+                        match chars.peek() {
+                            Some('-') => {
+                                chars.next();
+                                match chars.next() {
+                                    Some(']') => {
+                                        items.push(ClassItem::Char(item));
+                                        items.push(ClassItem::Char('-'));
+                                        break;
+                                    }
+                                    Some(to) if to >= item => items.push(ClassItem::Range(item, to)),
+                                    Some(_) => return Err(GlobalError::pattern_invalid(value, "a range is reversed")),
+                                    None => return Err(GlobalError::pattern_invalid(value, "a class is not closed")),
+                                }
+                            }
+                            _ => items.push(ClassItem::Char(item)),
+                        }
+                    }
+                    Node::Class { negated, items }
+                }
+                '*' | '+' | '?' => {
+                    return Err(GlobalError::pattern_invalid(value, "a repetition does not follow anything"));
+                }
+                '(' | ')' => return Err(GlobalError::pattern_invalid(value, "groups are not supported")),
+                c => Node::Char(c),
+            };
+            let repeat = match chars.next_if(|c| matches!(c, '*' | '+' | '?')) {
+                Some('*') => Repeat::ZeroOrMore,
+                Some('+') => Repeat::OneOrMore,
+                Some('?') => Repeat::ZeroOrOne,
+                _ => Repeat::One,
+            };
+            if repeat != Repeat::One && matches!(node, Node::Start | Node::End) {
+                return Err(GlobalError::pattern_invalid(value, "an anchor cannot be repeated"));
+            }
+            branches
+                .last_mut()
+                .expect("there is always a branch")
+                .push(Piece { node, repeat });
+        }
+        Ok(Self::Regex(branches))
+    }
+
+    fn escape(c: char) -> Node {
+        let class = |item| Node::Class { negated: false, items: vec![item] };
+        match c {
+            'd' => class(ClassItem::Digit),
+            'w' => class(ClassItem::Word),
+            's' => class(ClassItem::Space),
+            c => Node::Char(c),
+        }
+    }
+
+    /// The first match in `text`, as a range of bytes.
+    pub fn find(&self, text: &str) -> Option<std::ops::Range<usize>> {
+        self.find_from(text, 0)
+    }
+
+    fn find_from(&self, text: &str, from: usize) -> Option<std::ops::Range<usize>> {
+        let chars = text
+            .char_indices()
+            .collect::<Vec<_>>();
+        let byte = |idx: usize| chars.get(idx).map(|(byte, _)| *byte).unwrap_or(text.len());
+        let chars_only = chars.iter().map(|(_, c)| *c).collect::<Vec<_>>();
+        let start = chars.iter().position(|(byte, _)| *byte >= from).unwrap_or(chars.len());
+        (start..=chars_only.len()).find_map(|start| {
+            let end = match self {
+                Self::Substring(pattern) => {
+                    let end = start + pattern.len();
+                    let candidate = chars_only.get(start..end)?;
+                    candidate
+                        .iter()
+                        .zip(pattern)
+                        .all(|(a, b)| eq_ignore_case(*a, *b))
+                        .then_some(end)
+                }
+                Self::Regex(branches) => branches
+                    .iter()
+                    .find_map(|pieces| Self::match_here(pieces, &chars_only, start)),
+            }?;
+            Some(byte(start)..byte(end))
+        })
+    }
+
+    /// Match the pieces at the position `pos` of the text and return where the match ends. The
+    /// repetitions are greedy and backtrack when the rest of the pieces don't match.
+    fn match_here(pieces: &[Piece], text: &[char], pos: usize) -> Option<usize> {
+        let Some((piece, rest)) = pieces.split_first() else {
+            return Some(pos);
+        };
+        match piece.node {
+            Node::Start => return if pos == 0 { Self::match_here(rest, text, pos) } else { None },
+            Node::End => return if pos == text.len() { Self::match_here(rest, text, pos) } else { None },
+            _ => {}
+        }
+        let (min, max) = match piece.repeat {
+            Repeat::One => (1, 1),
+            Repeat::ZeroOrOne => (0, 1),
+            Repeat::ZeroOrMore => (0, usize::MAX),
+            Repeat::OneOrMore => (1, usize::MAX),
+        };
+        let count = text[pos..]
+            .iter()
+            .take(max)
+            .take_while(|c| piece.node.matches(**c))
+            .count();
+        (min..=count)
+            .rev()
+            .find_map(|count| Self::match_here(rest, text, pos + count))
+    }
+
+    /// Highlight every match in `text`.
+    pub fn highlight(&self, text: &str) -> String {
+        let mut highlighted = String::new();
+        let mut from = 0;
+        while let Some(range) = self.find_from(text, from) {
+            highlighted.push_str(&text[from..range.start]);
+            highlighted.push_str(&text[range.clone()].yellow().bold().to_string());
+            from = match range.is_empty() {
+                true => match text[range.end..].chars().next() {
+                    Some(c) => {
+                        highlighted.push(c);
+                        range.end + c.len_utf8()
+                    }
+                    None => return highlighted,
+                },
+                false => range.end,
+            };
+        }
+        highlighted.push_str(&text[from..]);
+        highlighted
+    }
+}
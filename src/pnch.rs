@@ -1,12 +1,12 @@
-use std::{str, fmt::Write};
-use crate::{storage, time, tag, error::GlobalError};
+use std::{str, fmt::Write, io::{Read, Seek}};
+use crate::{storage, time, tag, index, note, pattern, template, format, error::GlobalError};
 use colored::*;
 
 /// A pnch is an activity.
 ///
 /// It is represented with a beginning (in), an end (out), a tag which helps categorize the
 /// activity and a description which differentiate between each activity with a same tag.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Pnch {
     /// The id of the entry
     pub id: u32,
@@ -20,6 +20,16 @@ pub struct Pnch {
     pub tag: Option<tag::Tag>,
     /// The description of the activity.
     pub description: Option<String>,
+    /// How the duration is rounded. It is not saved, see `Pnchs::rounded`.
+    pub rounding: time::Rounding,
+    /// The UTC offset when the activity started. The pnchs saved without one are in the current
+    /// local time.
+    pub in_offset: Option<time::Offset>,
+    /// The UTC offset when the activity ended, see `in_offset`.
+    pub out_offset: Option<time::Offset>,
+    /// The pnch was opened by `pnch resume`, it continues the pnch closed by the break. See
+    /// `Pnchs::chains`.
+    pub resumed: bool,
 }
 
 impl Pnch {
@@ -33,31 +43,57 @@ impl Pnch {
     const TAG_ID_SIZE: usize = tag::Tag::ID_SIZE;
     /// size of the description field in bytes
     const DESCRIPTION_SIZE: usize = 80;
+    /// size of the seconds of the in and out times in bytes
+    const SECONDS_SIZE: usize = 2;
+    /// size of the UTC offsets of the in and out times in bytes
+    const OFFSETS_SIZE: usize = 2 * time::Offset::SIZE;
+    /// size of the flags in bytes
+    const FLAGS_SIZE: usize = 1;
+    /// flag of a pnch opened by `pnch resume`
+    const RESUMED_FLAG: u8 = 0b1;
+    /// printed instead of the duration of a pnch ending before it starts
+    const INCONSISTENT_LABEL: &'static str = "!invalid";
+    /// total size of a pnch in the version 1 of the format, without its seconds and offsets.
+    const V1_SIZE: usize = Self::DATE_SIZE + Self::TAG_ID_SIZE +  Self::OUT_SIZE + Self::IN_SIZE + Self::DESCRIPTION_SIZE;
+    /// total size of a pnch in the version 2 of the format, without its flags.
+    const V2_SIZE: usize = Self::V1_SIZE + Self::SECONDS_SIZE + Self::OFFSETS_SIZE;
     /// total size of a pnch when saved in a file in bytes.
-    const SIZE: usize = Self::DATE_SIZE + Self::TAG_ID_SIZE +  Self::OUT_SIZE + Self::IN_SIZE + Self::DESCRIPTION_SIZE;
+    const SIZE: usize = Self::V2_SIZE + Self::FLAGS_SIZE;
 
-    pub fn new(id: u32, time: time::Time, tag: Option<tag::Tag>, description: Option<String>) -> Self {
+    /// An open pnch of today, started at `time`.
+    pub fn new(id: u32, clock: &time::Clock, time: time::Time, tag: Option<tag::Tag>, description: Option<String>) -> Self {
         Self {
             id,
             _in: time,
             out: None,
-            date: time::Date::today(),
+            date: clock.today(),
             description,
             tag,
+            rounding: time::Rounding::None,
+            in_offset: clock.offset(),
+            out_offset: None,
+            resumed: false,
         }
     }
 
-    pub fn out(&mut self, time: time::Time, tag: Option<tag::Tag>, description: Option<String>) -> Result<(), GlobalError> {
+    /// The pnch printed with its times in `format` and its duration in `duration_format`.
+    /// Printing the pnch directly uses the 24-hour clock.
+    pub fn display(&self, format: time::TimeFormat, duration_format: time::DurationFormat) -> PnchDisplay<'_> {
+        PnchDisplay(self, format, duration_format)
+    }
+
+    /// Close the pnch at `time`, punched with the UTC offset `offset`.
+    pub fn out(&mut self, time: time::Time, offset: Option<time::Offset>, tag: Option<tag::Tag>, description: Option<String>) -> Result<(), GlobalError> {
         if self.out.is_some() {
             return Err(GlobalError::pnch_already_closed());
         }
-        if time < self._in {
+        if self.until(time, offset) < time::Duration::zero() {
             return Err(GlobalError::pnch_out_before_in(self._in, time));
         }
         if let Some(desc) = description {
             if self.description.is_some() {
                 return Err(GlobalError::desc_already_specified(
-                    &tag.map(|t| t.to_string()).unwrap_or(String::new()),
+                    &tag.map(|t| t.to_string()).unwrap_or_default(),
                     &desc)
                 );
             }
@@ -68,9 +104,87 @@ impl Pnch {
             return Err(GlobalError::desc_not_specified());
         }
         self.out = Some(time);
+        self.out_offset = offset;
+        Ok(())
+    }
+
+    /// Change the start or the end of the pnch, or both. The new times are checked together, so
+    /// both bounds can be moved past each other in a single edit, and the pnch is left untouched
+    /// when the end is before the start.
+    pub fn set_times(&mut self, _in: Option<time::Time>, out: Option<time::Time>) -> Result<(), GlobalError> {
+        let mut edited = self.clone();
+        if let Some(_in) = _in {
+            edited._in = _in;
+        }
+        if let Some(out) = out {
+            edited.out = Some(out);
+        }
+        if let Some(out) = edited.out.filter(|out| edited.until(*out, edited.out_offset) < time::Duration::zero()) {
+            return Err(GlobalError::pnch_out_before_in(edited._in, out));
+        }
+        *self = edited;
         Ok(())
     }
 
+    /// Split a closed pnch in two at the given time. This pnch ends at `at` and the returned pnch,
+    /// with the same tag and description, starts at `at` and ends at the original out time.
+    ///
+    /// The returned pnch has the id `id` and must be added to the pnchs.
+    pub fn split(&mut self, id: u32, at: time::Time) -> Result<Self, GlobalError> {
+        let out = self.out.ok_or_else(GlobalError::pnch_split_open)?;
+        if at <= self._in || self.until(at, self.in_offset) >= self.until(out, self.out_offset) {
+            return Err(GlobalError::pnch_split_out_of_range(self._in, out, at));
+        }
+        self.out = Some(at);
+        // `at` is in the offset of the start.
+        let out_offset = std::mem::replace(&mut self.out_offset, self.in_offset);
+        Ok(Self {
+            id,
+            date: self.date.clone(),
+            _in: at,
+            out: Some(out),
+            tag: self.tag.clone(),
+            description: self.description.clone(),
+            rounding: self.rounding,
+            in_offset: self.in_offset,
+            out_offset,
+            resumed: false,
+        })
+    }
+
+    /// Set the description, truncated to the size it can take in the database. Returns `true`
+    /// when it was truncated.
+    pub fn set_description(&mut self, mut description: String) -> bool {
+        let truncated = Self::truncate_description(&mut description);
+        self.description = Some(description);
+        truncated
+    }
+
+    /// Truncate `description` to the size it can take in the database, on a character boundary.
+    /// Returns `true` when it was truncated.
+    pub fn truncate_description(description: &mut String) -> bool {
+        let truncated = description.len() > Self::DESCRIPTION_SIZE;
+        if truncated {
+            let end = (0..=Self::DESCRIPTION_SIZE)
+                .rev()
+                .find(|&idx| description.is_char_boundary(idx))
+                .unwrap_or_default();
+            description.truncate(end);
+        }
+        truncated
+    }
+
+    /// Append `text` to the description, separated by `; `. Without a description, the text
+    /// becomes the description. Returns `true` when the description was truncated, see
+    /// `Pnch::set_description`.
+    pub fn append_description(&mut self, text: &str) -> bool {
+        let description = match self.description.take() {
+            Some(description) => format!("{description}; {text}"),
+            None => text.to_owned(),
+        };
+        self.set_description(description)
+    }
+
     fn try_from(id: u32, chunk: &[u8], tags: &tag::Tags) -> Result<Self, GlobalError> {
         if chunk.len() != Self::SIZE {
             return Err(GlobalError::wrong_byte_len("pnch", chunk.len(), Self::SIZE));
@@ -78,7 +192,11 @@ impl Pnch {
         let (date_bytes, chunk) = chunk.split_at(Self::DATE_SIZE);
         let (in_bytes, chunk) = chunk.split_at(Self::IN_SIZE);
         let (out_bytes, chunk) = chunk.split_at(Self::OUT_SIZE);
-        let (tag_id_bytes, description_bytes) = chunk.split_at(Self::TAG_ID_SIZE);
+        let (tag_id_bytes, chunk) = chunk.split_at(Self::TAG_ID_SIZE);
+        let (description_bytes, chunk) = chunk.split_at(Self::DESCRIPTION_SIZE);
+        let (seconds, chunk) = chunk.split_at(Self::SECONDS_SIZE);
+        let (offsets, flags) = chunk.split_at(Self::OFFSETS_SIZE);
+        let (in_offset_bytes, out_offset_bytes) = offsets.split_at(time::Offset::SIZE);
         let description_bytes = description_bytes
             .iter()
             .copied()
@@ -90,11 +208,11 @@ impl Pnch {
             .expect("split_at panics if not correct size");
         let tag = match u32::from_le_bytes(tag_id_bytes) {
             0xFFFF => None,
-            tag_id @ _ => tags.get(tag_id)
+            tag_id => tags.get(tag_id)
         };
         let out = match out_bytes {
             &[0xFF, 0xFF] => None,
-            bytes @ _ => Some(bytes.try_into()?),
+            bytes => Some(time::Time::try_from(bytes)?.with_seconds(seconds[1])),
         };
         let description = match description_bytes.len() {
             0 => None,
@@ -103,15 +221,77 @@ impl Pnch {
         Ok(Pnch {
             id,
             date: date_bytes.try_into()?,
-            _in: in_bytes.try_into()?,
+            _in: time::Time::try_from(in_bytes)?.with_seconds(seconds[0]),
             out,
             tag,
-            description
+            description,
+            rounding: time::Rounding::None,
+            in_offset: time::Offset::from_le_bytes(in_offset_bytes.try_into().expect("split_at panics if not correct size")),
+            out_offset: time::Offset::from_le_bytes(out_offset_bytes.try_into().expect("split_at panics if not correct size")),
+            resumed: flags[0] & Self::RESUMED_FLAG != 0,
         })
     }
 
+    /// The duration of a closed pnch. A pnch ending before it starts, which can only be saved by
+    /// editing the database, has no duration either.
     pub fn duration(&self) -> Option<time::Duration> {
-        self.out.map(|out| out - self._in)
+        let duration = self.until(self.out?, self.out_offset);
+        (duration >= time::Duration::zero()).then(|| self.rounding.apply(duration))
+    }
+
+    /// The pnch ends before it starts, see `Pnch::duration`.
+    pub fn is_inconsistent(&self) -> bool {
+        self.out.is_some_and(|out| self.until(out, self.out_offset) < time::Duration::zero())
+    }
+
+    /// The duration of the pnch, where an open pnch is considered to end at `running.now`.
+    pub fn elapsed(&self, running: &Running) -> time::Duration {
+        match self.out {
+            Some(_) => self.duration().unwrap_or(time::Duration::zero()),
+            None => self.until(running.now, running.offset),
+        }
+    }
+
+    /// The time from the start of the pnch to `time`, taken with the UTC offset `offset`. The
+    /// times are compared as local times when one of the offsets is not known.
+    pub fn until(&self, time: time::Time, offset: Option<time::Offset>) -> time::Duration {
+        let shift = match (self.in_offset, offset) {
+            (Some(_in), Some(out)) => _in - out,
+            _ => time::Duration::zero(),
+        };
+        time - self._in + shift
+    }
+
+    /// The pnch as if it was punched out now, to count an open pnch in the totals. A pnch does
+    /// not span midnight: a pnch opened on a previous day is punched out at the end of its day,
+    /// and a pnch opened in the future lasts zero minutes.
+    pub fn punched_out_now(&self, running: &Running) -> Self {
+        let (out, out_offset) = match self.out {
+            Some(out) => (out, self.out_offset),
+            None if self.date == running.today => (std::cmp::max(running.now, self._in), running.offset),
+            None if self.date < running.today => (time::Time::end_of_day(), self.in_offset),
+            None => (self._in, self.in_offset),
+        };
+        Self { out: Some(out), out_offset, ..self.clone() }
+    }
+}
+
+/// The moment the open pnchs are counted until, for the totals that include them.
+#[derive(Debug, Clone)]
+pub struct Running {
+    pub today: time::Date,
+    pub now: time::Time,
+    pub offset: Option<time::Offset>,
+}
+
+impl Running {
+    /// The current moment of `clock`.
+    pub fn at(clock: &time::Clock) -> Self {
+        Self {
+            today: clock.today(),
+            now: clock.now(),
+            offset: clock.offset(),
+        }
     }
 }
 
@@ -132,24 +312,43 @@ impl From<&Pnch> for Vec<u8> {
         if let Some(description) = &pnch.description {
             buffer.extend_from_slice(description.as_bytes());
         }
-        buffer.append(&mut vec![0; Pnch::SIZE - buffer.len()]);
+        buffer.append(&mut vec![0; Pnch::V1_SIZE - buffer.len()]);
+        buffer.push(pnch._in.seconds());
+        buffer.push(pnch.out.map(|out| out.seconds()).unwrap_or(0));
+        buffer.extend_from_slice(&time::Offset::to_le_bytes(pnch.in_offset));
+        buffer.extend_from_slice(&time::Offset::to_le_bytes(pnch.out_offset));
+        buffer.push(if pnch.resumed { Pnch::RESUMED_FLAG } else { 0 });
         buffer
     }
 }
 
 impl std::fmt::Display for Pnch {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "  #{} >", self.id)?;
-        match self.out {
-            Some(out) => writeln!(f, " From {} to {out} ({})",
-                self._in, out - self._in)?,
-            None => writeln!(f, " Since {} ", self._in)?,
+        write!(f, "{}", self.display(time::TimeFormat::H24, time::DurationFormat::Clock))
+    }
+}
+
+/// A pnch printed with the times and duration in a given format, see `Pnch::display`.
+pub struct PnchDisplay<'a>(&'a Pnch, time::TimeFormat, time::DurationFormat);
+
+impl std::fmt::Display for PnchDisplay<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let PnchDisplay(pnch, format, duration_format) = self;
+        write!(f, "  #{} >", pnch.id)?;
+        let duration = match pnch.duration() {
+            Some(duration) => duration.format_long(*duration_format),
+            None => String::from(Pnch::INCONSISTENT_LABEL),
+        };
+        match pnch.out {
+            Some(out) => writeln!(f, " From {} to {} ({duration})",
+                pnch._in.format(*format), out.format(*format))?,
+            None => writeln!(f, " Since {} ", pnch._in.format(*format))?,
         }
-        match &self.tag {
-            Some(tag) => write!(f, "    {tag} ")?,
+        match &pnch.tag {
+            Some(tag) => write!(f, "    {} ", tag.paint(&tag.to_string()))?,
             _ => write!(f, "    [---] ")?,
         }
-        match &self.description {
+        match &pnch.description {
             Some(description) => write!(f, "{description}")?,
             _ => write!(f, "no description")?,
         }
@@ -165,7 +364,7 @@ impl std::cmp::Ord for Pnch {
 
 impl std::cmp::PartialOrd for Pnch {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        (&self.date, self._in).partial_cmp(&(&other.date, other._in))
+        Some(self.cmp(other))
     }
 }
 
@@ -192,224 +391,2404 @@ impl std::str::FromStr for Description {
     }
 }
 
-/// A group of pnch.
-pub struct Pnchs(pub Vec<Pnch>);
+/// Options changing how pnchs are rendered in the list and table formats.
+#[derive(Clone)]
+pub struct DisplayOptions {
+    /// The date of today, used to render relative dates.
+    pub today: time::Date,
+    /// Render the dates of the last week as `Today`, `Yesterday` or their weekday name.
+    pub relative_dates: bool,
+    /// Highlight the matches of this pattern in the descriptions.
+    pub highlight: Option<pattern::Pattern>,
+    /// Print these notes under their pnch in the list.
+    pub notes: Option<note::Notes>,
+    /// Add a row with the total of each day in the table.
+    pub day_totals: bool,
+    /// Print the total duration of the pnchs before them.
+    pub summary: bool,
+    /// The width of the table, its description column fills the space left by the other columns.
+    /// The table has fixed widths when it is `None`.
+    pub width: Option<usize>,
+    /// Cut the cells of the table longer than their column, ending them with `…`.
+    pub truncate: bool,
+    /// The days without pnchs to show between the pnchs, sorted. The pnchs must be sorted by date.
+    pub empty_days: Vec<time::Date>,
+    /// Print the ISO week number after the first date of each week, and separate the weeks in the
+    /// table.
+    pub show_week: bool,
+    /// The pnchs are all on the same day: the list prints the date once in the summary instead of
+    /// a heading.
+    pub single_day: bool,
+    /// Count the open pnchs in the totals until this moment. The pnchs are still printed as
+    /// open.
+    pub running: Option<Running>,
+    /// Print the short name of the weekday after the dates, like `2024-05-11 Sat`.
+    pub show_weekday: bool,
+    /// The clock used to print the times.
+    pub time_format: time::TimeFormat,
+    /// The current UTC offset, set to print the offset after the times of the table punched with
+    /// another offset.
+    pub offset: Option<time::Offset>,
+    /// How the durations and totals are printed.
+    pub duration_format: time::DurationFormat,
+}
 
-impl Pnchs {
-    const PNCHS_FILE_NAME: &'static str = "pnchs.db";
+impl DisplayOptions {
+    /// The options without highlight, notes or day totals.
+    pub fn new(today: time::Date, relative_dates: bool) -> Self {
+        Self {
+            today,
+            relative_dates,
+            highlight: None,
+            notes: None,
+            day_totals: false,
+            summary: true,
+            width: None,
+            truncate: true,
+            empty_days: Vec::new(),
+            show_week: false,
+            single_day: false,
+            running: None,
+            show_weekday: false,
+            time_format: time::TimeFormat::H24,
+            offset: None,
+            duration_format: time::DurationFormat::Clock,
+        }
+    }
 
-    pub fn load(tags: &tag::Tags) -> Result<Self, GlobalError> {
-        let buffer = storage::load(Self::PNCHS_FILE_NAME)?;
-        let mut pnchs = buffer
-            .chunks_exact(Pnch::SIZE)
-            .into_iter()
-            .enumerate()
-            .map(|(id, chunk)| Pnch::try_from(id as u32, chunk, tags))
-            .collect::<Result<Vec<Pnch>, GlobalError>>()?;
-        pnchs.sort();
-        Ok(Self(pnchs))
+    /// The offset of a time when it is printed, see `offset`.
+    fn other_offset(&self, offset: Option<time::Offset>) -> Option<time::Offset> {
+        offset.filter(|offset| self.offset.is_some_and(|current| current != *offset))
     }
 
-    pub fn _in(&mut self, pnch: Pnch) -> Result<(), GlobalError> {
-        match self.0.last() {
-            Some(pnch) if pnch.out.is_none() => {
-                return Err(GlobalError::pnch_already_open());
-            }
-            _ => {
-                self.0.push(pnch);
-            }
+    /// The time in the table, followed by its offset when it is printed.
+    fn time_cell(&self, time: time::Time, offset: Option<time::Offset>) -> String {
+        match self.other_offset(offset) {
+            Some(offset) => format!("{} {offset}", time.format(self.time_format)),
+            None => time.format(self.time_format),
         }
-        Ok(())
     }
 
-    pub fn get(&mut self, id: u32) -> Option<&mut Pnch> {
-        self.0.iter_mut().find(|pnch| pnch.id == id)
+    /// The heading of a date, used in the list and in the date cells of the table. The absolute
+    /// date is kept in parentheses after a relative date.
+    fn date_heading(&self, date: &time::Date) -> String {
+        match self.relative(date) {
+            Some(relative) => format!("{relative} ({date})"),
+            None => self.absolute(date),
+        }
     }
 
-    pub fn get_last(&mut self) -> Option<&mut Pnch> {
-        self.0.last_mut()
+    /// The date, followed by its weekday when `show_weekday` is set. A relative date already
+    /// tells the day, so it is not used after one.
+    fn absolute(&self, date: &time::Date) -> String {
+        match (self.show_weekday, date.short_weekday()) {
+            (true, Some(weekday)) => format!("{date} {weekday}"),
+            _ => date.to_string(),
+        }
+    }
+
+    /// The duration of a pnch in the totals and whether it is an open pnch counted until now.
+    fn counted_duration(&self, pnch: &Pnch) -> (time::Duration, bool) {
+        match (&self.running, pnch.duration()) {
+            (_, Some(duration)) => (duration, false),
+            (Some(running), None) if pnch.out.is_none() => {
+                (pnch.punched_out_now(running).duration().unwrap_or(time::Duration::zero()), true)
+            }
+            _ => (time::Duration::zero(), false),
+        }
     }
 
-    pub fn save(&self) -> Result<(), GlobalError> {
-        let path = storage::build_path(Self::PNCHS_FILE_NAME)?;
-        let content = self.0
+    /// The summary printed before the pnchs, with a note when an open pnch is counted until now.
+    fn summary(&self, pnchs: &Pnchs) -> String {
+        let (total, running) = pnchs.0
             .iter()
-            .map(|pnch| Vec::from(pnch))
-            .flatten()
-            .collect::<Vec<u8>>();
-        std::fs::write(path, content)
-            .map_err(|_| GlobalError::fs("save", "pnchs"))?;
-        Ok(())
+            .map(|pnch| self.counted_duration(pnch))
+            .fold((time::Duration::zero(), false), |(total, running), (duration, open)| {
+                (total + duration, running || open)
+            });
+        let total = total.format_long(self.duration_format);
+        match running {
+            true => format!("You were punched in for {total}, counting the open entry until now"),
+            false => format!("You were punched in for {total}"),
+        }
     }
 
-    pub fn into_csv(self) -> Result<String, GlobalError> {
-        self.0
-            .into_iter()
-            .map(|pnch| {
-                let mut line = String::new();
-                match pnch.tag {
-                    Some(tag) => write!(&mut line, "{},", tag.tag)?,
-                    None => write!(&mut line, ",")?,
-                }
-                write!(&mut line, "{},", pnch.description.unwrap_or_default())?;
-                write!(&mut line, "{},", pnch.date)?;
-                write!(&mut line, "{},", pnch._in)?;
-                match pnch.out {
-                    Some(out) => write!(&mut line, "{out}\n")?,
-                    None => write!(&mut line, "\n")?,
-                }
-                Ok(line)
-            })
-            .collect::<Result<String, std::fmt::Error>>()
-            .map_err(|_| GlobalError::formatting("csv"))
+    /// The ISO week number of `date`, like `W20`, when `show_week` is set and `date` is not in the
+    /// same week as the previous date, which is updated.
+    fn new_week(&self, date: &time::Date, week: &mut Option<(i32, u8)>) -> Option<String> {
+        let date_week = date.iso_week();
+        if !self.show_week || date_week == *week {
+            return None;
+        }
+        *week = date_week;
+        date_week.map(|(_, number)| format!("W{number}"))
     }
 
-    pub fn into_table(self) -> PnchsTable {
-        PnchsTable(self)
+    fn relative(&self, date: &time::Date) -> Option<String> {
+        if !self.relative_dates {
+            return None;
+        }
+        date.relative_to(&self.today)
     }
+}
 
-    pub fn duration(&self) -> time::Duration {
-        self.0
+/// A part of a `Split`.
+#[derive(Debug, Clone)]
+pub struct SplitPart {
+    pub tag: String,
+    pub description: Option<String>,
+    /// The duration of the part. `None` is the rest of the pnch once the other parts are removed.
+    pub duration: Option<time::Duration>,
+}
+
+/// How to split a pnch in consecutive pnchs with different tags.
+///
+/// A split can be created from a string in the format `tag[:description]=duration,...` where the
+/// duration of one of the parts can be `rest`. For example `ISSUE-1=1h30m,ISSUE-2:Review=rest`.
+#[derive(Debug, Clone)]
+pub struct Split(pub Vec<SplitPart>);
+
+impl Split {
+    /// Hint on how to format a split as a string.
+    const FORMAT_HINT: &'static str
+        = "`tag[:description]=duration,...` where one of the durations can be `rest`, like `ISSUE-1=1h30m,ISSUE-2=rest`";
+
+    /// The duration of each part when splitting a pnch of the given duration.
+    ///
+    /// The parts must cover the whole pnch: either their durations add up to the duration of the
+    /// pnch or a `rest` part takes the remaining time.
+    pub fn durations(&self, total: time::Duration) -> Result<Vec<time::Duration>, GlobalError> {
+        let specified = self.0
+            .iter()
+            .filter_map(|part| part.duration)
+            .sum::<time::Duration>();
+        let has_rest = self.0.iter().any(|part| part.duration.is_none());
+        if specified > total || (!has_rest && specified != total) {
+            return Err(GlobalError::split_wrong_duration(specified, total));
+        }
+        let rest = total - specified;
+        Ok(self.0
             .iter()
-            .filter_map(|pnch| pnch.duration())
-            .fold(time::Duration::zero(), |total, duration| {
-                total + duration
+            .map(|part| part.duration.unwrap_or(rest))
+            .collect())
+    }
+}
+
+impl std::str::FromStr for Split {
+    type Err = GlobalError;
+    fn from_str(value: &str) -> Result<Self, GlobalError> {
+        let parts = value
+            .split(',')
+            .map(|part| {
+                let (tag_and_description, duration_str) = part
+                    .rsplit_once('=')
+                    .ok_or_else(|| GlobalError::parse(Self::FORMAT_HINT))?;
+                let duration = match duration_str.trim() {
+                    "rest" => None,
+                    duration_str => match duration_str.parse::<time::Duration>()? {
+                        duration if duration > time::Duration::zero() => Some(duration),
+                        _ => return Err(GlobalError::parse(Self::FORMAT_HINT)),
+                    }
+                };
+                let (tag, description) = match tag_and_description.split_once(':') {
+                    Some((tag, description)) => (tag, Some(description.to_owned())),
+                    None => (tag_and_description, None),
+                };
+                if tag.is_empty() {
+                    return Err(GlobalError::parse(Self::FORMAT_HINT));
+                }
+                tag::Tag::check_name(tag)?;
+                Ok(SplitPart { tag: tag.to_owned(), description, duration })
             })
+            .collect::<Result<Vec<_>, GlobalError>>()?;
+        if parts.iter().filter(|part| part.duration.is_none()).count() > 1 {
+            return Err(GlobalError::parse(Self::FORMAT_HINT));
+        }
+        Ok(Self(parts))
     }
 }
 
-impl std::fmt::Display for Pnchs {
+/// Statistics about the pnchs database, see `Pnchs::stat`.
+pub struct DbStats {
+    /// Number of complete pnchs in the database.
+    pub count: usize,
+    /// Number of bytes at the end of the database that do not form a complete pnch.
+    pub trailing_bytes: usize,
+    /// Date of the first and last pnchs.
+    pub span: Option<(time::Date, time::Date)>,
+    /// The database is not sorted, the span is based on the first and last pnchs only.
+    pub approximate_span: bool,
+    /// The last pnch is open.
+    pub open: bool,
+}
+
+impl std::fmt::Display for DbStats {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if self.0.len() == 0 {
-            // TODO: The error should not be printed here
-            // We should also add a HINT to clarify that the filter was
-            // probably too strict.
-            return writeln!(f, "{}\n    No pnchs were found.", "error:".red());
-        }
-        let total_duration = self.duration();
-        writeln!(f, "You were punched in for {total_duration}")?;
-        self.0
-            .iter()
-            .try_fold(time::Date::min(), |mut date, pnch| {
-                if date != pnch.date {
-                    date = pnch.date.clone();
-                    writeln!(f, "\n{date}")?;
-                }
-                writeln!(f, "{pnch}")?;
-                Ok(date)
-            })?;
+        writeln!(f, "entries: {}", self.count)?;
+        match &self.span {
+            Some((first, last)) if self.approximate_span => {
+                writeln!(f, "span: {first} to {last} (approximate, the entries are not sorted)")?
+            }
+            Some((first, last)) => writeln!(f, "span: {first} to {last}")?,
+            None => writeln!(f, "span: none")?,
+        }
+        writeln!(f, "open entry: {}", if self.open { "yes" } else { "no" })?;
+        if self.trailing_bytes > 0 {
+            writeln!(f, "{} {} trailing bytes do not form a complete entry",
+                "warning:".yellow(), self.trailing_bytes)?;
+        }
         Ok(())
     }
 }
 
-#[derive(Debug, Clone)]
-pub enum Format {
-    Table,
-    List,
-    Csv
+/// A break in the open pnch.
+///
+/// The open pnch is closed when the break starts. The break is saved as a pnch starting when the
+/// break started, with the tag and description of the closed pnch, so a new pnch with the same
+/// tag and description is opened when the break ends.
+pub struct Break(pub Pnch);
+
+impl Break {
+    pub const BREAK_FILE_NAME: &'static str = "break.db";
+
+    /// The current break, if any. The break is saved without a header, a break saved by an older
+    /// version of pnch is recognized by the size of its record.
+    pub fn load(storage: &storage::Storage, tags: &tag::Tags) -> Result<Option<Self>, GlobalError> {
+        let buffer = storage.load(Self::BREAK_FILE_NAME)?;
+        let buffer = match buffer.len() {
+            0 => return Ok(None),
+            Pnch::V1_SIZE => Pnchs::migrate(storage, Self::BREAK_FILE_NAME, 1, &buffer)?,
+            Pnch::V2_SIZE => Pnchs::migrate(storage, Self::BREAK_FILE_NAME, 2, &buffer)?,
+            _ => buffer,
+        };
+        Ok(Some(Self(Pnch::try_from(0, &buffer, tags)?)))
+    }
+
+    pub fn save(&self, storage: &storage::Storage) -> Result<(), GlobalError> {
+        storage.save(Self::BREAK_FILE_NAME, &Vec::from(&self.0), "break")
+    }
+
+    /// End the break.
+    pub fn clear(storage: &storage::Storage) -> Result<(), GlobalError> {
+        storage.save(Self::BREAK_FILE_NAME, &[], "break")
+    }
 }
 
-impl str::FromStr for Format {
-    type Err = GlobalError;
-    fn from_str(value: &str) -> Result<Self, Self::Err> {
-        match &value.to_lowercase()[..] {
-            "table" => Ok(Self::Table),
-            "list" => Ok(Self::List),
-            "csv" => Ok(Self::Csv),
-            _ => Err(GlobalError::parse("`pretty` or `csv`"))
+/// Summary statistics of pnchs, see `Pnchs::stats`.
+pub struct Stats {
+    pub duration: time::Duration,
+    pub count: usize,
+    /// Number of open pnchs, counted in `count` but not in the durations.
+    pub open: usize,
+    /// Number of distinct days with at least one pnch.
+    pub days: usize,
+    /// Average duration of the days with at least one pnch.
+    pub average: time::Duration,
+    /// Id, date and duration of the longest pnch.
+    pub longest: Option<(u32, time::Date, time::Duration)>,
+    /// Number of consecutive days with at least one pnch, up to today. The streak is not broken
+    /// as long as today has no pnch yet.
+    pub streak: usize,
+    /// How the durations are printed.
+    pub duration_format: time::DurationFormat,
+}
+
+impl std::fmt::Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let format = self.duration_format;
+        writeln!(f, "total: {}", self.duration.format_long(format))?;
+        match self.open {
+            0 => writeln!(f, "entries: {}", self.count)?,
+            open => writeln!(f, "entries: {} ({open} open, not counted in the durations)", self.count)?,
+        }
+        writeln!(f, "days: {}", self.days)?;
+        writeln!(f, "average per day: {}", self.average.format_long(format))?;
+        match &self.longest {
+            Some((id, date, duration)) => writeln!(f, "longest entry: #{id} on {date} ({})", duration.format_long(format))?,
+            None => writeln!(f, "longest entry: none")?,
         }
+        writeln!(f, "current streak: {} days", self.streak)
     }
 }
 
-pub struct PnchsTable(Pnchs);
+/// A group of pnch.
+pub struct Pnchs(pub Vec<Pnch>);
 
-impl PnchsTable {
-    const COLS: usize = 6;
-    const COLS_WIDTH: [usize; Self::COLS] = [12, 7, 16, 7, 7, 44];
+impl Pnchs {
+    pub const PNCHS_FILE_NAME: &'static str = "pnchs.db";
+    /// The format of the pnchs database and of the archives. The version 2 added the seconds and
+    /// the UTC offsets of the times to the records, the version 3 added the flags.
+    pub const FORMAT: format::Format = format::Format::new(*b"PNCH", 3, Pnch::V1_SIZE, "pnchs", Self::migrate);
+    /// Before the version 2, the seconds of the times of a pnchs file were saved in the file with
+    /// the same name and this suffix, like `pnchs.db.seconds`. Each record has the date and the in
+    /// time of its pnch, its seconds are only used when they match the pnch at the same position,
+    /// so a file saved without its seconds, by an older version, doesn't get the seconds of other
+    /// pnchs.
+    const SECONDS_FILE_SUFFIX: &'static str = ".seconds";
+    /// Before the version 2, the UTC offsets of the times were saved like their seconds, in the
+    /// file with this suffix, like `pnchs.db.offsets`.
+    const OFFSETS_FILE_SUFFIX: &'static str = ".offsets";
+    /// size of the date and the in time starting the records of the seconds and offsets files
+    const SIDE_KEY_SIZE: usize = Pnch::DATE_SIZE + Pnch::IN_SIZE;
 
-    fn pnch_to_cells(&self, pnch: &Pnch, date: &mut time::Date) -> (bool, Vec<String>) {
-        let mut cells = Vec::new();
-        let did_date_update = if pnch.date != *date {
-            *date = pnch.date.clone();
-            cells.push(format!("{date}"));
-            true
-        } else {
-            cells.push(String::new());
-            false
+    /// Get statistics about the database without loading every pnch.
+    ///
+    /// Pnchs have a fixed size, so only the first and last pnchs are read.
+    pub fn stat(storage: &storage::Storage) -> Result<DbStats, GlobalError> {
+        let error = |_| GlobalError::fs("load", "pnchs");
+        let path = storage.build_path(Self::PNCHS_FILE_NAME)?;
+        let mut file = match std::fs::File::open(path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(DbStats { count: 0, trailing_bytes: 0, span: None, approximate_span: false, open: false });
+            }
+            Err(err) => return Err(error(err)),
         };
-        cells.push(pnch.id.to_string());
-        cells.push(pnch.tag.as_ref().map(|t| t.tag.to_string()).unwrap_or(String::from("---")));
-        cells.push(pnch._in.to_string());
-        cells.push(pnch.out.as_ref().map(|o| o.to_string()).unwrap_or(String::new()));
-        cells.push(pnch.description.clone().unwrap_or(String::new()));
-        (did_date_update, cells)
+        let header_size = Self::FORMAT.header_size(&mut file).map_err(error)?;
+        let len = (file.metadata().map_err(error)?.len() as usize).saturating_sub(header_size);
+        let count = len / Pnch::SIZE;
+        let mut stats = DbStats {
+            count,
+            trailing_bytes: len % Pnch::SIZE,
+            span: None,
+            approximate_span: false,
+            open: false,
+        };
+        if count == 0 {
+            return Ok(stats);
+        }
+        let mut chunk = [0; Pnch::SIZE];
+        file.seek(std::io::SeekFrom::Start(header_size as u64)).map_err(error)?;
+        file.read_exact(&mut chunk).map_err(error)?;
+        let first = time::Date::try_from(&chunk[..Pnch::DATE_SIZE])?;
+        file.seek(std::io::SeekFrom::Start((header_size + (count - 1) * Pnch::SIZE) as u64)).map_err(error)?;
+        file.read_exact(&mut chunk).map_err(error)?;
+        let last = time::Date::try_from(&chunk[..Pnch::DATE_SIZE])?;
+        let out_offset = Pnch::DATE_SIZE + Pnch::IN_SIZE;
+        stats.open = chunk[out_offset..out_offset + Pnch::OUT_SIZE] == time::Time::NONE_DATE;
+        stats.approximate_span = first > last;
+        stats.span = Some(match stats.approximate_span {
+            true => (last, first),
+            false => (first, last),
+        });
+        Ok(stats)
     }
 
-    fn cells_to_string(&self, cells: Vec<String>) -> String {
-        let mut cells = cells
-            .iter()
+    pub fn load(storage: &storage::Storage, tags: &tag::Tags) -> Result<Self, GlobalError> {
+        Self::load_file(storage, Self::PNCHS_FILE_NAME, tags)
+    }
+
+    /// Load the pnchs of a file with the same format as the pnchs database, like an archive.
+    pub fn load_file(storage: &storage::Storage, file: &str, tags: &tag::Tags) -> Result<Self, GlobalError> {
+        let buffer = Self::FORMAT.load(storage, file)?;
+        let mut pnchs = buffer
+            .chunks_exact(Pnch::SIZE)
             .enumerate()
-            .map(|(idx, cell)| {
-                format!("│ {:<width$} ", cell, width = Self::COLS_WIDTH[idx] - 2)
+            .map(|(id, chunk)| {
+                Pnch::try_from(id as u32, chunk, tags)
+                    .map_err(|err| err.in_record(file, id, Pnch::SIZE))
             })
-            .collect::<String>();
-        cells.push_str("│");
-        cells
+            .collect::<Result<Vec<Pnch>, GlobalError>>()?;
+        Self::retire_side_files(storage, file)?;
+        pnchs.sort();
+        Ok(Self(pnchs))
     }
 
-    fn separator(&self, left: &str, mid: &str, right: &str) -> String {
-        let mut separator = String::from(left);
-        separator.push_str(&Self::COLS_WIDTH.iter().enumerate().map(|(idx, width)| {
-            let mut end = mid;
-            if idx == Self::COLS_WIDTH.len() - 1 {
-                end = right
+    /// Migrate the records of an older version of the format to the current version.
+    fn migrate(storage: &storage::Storage, file: &str, version: u16, records: &[u8]) -> Result<Vec<u8>, GlobalError> {
+        let records = match version {
+            0 | 1 => Self::migrate_side_files(storage, file, records)?,
+            _ => records.to_vec(),
+        };
+        // The pnchs saved before the version 3 have no flags.
+        let chunks = records.chunks_exact(Pnch::V2_SIZE);
+        let trailing = chunks.remainder();
+        let mut migrated = Vec::with_capacity(records.len() / Pnch::V2_SIZE * Pnch::SIZE + trailing.len());
+        for record in chunks {
+            migrated.extend_from_slice(record);
+            migrated.push(0);
+        }
+        migrated.extend_from_slice(trailing);
+        Ok(migrated)
+    }
+
+    /// Migrate the records of the versions 0 and 1 of the format, whose seconds and offsets were
+    /// in the files next to them, to the version 2.
+    fn migrate_side_files(storage: &storage::Storage, file: &str, records: &[u8]) -> Result<Vec<u8>, GlobalError> {
+        let seconds = Self::load_side_file(storage, file, Self::SECONDS_FILE_SUFFIX)?.unwrap_or_default();
+        let offsets = Self::load_side_file(storage, file, Self::OFFSETS_FILE_SUFFIX)?.unwrap_or_default();
+        let no_offsets = [time::Offset::to_le_bytes(None), time::Offset::to_le_bytes(None)].concat();
+        let chunks = records.chunks_exact(Pnch::V1_SIZE);
+        let trailing = chunks.remainder();
+        let mut migrated = Vec::with_capacity(records.len() / Pnch::V1_SIZE * Pnch::V2_SIZE + trailing.len());
+        for (i, record) in chunks.enumerate() {
+            migrated.extend_from_slice(record);
+            let key = &record[..Self::SIDE_KEY_SIZE];
+            migrated.extend_from_slice(Self::side_record(&seconds, i, key, Pnch::SECONDS_SIZE).unwrap_or(&[0, 0]));
+            migrated.extend_from_slice(Self::side_record(&offsets, i, key, Pnch::OFFSETS_SIZE).unwrap_or(&no_offsets));
+        }
+        migrated.extend_from_slice(trailing);
+        Ok(migrated)
+    }
+
+    /// The content of the seconds or offsets file of `file`, if it has one.
+    fn load_side_file(storage: &storage::Storage, file: &str, suffix: &str) -> Result<Option<Vec<u8>>, GlobalError> {
+        match std::fs::read(storage.build_path(&format!("{file}{suffix}"))?) {
+            Ok(content) => Ok(Some(content)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(_) => Err(GlobalError::fs("load", file)),
+        }
+    }
+
+    /// Once `file` is migrated to the version 2, its seconds and offsets files are not used
+    /// anymore: they are kept as a copy, like the database, with the suffix `.v1.bak`.
+    fn retire_side_files(storage: &storage::Storage, file: &str) -> Result<(), GlobalError> {
+        for suffix in [Self::SECONDS_FILE_SUFFIX, Self::OFFSETS_FILE_SUFFIX] {
+            let path = storage.build_path(&format!("{file}{suffix}"))?;
+            if !std::path::Path::new(&path).exists() || Self::FORMAT.version(storage, file)? < 2 {
+                continue;
             }
-            format!("{}{end}", &"-".repeat(*width))
-        }).collect::<String>());
-        separator
+            let backup = storage.build_path(&format::Format::backup_file_name(&format!("{file}{suffix}"), 1))?;
+            std::fs::rename(path, backup).map_err(|_| GlobalError::fs("migrate", file))?;
+        }
+        Ok(())
     }
-}
 
-//┌────────────┬───────┬────────────────┬───────┬───────┬────────────────────────────────────┐
-//│ Date       │ Id    │ Tag            │ In    │ Out   │ Description                        │
-//├────────────┼───────┼────────────────┼───────┼───────┼────────────────────────────────────┤
-//│ 2023-05-10 │  9:23 │ RDG-123        │  9:23 │ 10:33 │ Ceci est ma description            │
-//│            │  9:23 │ RDG-123        │  9:23 │ 10:33 │ Ceci est ma description            │
-//│            │  9:23 │ RDG-123-123-2..│  9:23 │ 10:33 │ Ceci est ma description            │
-//├────────────┼───────┼────────────────┼───────┼───────┼────────────────────────────────────┤
-//│ 2023-05-11 │  9:23 │ RDG-123        │  9:23 │ 10:33 │ Ceci est ma description            │
-//│            │  9:23 │ RDG-123        │  9:23 │ 10:33 │ Ceci est ma description            │
-//│            │  9:23 │ RDG-123        │  9:23 │ 10:33 │ Ceci est ma description            │
-//└────────────┴───────┴────────────────┴───────┴───────┴────────────────────────────────────┘
-impl std::fmt::Display for PnchsTable {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if self.0.0.len() == 0 {
-            // TODO: The error should not be printed here
-            // We should also add a HINT to clarify that the filter was
-            // probably too strict.
-            return writeln!(f, "{}\n    No pnchs were found.", "error:".red());
+    /// The rest of the `i`-th record of a seconds or offsets file, when it starts with `key`.
+    fn side_record<'a>(side: &'a [u8], i: usize, key: &[u8], size: usize) -> Option<&'a [u8]> {
+        side.chunks_exact(Self::SIDE_KEY_SIZE + size)
+            .nth(i)
+            .and_then(|record| record.strip_prefix(key))
+    }
+
+    /// Save the pnchs to `file`, like an archive.
+    pub fn save_file(&self, storage: &storage::Storage, file: &str) -> Result<(), GlobalError> {
+        storage.save(file, &Self::FORMAT.with_header(&self.to_bytes()), "pnchs")
+    }
+
+    /// The pnchs referencing a tag id that does not exist, as `(id, tag id)` pairs.
+    ///
+    /// These pnchs are loaded without a tag, so the tag ids are read from the database directly.
+    pub fn unknown_tags(storage: &storage::Storage, tags: &tag::Tags) -> Result<Vec<(u32, u32)>, GlobalError> {
+        let buffer = Self::FORMAT.load(storage, Self::PNCHS_FILE_NAME)?;
+        let offset = Pnch::DATE_SIZE + Pnch::IN_SIZE + Pnch::OUT_SIZE;
+        Ok(buffer
+            .chunks_exact(Pnch::SIZE)
+            .enumerate()
+            .filter_map(|(id, chunk)| {
+                let tag_id_bytes = chunk[offset..offset + Pnch::TAG_ID_SIZE]
+                    .try_into()
+                    .expect("the chunk is a complete pnch");
+                match u32::from_le_bytes(tag_id_bytes) {
+                    tag_id if tag_id == tag::Tag::none().id || tags.get(tag_id).is_some() => None,
+                    tag_id => Some((id as u32, tag_id)),
+                }
+            })
+            .collect())
+    }
+
+    /// Apply the safe fixes to the database directly and return the number of pnchs that were
+    /// changed: the trailing bytes that do not form a complete pnch are dropped and the times out
+    /// of range are clamped. Everything else is left untouched, including the order of the pnchs.
+    pub fn repair(storage: &storage::Storage) -> Result<usize, GlobalError> {
+        let mut buffer = Self::FORMAT.load(storage, Self::PNCHS_FILE_NAME)?;
+        buffer.truncate(buffer.len() - buffer.len() % Pnch::SIZE);
+        let out_offset = Pnch::DATE_SIZE + Pnch::IN_SIZE;
+        let mut count = 0;
+        for chunk in buffer.chunks_exact_mut(Pnch::SIZE) {
+            let mut changed = false;
+            for offset in [Pnch::DATE_SIZE, out_offset] {
+                let bytes = &mut chunk[offset..offset + time::Time::SIZE];
+                if offset == out_offset && *bytes == time::Time::NONE_DATE {
+                    continue;
+                }
+                let time = time::Time::try_from(&*bytes)?;
+                // The end of a pnch can be on the next day.
+                let (valid, clamped) = match offset == out_offset {
+                    true => (time.is_valid_out(), time.clamped_out()),
+                    false => (time.is_valid(), time.clamped()),
+                };
+                if !valid {
+                    bytes.copy_from_slice(&clamped.to_le_bytes());
+                    changed = true;
+                }
+            }
+            count += changed as usize;
         }
-        let separator = self.separator("├", "┼", "┤");
-        let mut rows = vec![
-            self.separator("┌", "┬", "┐"),
-            self.cells_to_string(vec![
-                String::from("Date"), String::from("Id"), String::from("Tag"),
-                String::from("In"), String::from("Out"), String::from("Description"),
-            ])
-        ];
+        index::DayIndex::invalidate(storage)?;
+        storage.save(Self::PNCHS_FILE_NAME, &Self::FORMAT.with_header(&buffer), "pnchs")?;
+        Ok(count)
+    }
 
-        let mut date = time::Date::min();
-        for pnch in self.0.0.iter() {
-            let (did_date_update, cells) = self.pnch_to_cells(pnch, &mut date);
-            if did_date_update {
-                rows.push(separator.clone());
+    pub fn _in(&mut self, pnch: Pnch) -> Result<(), GlobalError> {
+        if self.0.iter().any(|other| other.out.is_none()) {
+            return Err(GlobalError::pnch_already_open());
+        }
+        self.0.push(pnch);
+        Ok(())
+    }
+
+    /// Insert a closed pnch where it belongs, so the pnchs stay sorted, and return the ids of the
+    /// pnchs of the same day that it overlaps.
+    ///
+    /// Unlike `Pnchs::_in`, the open pnch is never touched.
+    pub fn add(&mut self, pnch: Pnch) -> Vec<u32> {
+        let overlaps = self.0
+            .iter()
+            .filter(|other| other.date == pnch.date)
+            .filter(|other| match (other.out, pnch.out) {
+                (Some(other_out), Some(out)) => other._in < out && pnch._in < other_out,
+                (None, Some(out)) => other._in < out,
+                _ => false,
+            })
+            .map(|other| other.id)
+            .collect();
+        let idx = self.0.partition_point(|other| *other <= pnch);
+        self.0.insert(idx, pnch);
+        overlaps
+    }
+
+    /// Close the open pnch at `time`, punched with the UTC offset `offset`, and split it in
+    /// consecutive pnchs, one for each part of the split. Returns the number of pnchs the open pnch was split into.
+    ///
+    /// Parts without a description keep the description of the open pnch.
+    pub fn out_split(&mut self, time: time::Time, offset: Option<time::Offset>, split: &Split, tags: &mut tag::Tags) -> Result<usize, GlobalError> {
+        let idx = self.0
+            .iter()
+            .rposition(|pnch| pnch.out.is_none())
+            .ok_or_else(GlobalError::pnch_not_open)?;
+        let mut current = self.0.remove(idx);
+        if current.until(time, offset) < time::Duration::zero() {
+            return Err(GlobalError::pnch_out_before_in(current._in, time));
+        }
+        let durations = split.durations(current.until(time, offset))?;
+        current.out = Some(time);
+        current.out_offset = offset;
+
+        let mut pnchs = Vec::new();
+        let mut parts = split.0.iter().zip(durations).filter(|(_, duration)| duration.minutes > 0).peekable();
+        while let Some((part, duration)) = parts.next() {
+            // There is no next part at the end of the pnch, so the last part is not split.
+            let next = match parts.peek() {
+                Some(_) => {
+                    let at = current._in
+                        .checked_add(duration)
+                        .expect("the durations are within the pnch");
+                    Some(current.split(self.0.len() as u32 + pnchs.len() as u32 + 1, at)?)
+                }
+                None => None,
+            };
+            current.tag = Some(tags.get_or_insert(part.tag.clone())?);
+            if let Some(description) = &part.description {
+                current.description = Some(description.clone());
+            }
+            if current.description.is_none() {
+                return Err(GlobalError::desc_not_specified());
+            }
+            pnchs.push(current);
+            match next {
+                Some(next) => current = next,
+                None => break,
             }
-            rows.push(self.cells_to_string(cells));
         }
-        rows.push(self.separator("└", "┴", "┘"));
-        let table = rows.join("\n");
-        let total_duration = self.0.duration();
-        writeln!(f, "You were punched in for {total_duration}")?;
-        writeln!(f, "{table}")
+        let count = pnchs.len();
+        self.0.splice(idx..idx, pnchs);
+        Ok(count)
+    }
+
+    /// Merge the pnch `second` into the pnch `first` and return the merged pnch with whether its
+    /// description was truncated.
+    ///
+    /// The merged pnch starts when the first one starts and ends when the second one ends. The
+    /// pnchs must be on the same day and must not overlap. Unless `force` is set, they must also
+    /// have the same tag and no other pnch can be between them. The descriptions are concatenated
+    /// when they differ.
+    pub fn merge(&mut self, first: u32, second: u32, force: bool) -> Result<(&Pnch, bool), GlobalError> {
+        if first == second {
+            return Err(GlobalError::pnch_merge_same(first));
+        }
+        let position = |id: u32| self.0
+            .iter()
+            .position(|pnch| pnch.id == id)
+            .ok_or_else(|| GlobalError::pnch_id_not_exists(id));
+        let (first_idx, second_idx) = match (position(first)?, position(second)?) {
+            (a, b) if a < b => (a, b),
+            (a, b) => (b, a),
+        };
+        let (first, second) = (&self.0[first_idx], &self.0[second_idx]);
+        let out = first.out.ok_or_else(|| GlobalError::pnch_merge_open(first.id))?;
+        if first.date != second.date {
+            return Err(GlobalError::pnch_merge_dates(first.id, second.id));
+        }
+        if second._in < out {
+            return Err(GlobalError::pnch_merge_overlap(first.id, second.id));
+        }
+        if !force && first.tag != second.tag {
+            return Err(GlobalError::pnch_merge_tags(first.id, second.id));
+        }
+        if !force && second_idx != first_idx + 1 {
+            return Err(GlobalError::pnch_merge_not_adjacent(first.id, second.id));
+        }
+
+        let second = self.0.remove(second_idx);
+        let merged = &mut self.0[first_idx];
+        merged.out = second.out;
+        let truncated = match second.description {
+            Some(description) if merged.description.as_ref() != Some(&description) => {
+                merged.append_description(&description)
+            }
+            _ => false,
+        };
+        Ok((merged, truncated))
+    }
+
+    /// Split the pnch `id` in two at the given time, see `Pnch::split`. The new pnch is inserted
+    /// right after the split pnch, so the pnchs stay sorted, and is returned.
+    pub fn split(&mut self, id: u32, at: time::Time) -> Result<&mut Pnch, GlobalError> {
+        let new_id = self.0.len() as u32;
+        let idx = self.0
+            .iter()
+            .position(|pnch| pnch.id == id)
+            .ok_or_else(|| GlobalError::pnch_id_not_exists(id))?;
+        let pnch = self.0[idx].split(new_id, at)?;
+        self.0.insert(idx + 1, pnch);
+        Ok(&mut self.0[idx + 1])
+    }
+
+    /// Open a closed pnch of today again, by removing its out time. It keeps its tag and
+    /// description, so it only needs a time to be closed again.
+    pub fn reopen(&mut self, id: u32, today: &time::Date) -> Result<&Pnch, GlobalError> {
+        if self.0.iter().any(|pnch| pnch.out.is_none()) {
+            return Err(GlobalError::pnch_already_open());
+        }
+        let pnch = self.get(id).ok_or_else(|| GlobalError::pnch_id_not_exists(id))?;
+        if pnch.date != *today {
+            return Err(GlobalError::pnch_reopen_not_today(id, &pnch.date));
+        }
+        pnch.out = None;
+        pnch.out_offset = None;
+        Ok(pnch)
+    }
+
+    pub fn get(&mut self, id: u32) -> Option<&mut Pnch> {
+        self.0.iter_mut().find(|pnch| pnch.id == id)
+    }
+
+    /// The open pnch. Entries added with `Pnchs::add` can be after it, so it is not always the
+    /// last one.
+    pub fn get_open(&mut self) -> Option<&mut Pnch> {
+        self.0.iter_mut().rev().find(|pnch| pnch.out.is_none())
+    }
+
+    pub fn get_last(&mut self) -> Option<&mut Pnch> {
+        self.0.last_mut()
+    }
+
+    /// The most recent closed pnch, skipping the open pnch.
+    pub fn get_last_closed(&mut self) -> Option<&mut Pnch> {
+        self.0.iter_mut().rev().find(|pnch| pnch.out.is_some())
+    }
+
+    /// Number of pnchs in the database, without loading them.
+    pub fn count(storage: &storage::Storage) -> Result<usize, GlobalError> {
+        Ok(Self::stat(storage)?.count)
+    }
+
+    pub fn save(&self, storage: &storage::Storage) -> Result<(), GlobalError> {
+        // The index is removed first, if the pnchs are saved but not the index, the index is
+        // rebuilt instead of being out of date.
+        index::DayIndex::invalidate(storage)?;
+        self.save_file(storage, Self::PNCHS_FILE_NAME)?;
+        note::Notes::remap(storage, &self.saved_ids())?;
+        index::DayIndex::build(self).save(storage, self.0.len())
+    }
+
+    /// The id of each pnch, mapped to the id it has once the pnchs are saved. The pnchs are saved
+    /// sorted and the id of a pnch is its position in the database.
+    pub fn saved_ids(&self) -> Vec<(u32, u32)> {
+        let mut pnchs = self.0.iter().collect::<Vec<_>>();
+        pnchs.sort();
+        pnchs
+            .iter()
+            .enumerate()
+            .map(|(id, pnch)| (pnch.id, id as u32))
+            .collect()
+    }
+
+    /// The pnchs encoded in the format of the pnchs database.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        // The ids are the position of the pnchs in the file, saving them sorted keeps the ids in
+        // the same order as the pnchs are listed.
+        let mut pnchs = self.0.iter().collect::<Vec<_>>();
+        pnchs.sort();
+        pnchs
+            .into_iter()
+            .flat_map(Vec::from)
+            .collect::<Vec<u8>>()
+    }
+
+    /// The columns of the csv format, they are only ever added at the end.
+    const CSV_COLUMNS: [&'static str; 6] = ["tag", "description", "date", "in", "out", "duration_minutes"];
+
+    /// Format the pnchs as csv, with a first line naming the columns when `header` is set. The
+    /// out time and the duration are empty for an open pnch.
+    ///
+    /// The duration is a number of minutes with the `clock` duration format, otherwise it is in
+    /// the duration format and its column is named `duration`.
+    pub fn into_csv(self, header: bool, delimiter: Delimiter, time_format: time::TimeFormat, duration_format: time::DurationFormat) -> Result<String, GlobalError> {
+        let d = delimiter.as_char();
+        let mut csv = String::new();
+        if header {
+            let mut columns = Self::CSV_COLUMNS;
+            if duration_format != time::DurationFormat::Clock {
+                columns[Self::CSV_COLUMNS.len() - 1] = "duration";
+            }
+            csv.push_str(&columns.join(&d.to_string()));
+            csv.push('\n');
+        }
+        self.0
+            .into_iter()
+            .try_for_each(|pnch| {
+                let line = &mut csv;
+                let duration = pnch.duration();
+                match pnch.tag {
+                    Some(tag) => write!(line, "{}{d}", csv_escape(&tag.tag, d))?,
+                    None => write!(line, "{d}")?,
+                }
+                write!(line, "{}{d}", csv_escape(&pnch.description.unwrap_or_default(), d))?;
+                write!(line, "{}{d}", pnch.date)?;
+                write!(line, "{}{d}", pnch._in.format(time_format))?;
+                match pnch.out {
+                    Some(out) => write!(line, "{}{d}", out.format(time_format))?,
+                    None => write!(line, "{d}")?,
+                }
+                match duration {
+                    Some(duration) if duration_format == time::DurationFormat::Clock => writeln!(line, "{}", duration.minutes),
+                    Some(duration) => writeln!(line, "{}", csv_escape(&duration.format(duration_format), d)),
+                    None => writeln!(line),
+                }
+            })
+            .map_err(|_| GlobalError::formatting("csv"))?;
+        Ok(csv)
+    }
+
+    /// Read the closed pnchs of a csv file in the format of `Pnchs::into_csv`, created with the
+    /// tags they name. The first line is skipped when it names the columns, and the duration
+    /// column is ignored, it is computed from the times. `file` names the csv in the errors.
+    ///
+    /// The rows dated after today are most likely typos, they are skipped unless `force` is set.
+    /// Returns the pnchs, with the ids following the ids of `self`, and the number of skipped rows.
+    pub fn read_csv(&self, csv: &str, file: &str, delimiter: Delimiter, tags: &mut tag::Tags, clock: &time::Clock,
+        force: bool) -> Result<(Vec<Pnch>, usize), GlobalError> {
+        let today = clock.today();
+        let mut pnchs = Vec::new();
+        let mut skipped = 0;
+        for (idx, row) in csv_records(csv, delimiter.as_char()).into_iter().enumerate() {
+            if idx == 0 && row.len() > 1 && row[..2] == Self::CSV_COLUMNS[..2] {
+                continue;
+            }
+            let id = (self.0.len() + pnchs.len()) as u32;
+            let pnch = Self::read_csv_row(id, &row, tags, clock).map_err(|err| err.in_row(file, idx + 1))?;
+            match pnch.date.check_not_future(&today, force) {
+                Ok(()) => pnchs.push(pnch),
+                Err(_) => skipped += 1,
+            }
+        }
+        Ok((pnchs, skipped))
+    }
+
+    fn read_csv_row(id: u32, row: &[String], tags: &mut tag::Tags, clock: &time::Clock) -> Result<Pnch, GlobalError> {
+        let [tag, description, date, _in, out, _duration] = row else {
+            return Err(GlobalError::import_columns(row.len(), &Self::CSV_COLUMNS));
+        };
+        tag::Tag::check_name(tag)?;
+        let _in = _in.trim().parse::<time::Time>()?;
+        let out = match out.trim() {
+            "" => return Err(GlobalError::import_open()),
+            out => out.parse::<time::Time>()?,
+        };
+        if out <= _in {
+            return Err(GlobalError::pnch_out_before_in(_in, out));
+        }
+        let tag = (!tag.is_empty()).then(|| tags.get_or_insert(tag.clone())).transpose()?;
+        let mut pnch = Pnch::new(id, clock, _in, tag, None);
+        pnch.date = date.trim().parse()?;
+        if !description.is_empty() {
+            pnch.set_description(description.clone());
+        }
+        pnch.out = Some(out);
+        pnch.out_offset = pnch.in_offset;
+        Ok(pnch)
+    }
+
+    /// Sort the pnchs, from the oldest, shortest or first tag, or the other way around when
+    /// `desc` is set. The pnchs with the same duration or tag stay sorted by date. The open pnchs
+    /// have no duration and always come last when sorting by duration, like the pnchs without a
+    /// tag when sorting by tag.
+    pub fn sort(&mut self, sort: Sort, desc: bool) {
+        self.0.sort_by(|a, b| {
+            let order = match sort {
+                Sort::Date => std::cmp::Ordering::Equal,
+                Sort::Duration => match (a.duration(), b.duration()) {
+                    (Some(a), Some(b)) if desc => b.cmp(&a),
+                    (a, b) => a.is_none().cmp(&b.is_none()).then(a.cmp(&b)),
+                },
+                Sort::Tag => {
+                    let (a, b) = (a.tag.as_ref().map(|tag| &tag.tag), b.tag.as_ref().map(|tag| &tag.tag));
+                    match (a, b) {
+                        (Some(a), Some(b)) if desc => b.cmp(a),
+                        (a, b) => a.is_none().cmp(&b.is_none()).then(a.cmp(&b)),
+                    }
+                }
+            };
+            order.then_with(|| match desc && matches!(sort, Sort::Date) {
+                // The pnchs starting at the same time are in the order they were added.
+                true => b.cmp(a).then(b.id.cmp(&a.id)),
+                false => a.cmp(b),
+            })
+        });
+    }
+
+    /// The pnchs of each tag, sorted by tag name, with the pnchs without a tag last. The pnchs
+    /// keep their order inside each group.
+    pub fn by_tag(&self) -> Vec<(Option<String>, Pnchs)> {
+        self.group_by(|pnch| (pnch.tag.is_none(), pnch.tag.as_ref().map(|tag| tag.tag.clone())))
+            .into_iter()
+            .map(|group| (group.key.1, Pnchs(group.pnchs.into_iter().cloned().collect())))
+            .collect()
+    }
+
+    /// The pnchs with their open pnchs punched out now, in the same order, to count them in the
+    /// totals. See `Pnch::punched_out_now`.
+    pub fn punched_out_now(&self, running: &Running) -> Self {
+        Self(self.0.iter().map(|pnch| pnch.punched_out_now(running)).collect())
+    }
+
+    /// The pnchs with their durations rounded, in the same order. Each duration is rounded before
+    /// the totals are computed, so the totals are the sums of the rounded durations.
+    pub fn rounded(self, rounding: time::Rounding) -> Self {
+        Self(self.0.into_iter().map(|pnch| Pnch { rounding, ..pnch }).collect())
+    }
+
+    /// The ids of the pnchs ending before they start, see `Pnch::is_inconsistent`.
+    pub fn inconsistent_ids(&self) -> Vec<u32> {
+        self.0
+            .iter()
+            .filter(|pnch| pnch.is_inconsistent())
+            .map(|pnch| pnch.id)
+            .collect()
+    }
+
+    pub fn has_open(&self) -> bool {
+        self.0.iter().any(|pnch| pnch.out.is_none())
+    }
+
+    /// The total duration of each tag, from the longest to the shortest. The pnchs without a tag
+    /// have no name.
+    pub fn duration_by_tag(&self) -> Vec<(Option<String>, time::Duration)> {
+        let mut durations = self
+            .group_by(|pnch| pnch.tag.as_ref().map(|tag| tag.tag.clone()))
+            .into_iter()
+            .map(|group| {
+                let duration = group.duration();
+                (group.key, duration)
+            })
+            .collect::<Vec<_>>();
+        durations.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        durations
+    }
+
+    /// One line per pnch, with the placeholders of the template replaced by its fields.
+    pub fn into_template(self, template: &template::Template, duration_format: time::DurationFormat) -> String {
+        self.0
+            .iter()
+            .map(|pnch| template.render(pnch, duration_format) + "\n")
+            .collect()
+    }
+
+    /// A standalone HTML document with a table of the pnchs, grouped by day with the total of
+    /// each day and the total of every pnch at the end.
+    pub fn into_html(self, duration_format: time::DurationFormat) -> String {
+        let mut html = String::from(concat!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Timesheet</title>\n",
+            "<style>table { border-collapse: collapse; } th, td { border: 1px solid #ccc; padding: 4px 8px; }",
+            " .total td { font-weight: bold; }</style>\n</head>\n<body>\n<table>\n<thead>\n",
+            "<tr><th>Date</th><th>Id</th><th>Tag</th><th>In</th><th>Out</th><th>Description</th></tr>\n",
+            "</thead>\n",
+        ));
+        let row = |cells: [String; 6]| {
+            let cells = cells.map(|cell| format!("<td>{}</td>", html_escape(&cell))).concat();
+            format!("<tr>{cells}</tr>\n")
+        };
+        for day in self.group_by(|pnch| pnch.date.clone()) {
+            html.push_str("<tbody>\n");
+            for pnch in day.pnchs.iter() {
+                html.push_str(&row([
+                    pnch.date.to_string(),
+                    pnch.id.to_string(),
+                    pnch.tag.as_ref().map(|tag| tag.tag.clone()).unwrap_or_default(),
+                    pnch._in.to_string(),
+                    pnch.out.map(|out| out.to_string()).unwrap_or_default(),
+                    pnch.description.clone().unwrap_or_default(),
+                ]));
+            }
+            html.push_str(&format!(
+                "<tr class=\"total\"><td colspan=\"5\">Total of {}</td><td>{}</td></tr>\n</tbody>\n",
+                day.key, day.duration().format_long(duration_format),
+            ));
+        }
+        html.push_str(&format!(
+            "<tfoot>\n<tr class=\"total\"><td colspan=\"5\">Total</td><td>{}</td></tr>\n</tfoot>\n",
+            self.duration().format_long(duration_format),
+        ));
+        html.push_str("</table>\n</body>\n</html>\n");
+        html
+    }
+
+    /// A JSON array with an object for each pnch, with its notes. The out time, the duration and
+    /// the offsets are `null` when the pnch doesn't have them, the duration is in minutes.
+    pub fn into_json(self, notes: Option<&note::Notes>) -> String {
+        let string = |value: &str| format!("\"{}\"", json_escape(value));
+        let or_null = |value: Option<String>| value.unwrap_or_else(|| String::from("null"));
+        let objects = self.0
+            .iter()
+            .map(|pnch| {
+                let notes = notes
+                    .into_iter()
+                    .flat_map(|notes| notes.of(pnch.id))
+                    .map(|note| format!("{{\"date\": \"{}\", \"time\": \"{}\", \"text\": {}}}", note.date, note.time, string(&note.text)))
+                    .collect::<Vec<_>>();
+                [
+                    format!("\"id\": {}", pnch.id),
+                    format!("\"date\": \"{}\"", pnch.date),
+                    format!("\"in\": \"{}\"", pnch._in),
+                    format!("\"out\": {}", or_null(pnch.out.map(|out| format!("\"{out}\"")))),
+                    format!("\"tag\": {}", or_null(pnch.tag.as_ref().map(|tag| string(&tag.tag)))),
+                    format!("\"description\": {}", or_null(pnch.description.as_deref().map(string))),
+                    format!("\"duration_minutes\": {}", or_null(pnch.duration().map(|duration| duration.minutes.to_string()))),
+                    format!("\"in_offset\": {}", or_null(pnch.in_offset.map(|offset| format!("\"{offset}\"")))),
+                    format!("\"out_offset\": {}", or_null(pnch.out_offset.map(|offset| format!("\"{offset}\"")))),
+                    format!("\"notes\": [{}]", notes.join(", ")),
+                ].join(", ")
+            })
+            .map(|fields| format!("  {{{fields}}}"))
+            .collect::<Vec<_>>();
+        match objects.is_empty() {
+            true => String::from("[]\n"),
+            false => format!("[\n{}\n]\n", objects.join(",\n")),
+        }
+    }
+
+    /// An iCalendar document with an event for each closed pnch, as described by RFC 5545. The
+    /// tag is the summary of the event. The times of a pnch punched with known UTC offsets are in
+    /// UTC, the others are floating, they are in the timezone of the calendar they are imported in.
+    ///
+    /// Open pnchs don't have an end yet and are skipped, their number is returned with the document.
+    /// The events are stamped with the current time of `clock`. The uid of an event names the pnch,
+    /// its tag and `profile`, so identical pnchs are still different events.
+    pub fn into_ics(self, clock: &time::Clock, profile: Option<&str>) -> (String, usize) {
+        let mut lines = vec![
+            String::from("BEGIN:VCALENDAR"),
+            String::from("VERSION:2.0"),
+            String::from("PRODID:-//pnch//pnch//EN"),
+        ];
+        let stamp = clock.ical_utc_now();
+        // The profiles cannot have a `.` in their name, they cannot be mistaken for the default.
+        let domain = match profile {
+            Some(profile) => format!("{profile}.pnch"),
+            None => String::from("pnch"),
+        };
+        let mut skipped = 0;
+        for pnch in self.0.iter() {
+            let Some(out) = pnch.out else {
+                skipped += 1;
+                continue;
+            };
+            let date = pnch.date.to_ical();
+            let tag_id = pnch.tag.as_ref().map(|tag| tag.id.to_string()).unwrap_or_else(|| String::from("none"));
+            let end_date = match out.is_next_day() {
+                true => pnch.date.next().unwrap_or(pnch.date.clone()),
+                false => pnch.date.clone(),
+            };
+            let utc = match (pnch.in_offset, pnch.out_offset) {
+                (Some(in_offset), Some(out_offset)) => pnch.date.to_ical_utc(pnch._in, in_offset).zip(pnch.date.to_ical_utc(out, out_offset)),
+                _ => None,
+            };
+            let (start, end) = utc.unwrap_or_else(|| {
+                (format!("{date}T{}", pnch._in.to_ical()), format!("{}T{}", end_date.to_ical(), out.to_ical()))
+            });
+            lines.extend([
+                String::from("BEGIN:VEVENT"),
+                format!("UID:{date}T{}-{}-{}-{}@{domain}", pnch._in.to_ical(), out.to_ical(), pnch.id, tag_id),
+                format!("DTSTAMP:{stamp}"),
+                format!("DTSTART:{start}"),
+                format!("DTEND:{end}"),
+                format!("SUMMARY:{}", ics_escape(pnch.tag.as_ref().map(|tag| tag.tag.as_str()).unwrap_or("---"))),
+            ]);
+            if let Some(description) = &pnch.description {
+                lines.push(format!("DESCRIPTION:{}", ics_escape(description)));
+            }
+            lines.push(String::from("END:VEVENT"));
+        }
+        lines.push(String::from("END:VCALENDAR"));
+        let ics = lines
+            .iter()
+            .map(|line| ics_fold(line))
+            .collect::<String>();
+        (ics, skipped)
+    }
+
+    pub fn into_table(self, options: DisplayOptions) -> PnchsTable {
+        PnchsTable(self, options)
+    }
+
+    pub fn into_list(self, options: DisplayOptions) -> PnchsList {
+        PnchsList(self, options)
+    }
+
+    /// The total duration of the pnchs. Open pnchs do not have a duration yet and are ignored.
+    pub fn duration(&self) -> time::Duration {
+        total_duration(self.0.iter())
+    }
+
+    /// Partition the pnchs in groups of pnchs sharing the same key, sorted by key.
+    ///
+    /// Every total computed on a subset of pnchs (per day, per tag, ...) should go through this
+    /// function: each pnch belongs to exactly one group, so the durations of the groups always add
+    /// up to `Pnchs::duration` and no pnch is ever counted twice.
+    ///
+    /// The chains of the pnchs are rolled up in the groups: a chain whose pnchs all share the same
+    /// key replaces its pnchs in their group, the pnchs of a chain split across groups are chains
+    /// of their own. A pnch is in exactly one chain of its group, so a total taken on the chains
+    /// is the same as a total taken on the pnchs.
+    pub fn group_by<K: Ord>(&self, key: impl Fn(&Pnch) -> K) -> Vec<Group<'_, K>> {
+        let mut groups: Vec<Group<K>> = Vec::new();
+        for pnch in self.0.iter() {
+            let key = key(pnch);
+            match groups.binary_search_by(|group| group.key.cmp(&key)) {
+                Ok(idx) => groups[idx].pnchs.push(pnch),
+                Err(idx) => groups.insert(idx, Group { key, pnchs: vec![pnch], chains: Vec::new() }),
+            }
+        }
+        let group_of = |groups: &[Group<K>], pnch: &Pnch| {
+            let key = key(pnch);
+            groups.binary_search_by(|group| group.key.cmp(&key)).expect("every pnch is in a group")
+        };
+        for chain in self.chains() {
+            let positions = chain.pnchs.iter().map(|pnch| group_of(&groups, pnch)).collect::<Vec<_>>();
+            match positions.iter().all(|&idx| idx == positions[0]) {
+                true => groups[positions[0]].chains.push(chain),
+                false => for (pnch, idx) in chain.pnchs.into_iter().zip(positions) {
+                    groups[idx].chains.push(Chain { pnchs: vec![pnch] });
+                },
+            }
+        }
+        debug_assert_eq!(
+            groups.iter().map(|group| group.pnchs.len()).sum::<usize>(),
+            self.0.len()
+        );
+        debug_assert!(groups.iter().all(|group| {
+            group.chains.iter().map(|chain| chain.pnchs.len()).sum::<usize>() == group.pnchs.len()
+        }));
+        debug_assert_eq!(
+            groups.iter().map(|group| group.duration()).sum::<time::Duration>(),
+            self.duration()
+        );
+        groups
+    }
+
+    /// The chains of the pnchs, from the earliest one. A resumed pnch continues the last chain
+    /// of its day with the same tag and description that ended before it, see `Pnch::resumed`.
+    /// The other pnchs start a chain, so each pnch is in exactly one chain.
+    pub fn chains(&self) -> Vec<Chain<'_>> {
+        let mut pnchs = self.0.iter().collect::<Vec<_>>();
+        pnchs.sort();
+        let mut chains: Vec<Chain> = Vec::new();
+        for pnch in pnchs {
+            let continued = match pnch.resumed {
+                true => chains.iter().rposition(|chain| chain.is_continued_by(pnch)),
+                false => None,
+            };
+            match continued {
+                Some(idx) => chains[idx].pnchs.push(pnch),
+                None => chains.push(Chain { pnchs: vec![pnch] }),
+            }
+        }
+        debug_assert_eq!(chains.iter().map(|chain| chain.pnchs.len()).sum::<usize>(), self.0.len());
+        chains
+    }
+
+    /// The distinct dates of the pnchs, sorted.
+    pub fn dates(&self) -> Vec<time::Date> {
+        self.group_by(|pnch| pnch.date.clone())
+            .into_iter()
+            .map(|group| group.key)
+            .collect()
+    }
+
+    /// The closed pnch with the longest duration.
+    pub fn longest(&self) -> Option<&Pnch> {
+        self.0
+            .iter()
+            .filter(|pnch| pnch.duration().is_some())
+            .max_by_key(|pnch| pnch.duration())
+    }
+
+    /// The total duration of each day with at least one pnch, sorted by date.
+    pub fn day_totals(&self) -> Vec<(time::Date, time::Duration)> {
+        self.group_by(|pnch| pnch.date.clone())
+            .into_iter()
+            .map(|group| {
+                let duration = group.duration();
+                (group.key, duration)
+            })
+            .collect()
+    }
+
+    pub fn stats(&self, today: &time::Date, duration_format: time::DurationFormat) -> Stats {
+        let duration = self.duration();
+        let days = self.day_totals();
+        let average = match days.len() {
+            0 => time::Duration::zero(),
+            len => time::Duration::from_seconds(duration.as_seconds() / len as i64),
+        };
+        let dates = self.dates();
+        let mut day = match dates.binary_search(today) {
+            Ok(_) => Some(today.clone()),
+            Err(_) => today.previous(),
+        };
+        let mut streak = 0;
+        while let Some(date) = day.filter(|date| dates.binary_search(date).is_ok()) {
+            streak += 1;
+            day = date.previous();
+        }
+        Stats {
+            duration,
+            count: self.0.len(),
+            open: self.0.iter().filter(|pnch| pnch.out.is_none()).count(),
+            days: days.len(),
+            average,
+            longest: self.longest().map(|pnch| {
+                (pnch.id, pnch.date.clone(), pnch.duration().expect("the longest pnch is closed"))
+            }),
+            streak,
+            duration_format,
+        }
+    }
+}
+
+fn total_duration<'a>(pnchs: impl Iterator<Item = &'a Pnch>) -> time::Duration {
+    pnchs.filter_map(|pnch| pnch.duration()).sum()
+}
+
+/// Split a delimiter-separated text in records of fields, as described by RFC 4180, see
+/// `csv_escape`. A line break in a quoted field is part of the field, the empty lines are
+/// skipped.
+pub fn csv_records(text: &str, delimiter: char) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.is_empty() => quoted = true,
+            c if quoted => field.push(c),
+            c if c == delimiter => record.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                record.push(std::mem::take(&mut field));
+                let record = std::mem::take(&mut record);
+                if record.len() > 1 || !record[0].is_empty() {
+                    records.push(record);
+                }
+            }
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records
+}
+
+/// Quote a field of a delimiter-separated format, as described by RFC 4180. A field containing
+/// the delimiter, a quote or a line break is wrapped in quotes and its quotes are doubled, the
+/// other fields are left as is.
+pub fn csv_escape(field: &str, delimiter: char) -> std::borrow::Cow<'_, str> {
+    if !field.contains([delimiter, '"', '\n', '\r']) {
+        return std::borrow::Cow::Borrowed(field);
+    }
+    std::borrow::Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+}
+
+/// Escape the characters that cannot be in a JSON string, as described by RFC 8259.
+fn json_escape(text: &str) -> String {
+    text.chars().fold(String::with_capacity(text.len()), |mut escaped, c| {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+        escaped
+    })
+}
+
+/// Escape the characters with a meaning in HTML.
+fn html_escape(text: &str) -> String {
+    text.chars().fold(String::with_capacity(text.len()), |mut escaped, c| {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+        escaped
+    })
+}
+
+/// Escape the characters with a meaning in the text values of iCalendar.
+fn ics_escape(text: &str) -> String {
+    text.chars().fold(String::with_capacity(text.len()), |mut escaped, c| {
+        match c {
+            '\\' | ';' | ',' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+        escaped
+    })
+}
+
+/// Fold an iCalendar line in lines of at most 75 bytes, without splitting a character, and end it
+/// with CRLF. The next lines start with a space.
+fn ics_fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + 2);
+    let mut len = 0;
+    for c in line.chars() {
+        if len + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            len = 1;
+        }
+        folded.push(c);
+        len += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+/// The number of columns taken by a character in a terminal. Combining marks take no column and
+/// the wide characters of East Asian scripts and emojis take two.
+fn char_width(c: char) -> usize {
+    match c as u32 {
+        0x0300..=0x036F | 0x1AB0..=0x1AFF | 0x1DC0..=0x1DFF | 0x200B..=0x200F | 0x20D0..=0x20FF
+            | 0xFE00..=0xFE0F | 0xFE20..=0xFE2F => 0,
+        0x1100..=0x115F | 0x2E80..=0x303E | 0x3041..=0x33FF | 0x3400..=0x4DBF | 0x4E00..=0x9FFF
+            | 0xA000..=0xA4CF | 0xAC00..=0xD7A3 | 0xF900..=0xFAFF | 0xFE30..=0xFE4F
+            | 0xFF00..=0xFF60 | 0xFFE0..=0xFFE6 | 0x1F300..=0x1F64F | 0x1F900..=0x1F9FF
+            | 0x20000..=0x3FFFD => 2,
+        _ => 1,
+    }
+}
+
+/// The number of columns taken by a text in a terminal.
+fn text_width(text: &str) -> usize {
+    text.chars().map(char_width).sum()
+}
+
+/// Cut a text taking more than `width` columns and end it with `…`, so it takes at most `width`
+/// columns. Only the first line of the text is kept.
+fn truncate(text: &str, width: usize) -> String {
+    let line = text.lines().next().unwrap_or_default();
+    if line.len() == text.len() && text_width(text) <= width {
+        return text.to_owned();
+    }
+    let mut truncated = String::new();
+    let mut used = 0;
+    for c in line.chars() {
+        used += char_width(c);
+        if used + 1 > width {
+            break;
+        }
+        truncated.push(c);
+    }
+    truncated.push('…');
+    truncated
+}
+
+/// Pnchs sharing the same key, see `Pnchs::group_by`.
+pub struct Group<'a, K> {
+    pub key: K,
+    /// The pnchs of the group, in the same order as they were in `Pnchs`.
+    pub pnchs: Vec<&'a Pnch>,
+    /// The pnchs of the group rolled up in their chains, from the earliest one.
+    pub chains: Vec<Chain<'a>>,
+}
+
+impl<K> Group<'_, K> {
+    /// The total duration of the group, taken on its chains.
+    pub fn duration(&self) -> time::Duration {
+        self.chains.iter().map(Chain::duration).sum()
+    }
+}
+
+/// A pnch and the pnchs resumed after its breaks, see `pnch break` and `pnch resume`. A pnch
+/// without breaks is a chain of its own.
+pub struct Chain<'a> {
+    /// The pnchs of the chain, from the earliest one.
+    pub pnchs: Vec<&'a Pnch>,
+}
+
+impl Chain<'_> {
+    /// The total duration of the pnchs of the chain, the breaks are not counted.
+    pub fn duration(&self) -> time::Duration {
+        total_duration(self.pnchs.iter().copied())
+    }
+
+    /// `pnch` is resumed after the last pnch of the chain.
+    fn is_continued_by(&self, pnch: &Pnch) -> bool {
+        let last = self.pnchs.last().expect("a chain has at least one pnch");
+        last.date == pnch.date
+            && last.tag.as_ref().map(|tag| tag.id) == pnch.tag.as_ref().map(|tag| tag.id)
+            && last.description == pnch.description
+            && last.out.is_some_and(|out| out <= pnch._in)
+    }
+}
+
+pub struct PnchsList(Pnchs, DisplayOptions);
+
+impl std::fmt::Display for PnchsList {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self(pnchs, options) = self;
+        // Without a summary, the date of a single day is still printed in a heading.
+        let single_day = options.single_day && options.summary;
+        let day = pnchs.0.first().map(|pnch| &pnch.date).or(options.empty_days.first());
+        match (options.summary, day) {
+            (true, Some(day)) if single_day => writeln!(f, "{} on {day}", options.summary(pnchs))?,
+            (true, _) => writeln!(f, "{}", options.summary(pnchs))?,
+            _ => {}
+        }
+        let mut empty_days = options.empty_days.iter().peekable();
+        let mut week = None;
+        let heading = |date: &time::Date, week: &mut Option<(i32, u8)>| match options.new_week(date, week) {
+            _ if single_day => String::new(),
+            Some(number) => format!("\n{} {number}", options.date_heading(date)),
+            None => format!("\n{}", options.date_heading(date)),
+        };
+        pnchs.0
+            .iter()
+            .try_fold(time::Date::min(), |mut date, pnch| {
+                while let Some(day) = empty_days.next_if(|day| **day < pnch.date) {
+                    writeln!(f, "{}\n  no entries", heading(day, &mut week))?;
+                }
+                if date != pnch.date {
+                    date = pnch.date.clone();
+                    writeln!(f, "{}", heading(&date, &mut week))?;
+                }
+                match (&options.highlight, &pnch.description) {
+                    (Some(pattern), Some(description)) => {
+                        let highlighted = Pnch { description: Some(pattern.highlight(description)), ..pnch.clone() };
+                        writeln!(f, "{}", highlighted.display(options.time_format, options.duration_format))?;
+                    }
+                    _ => writeln!(f, "{}", pnch.display(options.time_format, options.duration_format))?,
+                }
+                for note in options.notes.iter().flat_map(|notes| notes.of(pnch.id)) {
+                    writeln!(f, "{note}")?;
+                }
+                Ok(date)
+            })?;
+        for day in empty_days {
+            writeln!(f, "{}\n  no entries", heading(day, &mut week))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Format {
+    Table,
+    List,
+    Csv,
+    Html,
+    Ics,
+    Json,
+    Template,
+}
+
+impl str::FromStr for Format {
+    type Err = GlobalError;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match &value.to_lowercase()[..] {
+            "table" => Ok(Self::Table),
+            "list" => Ok(Self::List),
+            "csv" => Ok(Self::Csv),
+            "html" => Ok(Self::Html),
+            "ics" => Ok(Self::Ics),
+            "json" => Ok(Self::Json),
+            "template" => Ok(Self::Template),
+            _ => Err(GlobalError::parse("one of `table`, `list`, `csv`, `html`, `ics`, `json` or `template`"))
+        }
+    }
+}
+
+/// The character separating the fields of the csv format.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Delimiter {
+    Comma,
+    Semicolon,
+    Tab,
+}
+
+impl Delimiter {
+    pub fn as_char(self) -> char {
+        match self {
+            Self::Comma => ',',
+            Self::Semicolon => ';',
+            Self::Tab => '\t',
+        }
+    }
+
+    pub fn from_char(c: char) -> Option<Self> {
+        match c {
+            ',' => Some(Self::Comma),
+            ';' => Some(Self::Semicolon),
+            '\t' => Some(Self::Tab),
+            _ => None,
+        }
+    }
+}
+
+impl str::FromStr for Delimiter {
+    type Err = GlobalError;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "\\t" | "tab" => Ok(Self::Tab),
+            value => {
+                let mut chars = value.chars();
+                match (chars.next().and_then(Self::from_char), chars.next()) {
+                    (Some(delimiter), None) => Ok(delimiter),
+                    _ => Err(GlobalError::parse("one of `,`, `;` or `\\t`")),
+                }
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Delimiter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Tab => write!(f, "\\t"),
+            delimiter => write!(f, "{}", delimiter.as_char()),
+        }
+    }
+}
+
+/// A set of pnch ids, parsed from single ids and ranges separated by commas, like `5,9,12-20`.
+#[derive(Debug, Clone)]
+pub struct IdSet(Vec<std::ops::RangeInclusive<u32>>);
+
+impl IdSet {
+    pub fn contains(&self, id: u32) -> bool {
+        self.0.iter().any(|range| range.contains(&id))
+    }
+}
+
+impl str::FromStr for IdSet {
+    type Err = GlobalError;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let error = || GlobalError::parse("ids or ranges of ids separated by commas, like `5,9,12-20`");
+        let parse = |id: &str| id.trim().parse::<u32>().map_err(|_| error());
+        value
+            .split(',')
+            .map(|part| match part.split_once('-') {
+                Some((first, last)) => match (parse(first)?, parse(last)?) {
+                    (first, last) if first <= last => Ok(first..=last),
+                    _ => Err(error()),
+                },
+                None => parse(part).map(|id| id..=id),
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(Self)
+    }
+}
+
+impl std::fmt::Display for IdSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let parts = self.0
+            .iter()
+            .map(|range| match range.start() == range.end() {
+                true => format!("#{}", range.start()),
+                false => format!("#{}-{}", range.start(), range.end()),
+            })
+            .collect::<Vec<_>>();
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// How to sort the pnchs when they are listed.
+#[derive(Debug, Clone, Copy)]
+pub enum Sort {
+    Date,
+    Duration,
+    Tag,
+}
+
+impl str::FromStr for Sort {
+    type Err = GlobalError;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match &value.to_lowercase()[..] {
+            "date" => Ok(Self::Date),
+            "duration" => Ok(Self::Duration),
+            "tag" => Ok(Self::Tag),
+            _ => Err(GlobalError::parse("one of `date`, `duration` or `tag`"))
+        }
+    }
+}
+
+/// How to group the pnchs when they are listed.
+#[derive(Debug, Clone)]
+pub enum GroupBy {
+    Tag,
+}
+
+impl str::FromStr for GroupBy {
+    type Err = GlobalError;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match &value.to_lowercase()[..] {
+            "tag" => Ok(Self::Tag),
+            _ => Err(GlobalError::parse("`tag`"))
+        }
+    }
+}
+
+pub struct PnchsTable(Pnchs, DisplayOptions);
+
+/// The total of the day being drawn in a table.
+struct DayTotal {
+    duration: time::Duration,
+    /// An open pnch is counted until now.
+    running: bool,
+}
+
+impl Default for DayTotal {
+    fn default() -> Self {
+        Self { duration: time::Duration::zero(), running: false }
+    }
+}
+
+impl PnchsTable {
+    const COLS: usize = 7;
+    const COLS_WIDTH: [usize; Self::COLS] = [12, 7, 16, 7, 7, 10, 44];
+    /// The description column is never narrower than this, even if the table overflows.
+    const MIN_DESCRIPTION_WIDTH: usize = 12;
+
+    /// The width of each column. The description column fills the width of the table, the other
+    /// columns have a fixed width.
+    fn widths(&self) -> [usize; Self::COLS] {
+        let mut widths = Self::COLS_WIDTH;
+        if self.1.relative_dates {
+            // Room for the longest relative date before the date, like `Wednesday (2024-05-15)`.
+            // It is longer than a date followed by its weekday.
+            widths[0] += 12;
+        } else if self.1.show_weekday {
+            // Room for the weekday after the date.
+            widths[0] += 4;
+        }
+        if self.1.show_week {
+            // Room for the week number after the date.
+            widths[0] += 4;
+        }
+        if self.1.time_format == time::TimeFormat::H12 {
+            // Room for the `AM` and `PM` after the times.
+            widths[3] += 3;
+            widths[4] += 3;
+        }
+        if self.0.0.iter().any(|pnch| pnch._in.seconds() != 0 || pnch.out.is_some_and(|out| out.seconds() != 0)) {
+            // Room for the seconds after the times.
+            widths[3] += 3;
+            widths[4] += 3;
+        }
+        if self.0.0.iter().any(|pnch| pnch.out.is_some_and(|out| out.is_next_day())) {
+            // Room for the `+1` after the end times on the next day.
+            widths[4] += 3;
+        }
+        let other_offset = |offset| self.1.other_offset(offset).is_some();
+        if self.0.0.iter().any(|pnch| other_offset(pnch.in_offset) || other_offset(pnch.out_offset)) {
+            // Room for the offsets after the times, like `-05:00`.
+            widths[3] += 7;
+            widths[4] += 7;
+        }
+        if let Some(width) = self.1.width {
+            // Every column is followed by a border, and the first column is preceded by one.
+            let fixed = widths[..Self::DESCRIPTION_COL].iter().sum::<usize>() + Self::COLS + 1;
+            widths[Self::DESCRIPTION_COL] = width
+                .saturating_sub(fixed)
+                .max(Self::MIN_DESCRIPTION_WIDTH);
+        }
+        widths
+    }
+
+    fn pnch_to_cells(&self, pnch: &Pnch, date: &mut time::Date) -> (bool, Vec<String>) {
+        let mut cells = Vec::new();
+        let did_date_update = if pnch.date != *date {
+            *date = pnch.date.clone();
+            cells.push(self.1.date_heading(date));
+            true
+        } else {
+            cells.push(String::new());
+            false
+        };
+        cells.push(pnch.id.to_string());
+        cells.push(pnch.tag.as_ref().map(|t| t.tag.to_string()).unwrap_or(String::from("---")));
+        cells.push(self.1.time_cell(pnch._in, pnch.in_offset));
+        cells.push(pnch.out.map(|out| self.1.time_cell(out, pnch.out_offset)).unwrap_or_default());
+        cells.push(match pnch.duration() {
+            Some(duration) => duration.format(self.1.duration_format),
+            None if pnch.is_inconsistent() => String::from(Pnch::INCONSISTENT_LABEL),
+            None => String::new(),
+        });
+        cells.push(pnch.description.clone().unwrap_or_default());
+        (did_date_update, cells)
+    }
+
+    const TAG_COL: usize = 2;
+    const DESCRIPTION_COL: usize = 6;
+
+    /// Close the previous day and add the separator of `day`, then return the label of its date
+    /// cell. A new week is separated by a double line when `show_week` is set.
+    fn start_day(&self, widths: &[usize; Self::COLS], rows: &mut Vec<String>, day: &time::Date, day_total: &mut DayTotal, week: &mut Option<(i32, u8)>) -> String {
+        if self.1.day_totals && rows.len() > 2 {
+            rows.push(self.day_total_row(widths, day_total));
+        }
+        *day_total = DayTotal::default();
+        match self.1.new_week(day, week) {
+            Some(number) => {
+                rows.push(self.separator(widths, "╞", "╪", "╡", "="));
+                format!("{} {number}", self.1.date_heading(day))
+            }
+            None => {
+                rows.push(self.separator(widths, "├", "┼", "┤", "-"));
+                self.1.date_heading(day)
+            }
+        }
+    }
+
+    /// Add the rows of a day without pnchs, closing the previous day.
+    fn push_empty_day(&self, widths: &[usize; Self::COLS], rows: &mut Vec<String>, day: &time::Date, day_total: &mut DayTotal, week: &mut Option<(i32, u8)>) {
+        let mut cells = vec![self.start_day(widths, rows, day, day_total, week)];
+        cells.extend(["—", "—", "—", "—", "0:00", "—"].map(String::from));
+        rows.push(self.cells_to_string(widths, cells, None));
+    }
+
+    /// The row of the total of a day, marked with a `+` when it counts an open pnch until now.
+    fn day_total_row(&self, widths: &[usize; Self::COLS], total: &DayTotal) -> String {
+        let mut cells = vec![String::new(); Self::DESCRIPTION_COL];
+        let duration = total.duration.format_long(self.1.duration_format);
+        match total.running {
+            true => cells.push(format!("day total: {duration}+")),
+            false => cells.push(format!("day total: {duration}")),
+        }
+        self.cells_to_string(widths, cells, None)
+    }
+
+    /// The row of the cells. When the row is the row of a pnch, its tag is colored and the matches
+    /// in its description are highlighted.
+    fn cells_to_string(&self, widths: &[usize; Self::COLS], cells: Vec<String>, pnch: Option<&Pnch>) -> String {
+        let mut cells = cells
+            .iter()
+            .enumerate()
+            .map(|(idx, cell)| {
+                let cell = match self.1.truncate {
+                    true => truncate(cell, widths[idx] - 2),
+                    false => cell.clone(),
+                };
+                // The padding is computed on the text alone, the colors add escape codes.
+                let padding = (widths[idx] - 2).saturating_sub(text_width(&cell));
+                let tag = pnch.and_then(|pnch| pnch.tag.as_ref());
+                let cell = match (&self.1.highlight, tag) {
+                    (Some(pattern), _) if pnch.is_some() && idx == Self::DESCRIPTION_COL => pattern.highlight(&cell),
+                    (_, Some(tag)) if idx == Self::TAG_COL => tag.paint(&cell).to_string(),
+                    _ => cell,
+                };
+                format!("│ {cell}{} ", " ".repeat(padding))
+            })
+            .collect::<String>();
+        cells.push('│');
+        cells
+    }
+
+    fn separator(&self, widths: &[usize; Self::COLS], left: &str, mid: &str, right: &str, line: &str) -> String {
+        let mut separator = String::from(left);
+        separator.push_str(&widths.iter().enumerate().map(|(idx, width)| {
+            let mut end = mid;
+            if idx == widths.len() - 1 {
+                end = right
+            }
+            format!("{}{end}", &line.repeat(*width))
+        }).collect::<String>());
+        separator
+    }
+}
+
+//┌────────────┬───────┬────────────────┬───────┬───────┬──────────┬────────────────────────────────────┐
+//│ Date       │ Id    │ Tag            │ In    │ Out   │ Duration │ Description                        │
+//├────────────┼───────┼────────────────┼───────┼───────┼──────────┼────────────────────────────────────┤
+//│ 2023-05-10 │  9:23 │ RDG-123        │  9:23 │ 10:33 │ 1:10     │ Ceci est ma description            │
+//│            │  9:23 │ RDG-123        │  9:23 │ 10:33 │ 1:10     │ Ceci est ma description            │
+//│            │  9:23 │ RDG-123-123-2..│  9:23 │ 10:33 │ 1:10     │ Ceci est ma description            │
+//├────────────┼───────┼────────────────┼───────┼───────┼──────────┼────────────────────────────────────┤
+//│ 2023-05-11 │  9:23 │ RDG-123        │  9:23 │ 10:33 │ 1:10     │ Ceci est ma description            │
+//│            │  9:23 │ RDG-123        │  9:23 │ 10:33 │ 1:10     │ Ceci est ma description            │
+//│            │  9:23 │ RDG-123        │  9:23 │       │          │ Ceci est ma description            │
+//└────────────┴───────┴────────────────┴───────┴───────┴──────────┴────────────────────────────────────┘
+impl std::fmt::Display for PnchsTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // The widths depend on every pnch, they are computed once for all the rows.
+        let widths = &self.widths();
+        let mut rows = vec![
+            self.separator(widths, "┌", "┬", "┐", "-"),
+            self.cells_to_string(widths, vec![
+                String::from("Date"), String::from("Id"), String::from("Tag"),
+                String::from("In"), String::from("Out"), String::from("Duration"),
+                String::from("Description"),
+            ], None)
+        ];
+
+        let mut date = time::Date::min();
+        let mut day_total = DayTotal::default();
+        let mut week = None;
+        let mut empty_days = self.1.empty_days.iter().peekable();
+        for pnch in self.0.0.iter() {
+            while let Some(day) = empty_days.next_if(|day| **day < pnch.date) {
+                self.push_empty_day(widths, &mut rows, day, &mut day_total, &mut week);
+            }
+            let (did_date_update, mut cells) = self.pnch_to_cells(pnch, &mut date);
+            if did_date_update {
+                cells[0] = self.start_day(widths, &mut rows, &date, &mut day_total, &mut week);
+            }
+            let (duration, running) = self.1.counted_duration(pnch);
+            day_total.duration = day_total.duration + duration;
+            day_total.running |= running;
+            rows.push(self.cells_to_string(widths, cells, Some(pnch)));
+        }
+        for day in empty_days {
+            self.push_empty_day(widths, &mut rows, day, &mut day_total, &mut week);
+        }
+        if self.1.day_totals {
+            rows.push(self.day_total_row(widths, &day_total));
+        }
+        rows.push(self.separator(widths, "└", "┴", "┘", "-"));
+        let table = rows.join("\n");
+        if self.1.summary {
+            writeln!(f, "{}", self.1.summary(&self.0))?;
+        }
+        writeln!(f, "{table}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A pnch of 2024-05-13 from 9:30:15 +02:00 to 17:10:45 -04:00.
+    fn pnch() -> Pnch {
+        let clock = time::Clock::fixed(&"2024-05-13".parse().unwrap(), "9:30:15".parse().unwrap(), time::Offset::from_minutes(120));
+        let mut pnch = Pnch::new(0, &clock, clock.now_with_seconds(), None, Some(String::from("travel")));
+        pnch.out("17:10:45".parse().unwrap(), Some(time::Offset::from_minutes(-240)), None, None).unwrap();
+        pnch
+    }
+
+    /// The record of `pnch` in the version 1 of the format, and its records in the seconds and
+    /// offsets files.
+    fn v1_records(pnch: &Pnch) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+        let record = Vec::from(pnch);
+        let key = &record[..Pnchs::SIDE_KEY_SIZE];
+        let seconds = [key, &record[Pnch::V1_SIZE..Pnch::V1_SIZE + Pnch::SECONDS_SIZE]].concat();
+        let offsets = [key, &record[Pnch::V1_SIZE + Pnch::SECONDS_SIZE..Pnch::V2_SIZE]].concat();
+        (record[..Pnch::V1_SIZE].to_vec(), seconds, offsets)
+    }
+
+    fn v1_header() -> Vec<u8> {
+        [&b"PNCH"[..], &1u16.to_le_bytes()].concat()
+    }
+
+    /// A closed pnch of `date` from `_in` to `out`, given in minutes since midnight.
+    fn closed(id: u32, date: &time::Date, (_in, out): (u64, u64), tag: Option<tag::Tag>, description: &str, resumed: bool) -> Pnch {
+        let minutes = |minutes: u64| format!("{}:{:02}", minutes / 60, minutes % 60).parse().unwrap();
+        let clock = time::Clock::fixed(date, minutes(_in), time::Offset::from_minutes(0));
+        let mut pnch = Pnch::new(id, &clock, minutes(_in), tag, Some(description.to_owned()));
+        pnch.out = Some(minutes(out));
+        pnch.out_offset = pnch.in_offset;
+        Pnch { resumed, ..pnch }
+    }
+
+    fn work() -> Option<tag::Tag> {
+        Some(tag::Tag { id: 0, tag: String::from("work") })
+    }
+
+    /// A xorshift generator: the pnchs of the property tests are random, but the same at each run.
+    struct Random(u64);
+
+    impl Random {
+        fn below(&mut self, n: u64) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0 % n
+        }
+    }
+
+    /// A few days of pnchs with a few tags and descriptions, where most pnchs are resumed after a
+    /// break, and the last pnch is still open.
+    fn random_pnchs(random: &mut Random) -> Pnchs {
+        let tags = [None, work(), Some(tag::Tag { id: 1, tag: String::from("home") })];
+        let descriptions = ["review", "meeting", "mail"];
+        let mut pnchs = Vec::new();
+        for day in 1..=1 + random.below(4) {
+            let date = format!("2024-05-{day:02}").parse().unwrap();
+            let mut minutes = 6 * 60 + random.below(120);
+            while minutes < 20 * 60 {
+                let out = minutes + 1 + random.below(150);
+                let tag = tags[random.below(3) as usize].clone();
+                let description = descriptions[random.below(3) as usize];
+                let resumed = random.below(3) > 0;
+                pnchs.push(closed(pnchs.len() as u32, &date, (minutes, out), tag, description, resumed));
+                minutes = out + random.below(45);
+            }
+        }
+        if let Some(last) = pnchs.last_mut() {
+            last.out = None;
+        }
+        Pnchs(pnchs)
+    }
+
+    #[test]
+    fn json_export_has_the_notes() {
+        let clock = time::Clock::fixed(&"2024-05-13".parse().unwrap(), "18:00".parse().unwrap(), time::Offset::from_minutes(120));
+        let mut notes = note::Notes::default();
+        notes.add(note::Note::new(0, &clock, String::from("said \"hi\"\nthen left")));
+        let mut open = Pnch::new(1, &clock, "18:00".parse().unwrap(), None, None);
+        open.in_offset = None;
+        let json = Pnchs(vec![pnch(), open]).into_json(Some(&notes));
+        assert_eq!(json, concat!(
+            "[\n",
+            "  {\"id\": 0, \"date\": \"2024-05-13\", \"in\": \"9:30:15\", \"out\": \"17:10:45\", \"tag\": null, \"description\": \"travel\", ",
+            "\"duration_minutes\": 820, \"in_offset\": \"+02:00\", \"out_offset\": \"-04:00\", ",
+            "\"notes\": [{\"date\": \"2024-05-13\", \"time\": \"18:00\", \"text\": \"said \\\"hi\\\"\\nthen left\"}]},\n",
+            "  {\"id\": 1, \"date\": \"2024-05-13\", \"in\": \"18:00\", \"out\": null, \"tag\": null, \"description\": null, ",
+            "\"duration_minutes\": null, \"in_offset\": null, \"out_offset\": null, \"notes\": []}\n",
+            "]\n",
+        ));
+        assert_eq!(Pnchs(Vec::new()).into_json(None), "[]\n");
+    }
+
+    #[test]
+    fn seconds_and_offsets_are_saved_in_the_record() {
+        let storage = storage::Storage::temp("pnch-v2-round-trip");
+        let tags = tag::Tags::load(&storage).unwrap();
+        Pnchs(vec![pnch()]).save_file(&storage, "pnchs.db").unwrap();
+        let loaded = Pnchs::load_file(&storage, "pnchs.db", &tags).unwrap();
+        assert_eq!(loaded.0, vec![pnch()]);
+        assert!(!storage.dir().join("pnchs.db.seconds").exists());
+        assert!(!storage.dir().join("pnchs.db.offsets").exists());
+        assert_eq!(storage.load("pnchs.db").unwrap().len(), 6 + Pnch::SIZE);
+    }
+
+    #[test]
+    fn v1_database_is_migrated_with_its_side_files() {
+        let storage = storage::Storage::temp("pnch-v1-migration");
+        let tags = tag::Tags::load(&storage).unwrap();
+        let (record, seconds, offsets) = v1_records(&pnch());
+        let v1 = [v1_header(), record].concat();
+        storage.save("pnchs.db", &v1, "pnchs").unwrap();
+        storage.save("pnchs.db.seconds", &seconds, "seconds").unwrap();
+        storage.save("pnchs.db.offsets", &offsets, "offsets").unwrap();
+
+        let loaded = Pnchs::load_file(&storage, "pnchs.db", &tags).unwrap();
+        assert_eq!(loaded.0, vec![pnch()]);
+        assert_eq!(Pnchs::FORMAT.version(&storage, "pnchs.db").unwrap(), 3);
+        assert_eq!(storage.load("pnchs.db.v1.bak").unwrap(), v1);
+        assert_eq!(storage.load("pnchs.db.seconds.v1.bak").unwrap(), seconds);
+        assert_eq!(storage.load("pnchs.db.offsets.v1.bak").unwrap(), offsets);
+        assert!(!storage.dir().join("pnchs.db.seconds").exists());
+        assert!(!storage.dir().join("pnchs.db.offsets").exists());
+        assert_eq!(Pnchs::load_file(&storage, "pnchs.db", &tags).unwrap().0, vec![pnch()]);
+    }
+
+    #[test]
+    fn v0_database_without_side_files_is_migrated() {
+        let storage = storage::Storage::temp("pnch-v0-migration");
+        let tags = tag::Tags::load(&storage).unwrap();
+        let (record, _, _) = v1_records(&pnch());
+        storage.save("pnchs.db", &record, "pnchs").unwrap();
+
+        let loaded = Pnchs::load_file(&storage, "pnchs.db", &tags).unwrap();
+        let pnch = &loaded.0[0];
+        assert_eq!((pnch._in.seconds(), pnch.out.map(|out| out.seconds())), (0, Some(0)));
+        assert_eq!((pnch.in_offset, pnch.out_offset), (None, None));
+        assert_eq!(pnch.description.as_deref(), Some("travel"));
+        assert_eq!(storage.load("pnchs.db.v0.bak").unwrap(), record);
+    }
+
+    #[test]
+    fn side_records_of_another_pnch_are_ignored() {
+        let storage = storage::Storage::temp("pnch-v1-stale-side-file");
+        let tags = tag::Tags::load(&storage).unwrap();
+        let (record, _, _) = v1_records(&pnch());
+        let mut other = pnch();
+        other._in = "8:00:30".parse().unwrap();
+        let (_, seconds, _) = v1_records(&other);
+        storage.save("pnchs.db", &[v1_header(), record].concat(), "pnchs").unwrap();
+        storage.save("pnchs.db.seconds", &seconds, "seconds").unwrap();
+
+        let loaded = Pnchs::load_file(&storage, "pnchs.db", &tags).unwrap();
+        assert_eq!(loaded.0[0]._in, "9:30".parse().unwrap());
+    }
+
+    #[test]
+    fn v2_database_is_migrated_without_flags() {
+        let storage = storage::Storage::temp("pnch-v2-migration");
+        let tags = tag::Tags::load(&storage).unwrap();
+        let v2 = [&b"PNCH"[..], &2u16.to_le_bytes(), &Vec::from(&pnch())[..Pnch::V2_SIZE]].concat();
+        storage.save("pnchs.db", &v2, "pnchs").unwrap();
+
+        assert_eq!(Pnchs::load_file(&storage, "pnchs.db", &tags).unwrap().0, vec![pnch()]);
+        assert_eq!(Pnchs::FORMAT.version(&storage, "pnchs.db").unwrap(), 3);
+        assert_eq!(storage.load("pnchs.db.v2.bak").unwrap(), v2);
+    }
+
+    #[test]
+    fn resumed_flag_is_saved_in_the_record() {
+        let storage = storage::Storage::temp("pnch-resumed-round-trip");
+        let tags = tag::Tags::load(&storage).unwrap();
+        let resumed = Pnch { id: 1, _in: "18:00".parse().unwrap(), out: None, resumed: true, ..pnch() };
+        Pnchs(vec![pnch(), resumed.clone()]).save_file(&storage, "pnchs.db").unwrap();
+        assert_eq!(Pnchs::load_file(&storage, "pnchs.db", &tags).unwrap().0, vec![pnch(), resumed]);
+    }
+
+    #[test]
+    fn break_of_an_older_version_is_loaded() {
+        let storage = storage::Storage::temp("pnch-old-break");
+        let tags = tag::Tags::load(&storage).unwrap();
+        let record = Vec::from(&pnch());
+        storage.save(Break::BREAK_FILE_NAME, &record[..Pnch::V1_SIZE], "break").unwrap();
+        let Break(pause) = Break::load(&storage, &tags).unwrap().unwrap();
+        assert_eq!((pause._in, pause.in_offset, pause.resumed), ("9:30".parse().unwrap(), None, false));
+
+        storage.save(Break::BREAK_FILE_NAME, &record[..Pnch::V2_SIZE], "break").unwrap();
+        assert_eq!(Break::load(&storage, &tags).unwrap().unwrap().0, pnch());
+
+        Break(pnch()).save(&storage).unwrap();
+        assert_eq!(Break::load(&storage, &tags).unwrap().unwrap().0, pnch());
+        Break::clear(&storage).unwrap();
+        assert!(Break::load(&storage, &tags).unwrap().is_none());
+    }
+
+    #[test]
+    fn resumed_pnchs_are_rolled_up_in_their_chain() {
+        let date = "2024-05-13".parse().unwrap();
+        let pnchs = Pnchs(vec![
+            closed(0, &date, (9 * 60, 10 * 60), work(), "review", false),
+            closed(1, &date, (10 * 60 + 15, 11 * 60), work(), "review", true),
+            closed(2, &date, (11 * 60, 12 * 60), work(), "meeting", false),
+            closed(3, &date, (12 * 60 + 30, 13 * 60), work(), "review", true),
+            // Resumed without a pnch to continue.
+            closed(4, &date, (13 * 60, 14 * 60), None, "mail", true),
+        ]);
+        let ids = |chain: &Chain| chain.pnchs.iter().map(|pnch| pnch.id).collect::<Vec<_>>();
+        assert_eq!(pnchs.chains().iter().map(ids).collect::<Vec<_>>(), vec![vec![0, 1, 3], vec![2], vec![4]]);
+
+        let groups = pnchs.group_by(|pnch| pnch.description.clone());
+        let review = groups.iter().find(|group| group.key.as_deref() == Some("review")).unwrap();
+        assert_eq!(review.chains.iter().map(ids).collect::<Vec<_>>(), vec![vec![0, 1, 3]]);
+        assert_eq!(review.duration(), time::Duration::from_seconds(135 * 60));
+
+        // The chain is split between the morning and the afternoon, so it is not rolled up.
+        let groups = pnchs.group_by(|pnch| pnch._in < "12:00".parse().unwrap());
+        let morning = groups.iter().find(|group| group.key).unwrap();
+        assert_eq!(morning.chains.iter().map(ids).collect::<Vec<_>>(), vec![vec![0], vec![1], vec![2]]);
+        assert_eq!(groups.iter().map(|group| group.duration()).sum::<time::Duration>(), pnchs.duration());
+    }
+
+    #[test]
+    fn grand_totals_do_not_depend_on_the_grouping() {
+        let mut rolled_up = 0;
+        for seed in 1..=200u64 {
+            let mut random = Random(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+            let pnchs = random_pnchs(&mut random);
+            let total = pnchs.0.iter().filter_map(|pnch| pnch.duration()).sum::<time::Duration>();
+            let chains = pnchs.chains();
+            assert_eq!(chains.iter().map(Chain::duration).sum::<time::Duration>(), total);
+            rolled_up += chains.iter().filter(|chain| chain.pnchs.len() > 1).count();
+
+            let by_entry = pnchs.group_by(|pnch| pnch.id);
+            let by_task = pnchs.group_by(|pnch| (pnch.tag.as_ref().map(|tag| tag.id), pnch.description.clone()));
+            let by_tag = pnchs.group_by(|pnch| pnch.tag.as_ref().map(|tag| tag.id));
+            let by_day = pnchs.group_by(|pnch| pnch.date.clone());
+            let by_morning = pnchs.group_by(|pnch| pnch._in < "12:00".parse().unwrap());
+            fn check<K>(groups: &[Group<'_, K>], total: time::Duration, count: usize) -> usize {
+                assert_eq!(groups.iter().map(|group| group.duration()).sum::<time::Duration>(), total);
+                let mut ids = groups.iter()
+                    .flat_map(|group| group.chains.iter().flat_map(|chain| chain.pnchs.iter().map(|pnch| pnch.id)))
+                    .collect::<Vec<_>>();
+                ids.sort();
+                assert_eq!(ids, (0..count as u32).collect::<Vec<_>>());
+                groups.iter().map(|group| group.chains.len()).sum()
+            }
+            let count = pnchs.0.len();
+            assert_eq!(check(&by_entry, total, count), count);
+            // A chain has one task and one day, so it is always rolled up.
+            assert_eq!(check(&by_task, total, count), chains.len());
+            assert_eq!(check(&by_day, total, count), chains.len());
+            assert!(check(&by_tag, total, count) >= chains.len());
+            assert!(check(&by_morning, total, count) >= chains.len());
+        }
+        assert!(rolled_up > 0);
+    }
+
+    #[test]
+    fn csv_records_follow_rfc_4180() {
+        let csv = "a,\"b, c\",\"say \"\"hi\"\"\"\r\n\n,\"two\nlines\",\n";
+        assert_eq!(csv_records(csv, ','), vec![
+            vec![String::from("a"), String::from("b, c"), String::from("say \"hi\"")],
+            vec![String::new(), String::from("two\nlines"), String::new()],
+        ]);
+        assert_eq!(csv_records("a;b", ';'), vec![vec![String::from("a"), String::from("b")]]);
+    }
+
+    #[test]
+    fn csv_import_skips_the_future_rows_unless_forced() {
+        let storage = storage::Storage::temp("pnch-csv-import");
+        let mut tags = tag::Tags::load(&storage).unwrap();
+        let clock = time::Clock::fixed(&"2024-05-13".parse().unwrap(), "18:00".parse().unwrap(), time::Offset::from_minutes(120));
+        let pnchs = Pnchs(vec![pnch()]);
+        let csv = Pnchs(vec![
+            closed(0, &"2024-05-10".parse().unwrap(), (9 * 60, 10 * 60), work(), "review, again", false),
+            closed(1, &"2024-05-14".parse().unwrap(), (9 * 60, 10 * 60), None, "tomorrow", false),
+            closed(2, &"2025-05-13".parse().unwrap(), (9 * 60, 10 * 60), None, "next year", false),
+        ]).into_csv(true, Delimiter::Comma, time::TimeFormat::H24, time::DurationFormat::Clock).unwrap();
+
+        let (imported, skipped) = pnchs.read_csv(&csv, "pnchs.csv", Delimiter::Comma, &mut tags, &clock, false).unwrap();
+        assert_eq!(skipped, 2);
+        assert_eq!(imported.len(), 1);
+        let pnch = &imported[0];
+        assert_eq!((pnch.id, pnch.date.to_string(), pnch.description.as_deref()), (1, String::from("2024-05-10"), Some("review, again")));
+        assert_eq!((pnch._in, pnch.out), ("9:00".parse().unwrap(), Some("10:00".parse().unwrap())));
+        assert_eq!(pnch.tag.as_ref().map(|tag| tag.tag.as_str()), Some("work"));
+
+        let (imported, skipped) = pnchs.read_csv(&csv, "pnchs.csv", Delimiter::Comma, &mut tags, &clock, true).unwrap();
+        assert_eq!((imported.len(), skipped), (3, 0));
+        assert_eq!(imported.iter().map(|pnch| pnch.id).collect::<Vec<_>>(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn csv_import_refuses_invalid_rows() {
+        let storage = storage::Storage::temp("pnch-csv-import-invalid");
+        let mut tags = tag::Tags::load(&storage).unwrap();
+        let clock = time::Clock::fixed(&"2024-05-13".parse().unwrap(), "18:00".parse().unwrap(), time::Offset::from_minutes(120));
+        let pnchs = Pnchs(Vec::new());
+        for csv in [",a,2024-05-10,9:00\n", ",a,2024-05-10,9:00,,\n", ",a,2024-05-10,10:00,9:00,\n", ",a,2024-13-10,9:00,10:00,\n"] {
+            assert!(pnchs.read_csv(csv, "pnchs.csv", Delimiter::Comma, &mut tags, &clock, true).is_err(), "{csv}");
+        }
+        let csv = "A,a,2024-05-10,9:00,10:00,\nABCDEFGHIJKLMNOPQRSTUVWXYZ0123,b,2024-05-10,10:00,11:00,\n";
+        let error = pnchs.read_csv(csv, "pnchs.csv", Delimiter::Comma, &mut tags, &clock, true).unwrap_err().to_string();
+        assert!(error.contains("The tag `ABCDEFGHIJKLMNOPQRSTUVWXYZ0123` is too long."), "{error}");
+        assert!(error.contains("It is the row 2 of `pnchs.csv`."), "{error}");
+    }
+
+    #[test]
+    fn relative_dates_keep_the_absolute_date() {
+        let options = DisplayOptions { show_weekday: true, ..DisplayOptions::new("2024-01-02".parse().unwrap(), true) };
+        let heading = |date: &str| options.date_heading(&date.parse().unwrap());
+        assert_eq!(heading("2024-01-02"), "Today (2024-01-02)");
+        assert_eq!(heading("2024-01-01"), "Yesterday (2024-01-01)");
+        assert_eq!(heading("2023-12-27"), "Wednesday (2023-12-27)");
+        // A week ago has the same weekday as today, the date is not relative anymore.
+        assert_eq!(heading("2023-12-26"), "2023-12-26 Tue");
+        assert_eq!(heading("2024-01-03"), "2024-01-03 Wed");
+        let options = DisplayOptions { relative_dates: false, ..options };
+        assert_eq!(options.date_heading(&"2024-01-02".parse().unwrap()), "2024-01-02 Tue");
+    }
+
+    #[test]
+    fn table_prints_the_absolute_date_next_to_the_relative_one() {
+        let options = DisplayOptions { show_week: true, ..DisplayOptions::new("2024-05-14".parse().unwrap(), true) };
+        let table = Pnchs(vec![pnch()]).into_table(options).to_string();
+        assert!(table.contains("│ Yesterday (2024-05-13) W20 "), "{table}");
+    }
+
+    #[test]
+    fn table_date_column_fits_the_weekday() {
+        let options = DisplayOptions { show_weekday: true, ..DisplayOptions::new("2024-05-20".parse().unwrap(), false) };
+        let table = Pnchs(vec![pnch()]).into_table(options).to_string();
+        assert!(table.contains("│ 2024-05-13 Mon │"), "{table}");
+        assert!(!table.contains('…'), "{table}");
+    }
+
+    #[test]
+    fn set_times_checks_the_final_pair() {
+        let date = "2024-05-13".parse().unwrap();
+        let original = closed(0, &date, (9 * 60, 10 * 60), None, "review", false);
+        let at = |value: &str| Some(value.parse::<time::Time>().unwrap());
+
+        let mut pnch = original.clone();
+        assert!(pnch.set_times(None, at("8:00")).is_err());
+        assert!(pnch.set_times(at("11:00"), None).is_err());
+        assert!(pnch.set_times(at("12:00"), at("11:00")).is_err());
+        assert_eq!(pnch, original);
+        // An entry can end when it starts.
+        let mut empty = original.clone();
+        empty.set_times(at("10:00"), None).unwrap();
+        assert_eq!(empty.duration(), Some(time::Duration::zero()));
+
+        // Both bounds move past each other: only the final pair is checked.
+        pnch.set_times(at("18:00"), at("19:00")).unwrap();
+        assert_eq!((pnch._in, pnch.out), (at("18:00").unwrap(), at("19:00")));
+        pnch.set_times(at("8:00"), at("8:30")).unwrap();
+        assert_eq!(pnch.duration(), Some(time::Duration::from_seconds(30 * 60)));
+    }
+
+    #[test]
+    fn set_times_accepts_an_out_on_the_next_day() {
+        let date = "2024-05-13".parse().unwrap();
+        let mut pnch = closed(0, &date, (22 * 60, 23 * 60), None, "deploy", false);
+        let next_day = "1:00".parse::<time::Time>().unwrap().next_day();
+        pnch.set_times(None, Some(next_day)).unwrap();
+        assert_eq!(pnch.duration(), Some(time::Duration::from_seconds(3 * 3600)));
+        pnch.set_times(Some("23:30".parse().unwrap()), None).unwrap();
+        assert_eq!(pnch.duration(), Some(time::Duration::from_seconds(90 * 60)));
+        // A start on the next day is after the end of the day.
+        assert!(pnch.set_times(Some("23:59".parse().unwrap()), Some("23:00".parse().unwrap())).is_err());
+    }
+
+    #[test]
+    fn csv_fields_are_quoted_when_needed() {
+        assert_eq!(csv_escape("plain text", ','), "plain text");
+        assert_eq!(csv_escape("fix parser, add tests", ','), "\"fix parser, add tests\"");
+        assert_eq!(csv_escape("say \"hi\"", ','), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_escape("two\nlines", ','), "\"two\nlines\"");
+        assert_eq!(csv_escape("a, b", ';'), "a, b");
+        assert_eq!(csv_escape("a; b", ';'), "\"a; b\"");
+    }
+
+    #[test]
+    fn csv_export_quotes_the_tags_and_descriptions() {
+        let date = "2024-05-13".parse().unwrap();
+        let tag = Some(tag::Tag { id: 0, tag: String::from("a,b") });
+        let pnchs = Pnchs(vec![
+            closed(0, &date, (9 * 60, 10 * 60), tag, "fix parser, add tests", false),
+            closed(1, &date, (10 * 60, 11 * 60), None, "say \"hi\"\nthen leave", false),
+        ]);
+        let csv = pnchs.into_csv(true, Delimiter::Comma, time::TimeFormat::H24, time::DurationFormat::Clock).unwrap();
+        assert_eq!(csv, concat!(
+            "tag,description,date,in,out,duration_minutes\n",
+            "\"a,b\",\"fix parser, add tests\",2024-05-13,9:00,10:00,60\n",
+            ",\"say \"\"hi\"\"\nthen leave\",2024-05-13,10:00,11:00,60\n",
+        ));
+        assert_eq!(csv_records(&csv, ',')[2][1], "say \"hi\"\nthen leave");
+    }
+
+    #[test]
+    fn open_pnchs_are_counted_until_now_without_spanning_midnight() {
+        let today = "2024-05-15".parse::<time::Date>().unwrap();
+        let yesterday = today.previous().unwrap();
+        let running = Running { today: today.clone(), now: "10:30".parse().unwrap(), offset: Some(time::Offset::from_minutes(0)) };
+        let open = |date: &time::Date, _in: &str| {
+            let clock = time::Clock::fixed(date, _in.parse().unwrap(), time::Offset::from_minutes(0));
+            Pnch::new(0, &clock, _in.parse().unwrap(), work(), Some(String::from("open")))
+        };
+        let minutes = |minutes: i64| Some(time::Duration::from_seconds(minutes * 60));
+        assert_eq!(open(&today, "9:00").punched_out_now(&running).duration(), minutes(90));
+        // An entry opened yesterday ends at midnight, the time after it is not counted.
+        let overnight = open(&yesterday, "22:15").punched_out_now(&running);
+        assert_eq!(overnight.out, Some(time::Time::end_of_day()));
+        assert_eq!(overnight.duration(), minutes(105));
+        assert_eq!(open(&today, "11:00").punched_out_now(&running).duration(), minutes(0));
+        assert_eq!(open(&today.next().unwrap(), "9:00").punched_out_now(&running).duration(), minutes(0));
+        let done = closed(1, &yesterday, (600, 660), work(), "done", false);
+        assert_eq!(done.punched_out_now(&running), done);
+
+        let pnchs = Pnchs(vec![done, open(&yesterday, "22:15")]);
+        let mut options = DisplayOptions::new(today, false);
+        assert_eq!(options.summary(&pnchs), "You were punched in for 1 hours 0 minutes");
+        options.running = Some(running);
+        assert_eq!(options.summary(&pnchs), "You were punched in for 2 hours 45 minutes, counting the open entry until now");
+    }
+
+    #[test]
+    fn rounded_totals_are_sums_of_rounded_durations() {
+        let date = "2024-05-13".parse().unwrap();
+        let pnchs = Pnchs(vec![
+            closed(0, &date, (9 * 60, 9 * 60 + 8), work(), "call", false),
+            closed(1, &date, (10 * 60, 10 * 60 + 8), work(), "call", false),
+            closed(2, &date, (11 * 60, 11 * 60 + 8), work(), "call", false),
+        ]);
+        let minutes = |minutes: i64| time::Duration::from_seconds(minutes * 60);
+        assert_eq!(pnchs.duration(), minutes(24));
+        let rounded = Pnchs(pnchs.0.clone()).rounded(time::Rounding::Nearest(15));
+        assert_eq!(rounded.duration(), minutes(45));
+        assert_eq!(rounded.duration_by_tag(), vec![(Some(String::from("work")), minutes(45))]);
+        assert_eq!(Pnchs(pnchs.0.clone()).rounded(time::Rounding::Up(30)).duration(), minutes(90));
+        // The times are kept, only the durations are rounded.
+        assert!(rounded.0.iter().zip(&pnchs.0).all(|(rounded, raw)| rounded._in == raw._in && rounded.out == raw.out));
+        assert_eq!(rounded.0.iter().map(Vec::<u8>::from).collect::<Vec<_>>(), pnchs.0.iter().map(Vec::<u8>::from).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn split_specs_are_parsed_in_order() {
+        let split = "ISSUE-1=1h30m,ISSUE-2:Review=rest".parse::<Split>().unwrap();
+        let parts = split.0.iter().map(|part| (part.tag.as_str(), part.description.as_deref(), part.duration)).collect::<Vec<_>>();
+        assert_eq!(parts, vec![
+            ("ISSUE-1", None, Some(time::Duration::from_seconds(90 * 60))),
+            ("ISSUE-2", Some("Review"), None),
+        ]);
+        for spec in ["A=1h,B=rest,C=rest", "=1h", "A", "A=0m,B=rest", "A=soon", "ABCDEFGHIJKLMNOPQRSTUVWXYZ0123=rest"] {
+            assert!(spec.parse::<Split>().is_err(), "{spec}");
+        }
+    }
+
+    #[test]
+    fn split_durations_cover_the_entry() {
+        let minutes = |minutes: i64| time::Duration::from_seconds(minutes * 60);
+        let durations = |spec: &str, total: i64| spec.parse::<Split>().unwrap().durations(minutes(total));
+        assert_eq!(durations("A=1h30m,B=rest", 180).unwrap(), vec![minutes(90), minutes(90)]);
+        assert_eq!(durations("A=1h,B=2h", 180).unwrap(), vec![minutes(60), minutes(120)]);
+        assert_eq!(durations("A=rest,B=3h", 180).unwrap(), vec![minutes(0), minutes(180)]);
+        assert!(durations("A=1h,B=1h", 180).is_err());
+        let error = durations("A=2h,B=2h,C=rest", 180).unwrap_err().to_string();
+        assert!(error.contains(&format!("add up to {}, but the entry lasts {}", minutes(240), minutes(180))), "{error}");
+    }
+
+    #[test]
+    fn out_split_replaces_the_open_entry_by_consecutive_entries() {
+        let clock = time::Clock::fixed(&"2024-05-13".parse().unwrap(), "9:00".parse().unwrap(), time::Offset::from_minutes(0));
+        let open = || Pnchs(vec![
+            closed(0, &"2024-05-13".parse().unwrap(), (8 * 60, 9 * 60), work(), "before", false),
+            Pnch::new(1, &clock, "9:00:20".parse().unwrap(), None, Some(String::from("block"))),
+        ]);
+        let times = |pnchs: &Pnchs| pnchs.0
+            .iter()
+            .map(|pnch| (pnch._in.to_string(), pnch.out.unwrap().to_string(), pnch.tag.as_ref().unwrap().tag.clone(), pnch.description.clone().unwrap()))
+            .collect::<Vec<_>>();
+        let at = "12:00:20".parse().unwrap();
+        let offset = clock.offset();
+
+        let mut tags = tag::Tags::load(&storage::Storage::temp("pnch-out-split")).unwrap();
+        let mut pnchs = open();
+        assert_eq!(pnchs.out_split(at, offset, &"ISSUE-1=1h30m,ISSUE-2:Review=rest".parse().unwrap(), &mut tags).unwrap(), 2);
+        assert_eq!(times(&pnchs)[1..], [
+            (String::from("9:00:20"), String::from("10:30:20"), String::from("ISSUE-1"), String::from("block")),
+            (String::from("10:30:20"), String::from("12:00:20"), String::from("ISSUE-2"), String::from("Review")),
+        ]);
+
+        // The parts consume the whole entry, without a rest.
+        let mut pnchs = open();
+        assert_eq!(pnchs.out_split(at, offset, &"A=1h,B=1h,C=1h".parse().unwrap(), &mut tags).unwrap(), 3);
+        let ends = times(&pnchs).into_iter().map(|(_in, out, tag, _)| (_in, out, tag)).collect::<Vec<_>>();
+        assert_eq!(ends[1..], [
+            (String::from("9:00:20"), String::from("10:00:20"), String::from("A")),
+            (String::from("10:00:20"), String::from("11:00:20"), String::from("B")),
+            (String::from("11:00:20"), String::from("12:00:20"), String::from("C")),
+        ]);
+
+        // An empty rest adds no entry.
+        let mut pnchs = open();
+        assert_eq!(pnchs.out_split(at, offset, &"A=3h,B=rest".parse().unwrap(), &mut tags).unwrap(), 1);
+        assert_eq!(pnchs.0.len(), 2);
+
+        // An invalid split leaves the entry open.
+        let mut pnchs = open();
+        assert!(pnchs.out_split(at, offset, &"A=2h,B=2h".parse().unwrap(), &mut tags).is_err());
+        assert!(pnchs.out_split("8:00".parse().unwrap(), offset, &"A=rest".parse().unwrap(), &mut tags).is_err());
+    }
+
+    #[test]
+    fn stat_reads_only_the_first_and_last_entries() {
+        let storage = storage::Storage::temp("pnch-stat");
+        let save = |records: &[u8]| storage.save(Pnchs::PNCHS_FILE_NAME, records, "pnchs").unwrap();
+        let stat = || Pnchs::stat(&storage).unwrap();
+        let (monday, friday) = ("2024-05-13".parse().unwrap(), "2024-05-17".parse().unwrap());
+        let first = Vec::from(&closed(0, &monday, (9 * 60, 10 * 60), work(), "first", false));
+        let middle = Vec::from(&closed(1, &"2024-05-15".parse().unwrap(), (9 * 60, 10 * 60), work(), "middle", false));
+        let clock = time::Clock::fixed(&friday, "9:00".parse().unwrap(), time::Offset::from_minutes(0));
+        let open = Vec::from(&Pnch::new(2, &clock, "9:00".parse().unwrap(), None, Some(String::from("open"))));
+
+        let stats = stat();
+        assert_eq!((stats.count, stats.trailing_bytes, stats.span, stats.open), (0, 0, None, false));
+        save(b"");
+        assert_eq!((stat().count, stat().span), (0, None));
+        save(&Pnchs::FORMAT.with_header(&[]));
+        assert_eq!((stat().count, stat().trailing_bytes, stat().span), (0, 0, None));
+
+        save(&Pnchs::FORMAT.with_header(&[first.clone(), middle.clone(), open.clone()].concat()));
+        let stats = stat();
+        assert_eq!((stats.count, stats.trailing_bytes, stats.open, stats.approximate_span), (3, 0, true, false));
+        assert_eq!(stats.span, Some((monday.clone(), friday.clone())));
+        assert!(stats.to_string().contains("span: 2024-05-13 to 2024-05-17\nopen entry: yes"));
+
+        // The complete entries before the garbage are counted.
+        save(&Pnchs::FORMAT.with_header(&[&first[..], &middle[..], &open[..5]].concat()));
+        let stats = stat();
+        assert_eq!((stats.count, stats.trailing_bytes, stats.open), (2, 5, false));
+        assert_eq!(stats.span, Some((monday.clone(), "2024-05-15".parse().unwrap())));
+        assert!(stats.to_string().contains("5 trailing bytes do not form a complete entry"));
+
+        // An older database may not be sorted, its span is only approximate.
+        save(&Pnchs::FORMAT.with_header(&[open.clone(), middle.clone(), first.clone()].concat()));
+        let stats = stat();
+        assert_eq!((stats.count, stats.open, stats.approximate_span), (3, false, true));
+        assert_eq!(stats.span, Some((monday, friday)));
+        assert!(stats.to_string().contains("(approximate, the entries are not sorted)"));
+    }
+
+    #[test]
+    fn ics_events_of_identical_entries_have_different_uids() {
+        let date = "2024-05-13".parse().unwrap();
+        let clock = time::Clock::fixed(&date, "18:00".parse().unwrap(), time::Offset::from_minutes(0));
+        let pnchs = || Pnchs(vec![
+            closed(0, &date, (9 * 60, 10 * 60), work(), "same", false),
+            closed(1, &date, (9 * 60, 10 * 60), work(), "same", false),
+            closed(2, &date, (9 * 60, 10 * 60), None, "same", false),
+        ]);
+        let uids = |profile| pnchs().into_ics(&clock, profile).0
+            .lines()
+            .filter_map(|line| line.strip_prefix("UID:").map(str::to_owned))
+            .collect::<Vec<_>>();
+        let default = uids(None);
+        assert_eq!(default, vec!["20240513T090000-100000-0-0@pnch", "20240513T090000-100000-1-0@pnch", "20240513T090000-100000-2-none@pnch"]);
+        assert!(uids(Some("personal")).iter().all(|uid| uid.ends_with("@personal.pnch") && !default.contains(uid)));
+    }
+
+    #[test]
+    fn ics_times_are_in_utc_when_the_offsets_are_known() {
+        let date = "2024-05-13".parse::<time::Date>().unwrap();
+        let clock = time::Clock::fixed(&date, "18:00".parse().unwrap(), time::Offset::from_minutes(0));
+        let mut overnight = closed(1, &date, (22 * 60, 0), work(), "overnight", false);
+        overnight.out = Some("1:00".parse::<time::Time>().unwrap().next_day());
+        overnight.in_offset = Some(time::Offset::from_minutes(120));
+        overnight.out_offset = Some(time::Offset::from_minutes(120));
+        let mut floating = closed(2, &date, (9 * 60, 10 * 60), work(), "floating", false);
+        floating.out_offset = None;
+        let (ics, _) = Pnchs(vec![pnch(), overnight, floating]).into_ics(&clock, None);
+        let times = ics.lines().filter(|line| line.starts_with("DTSTART") || line.starts_with("DTEND")).collect::<Vec<_>>();
+        assert_eq!(times, vec![
+            "DTSTART:20240513T073015Z", "DTEND:20240513T211045Z",
+            "DTSTART:20240513T200000Z", "DTEND:20240513T230000Z",
+            "DTSTART:20240513T090000", "DTEND:20240513T100000",
+        ]);
     }
 }
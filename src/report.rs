@@ -0,0 +1,343 @@
+use std::str;
+use crate::{time, pnch, error::GlobalError};
+
+/// The total duration of each day, compared to the day target.
+pub struct DayReport {
+    /// The total of each day, sorted by date.
+    pub days: Vec<(time::Date, time::Duration)>,
+    /// The day target, no difference is computed when it is zero.
+    pub target: time::Duration,
+    pub duration_format: time::DurationFormat,
+}
+
+impl DayReport {
+    /// The report of the days with pnchs. The `empty_days` are added with a duration of zero, so
+    /// they count as a deficit.
+    pub fn new(pnchs: &pnch::Pnchs, target: time::Duration, empty_days: Vec<time::Date>, duration_format: time::DurationFormat) -> Self {
+        let mut days = pnchs.day_totals();
+        days.extend(empty_days.into_iter().map(|date| (date, time::Duration::zero())));
+        days.sort();
+        Self { days, target, duration_format }
+    }
+
+    fn has_target(&self) -> bool {
+        self.target > time::Duration::zero()
+    }
+}
+
+impl std::fmt::Display for DayReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.days.is_empty() {
+            return writeln!(f, "No pnchs were found.");
+        }
+        let format = self.duration_format;
+        for (date, duration) in self.days.iter() {
+            let weekday = date.weekday().map(|w| w.to_string()).unwrap_or_default();
+            write!(f, "{date} {weekday:<10} {:<22}", duration.format_long(format))?;
+            if self.has_target() {
+                write!(f, "{}", (*duration - self.target).format_signed(format))?;
+            }
+            writeln!(f)?;
+        }
+        let total = self.days.iter().map(|(_, duration)| *duration).sum::<time::Duration>();
+        writeln!(f, "\ntotal: {}", total.format_long(format))?;
+        if self.has_target() {
+            let expected = time::Duration::from_seconds(self.target.as_seconds() * self.days.len() as i64);
+            writeln!(f, "difference with the day target of {}: {}",
+                self.target.format(format), (total - expected).format_signed(format))?;
+        }
+        Ok(())
+    }
+}
+
+/// The total duration of each tag.
+pub struct TagReport {
+    /// The name, total duration and number of pnchs of each tag, from the longest to the
+    /// shortest total. Pnchs without a tag have no name.
+    pub tags: Vec<(Option<String>, time::Duration, usize)>,
+    pub duration_format: time::DurationFormat,
+}
+
+impl TagReport {
+    pub fn new(pnchs: &pnch::Pnchs, duration_format: time::DurationFormat) -> Self {
+        let mut tags = pnchs
+            .group_by(|pnch| pnch.tag.as_ref().map(|tag| tag.tag.clone()))
+            .into_iter()
+            .map(|group| {
+                let duration = group.duration();
+                (group.key, duration, group.pnchs.len())
+            })
+            .collect::<Vec<_>>();
+        tags.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        Self { tags, duration_format }
+    }
+}
+
+impl std::fmt::Display for TagReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.tags.is_empty() {
+            return writeln!(f, "No pnchs were found.");
+        }
+        for (tag, duration, count) in self.tags.iter() {
+            let tag = tag.as_deref().unwrap_or("---");
+            writeln!(f, "{tag:<24} {:<22} {count} entries", duration.format_long(self.duration_format))?;
+        }
+        let total = self.tags.iter().map(|(_, duration, _)| *duration).sum::<time::Duration>();
+        writeln!(f, "\ntotal: {}", total.format_long(self.duration_format))
+    }
+}
+
+/// The number of different days of the pnchs.
+fn active_days(pnchs: &[&pnch::Pnch]) -> usize {
+    let mut days = pnchs.iter().map(|pnch| &pnch.date).collect::<Vec<_>>();
+    days.sort();
+    days.dedup();
+    days.len()
+}
+
+/// How the totals of a report are grouped, instead of by tag.
+#[derive(Debug, Clone, Copy)]
+pub enum GroupBy {
+    Week,
+    Month,
+}
+
+impl str::FromStr for GroupBy {
+    type Err = GlobalError;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match &value.to_lowercase()[..] {
+            "week" => Ok(Self::Week),
+            "month" => Ok(Self::Month),
+            _ => Err(GlobalError::parse("`week` or `month`"))
+        }
+    }
+}
+
+/// The total of a calendar week.
+pub struct WeekTotal {
+    /// The ISO year and number of the week. A week that does not start on monday has the number
+    /// of the ISO week containing most of its days.
+    pub week: (i32, u8),
+    /// The first day of the week.
+    pub first: time::Date,
+    /// The last day of the week.
+    pub last: time::Date,
+    pub duration: time::Duration,
+    /// The number of days of the week with pnchs.
+    pub active_days: usize,
+}
+
+impl WeekTotal {
+    /// An empty week, starting on `first`.
+    fn new(first: time::Date) -> Option<Self> {
+        Some(Self {
+            // The middle day of the week is in the ISO week with most of its days.
+            week: first.add_days(3)?.iso_week()?,
+            last: first.add_days(6)?,
+            first,
+            duration: time::Duration::zero(),
+            active_days: 0,
+        })
+    }
+
+    /// The average duration of the days with pnchs.
+    fn average(&self) -> time::Duration {
+        match self.active_days {
+            0 => time::Duration::zero(),
+            days => time::Duration::from_seconds(self.duration.as_seconds() / days as i64),
+        }
+    }
+}
+
+/// The total duration of each week.
+pub struct WeekReport {
+    /// The total of each week, sorted.
+    pub weeks: Vec<WeekTotal>,
+    pub duration_format: time::DurationFormat,
+}
+
+impl WeekReport {
+    const CSV_COLUMNS: [&'static str; 6] = ["week", "from", "to", "duration_minutes", "active_days", "average_minutes"];
+
+    /// The report of the weeks with pnchs, starting on `week_start`. With `empty_weeks`, the
+    /// weeks without pnchs between these two dates are added with a duration of zero. The pnchs
+    /// with an invalid date are not in any week.
+    pub fn new(pnchs: &pnch::Pnchs, week_start: time::Weekday, empty_weeks: Option<(time::Date, time::Date)>, duration_format: time::DurationFormat) -> Self {
+        let mut weeks = pnchs
+            .group_by(|pnch| pnch.date.start_of_week(week_start))
+            .into_iter()
+            .filter_map(|group| {
+                let mut total = WeekTotal::new(group.key.clone()?)?;
+                total.duration = group.duration();
+                total.active_days = active_days(&group.pnchs);
+                Some(total)
+            })
+            .collect::<Vec<_>>();
+        if let Some((from, to)) = empty_weeks {
+            let firsts = std::iter::successors(
+                from.start_of_week(week_start),
+                |first| first.add_days(7),
+            );
+            for first in firsts.take_while(|first| *first <= to) {
+                if weeks.iter().all(|total| total.first != first) {
+                    weeks.extend(WeekTotal::new(first));
+                }
+            }
+            weeks.sort_by(|a, b| a.first.cmp(&b.first));
+        }
+        Self { weeks, duration_format }
+    }
+
+    /// Format the report as csv, with a first line naming the columns.
+    pub fn to_csv(&self, delimiter: pnch::Delimiter) -> String {
+        let d = delimiter.as_char();
+        let mut csv = Self::CSV_COLUMNS.join(&d.to_string());
+        csv.push('\n');
+        for total in self.weeks.iter() {
+            let (year, week) = total.week;
+            csv.push_str(&format!(
+                "{year}-W{week:02}{d}{}{d}{}{d}{}{d}{}{d}{}\n",
+                total.first, total.last, total.duration.minutes, total.active_days, total.average().minutes,
+            ));
+        }
+        csv
+    }
+}
+
+impl std::fmt::Display for WeekReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.weeks.is_empty() {
+            return writeln!(f, "No pnchs were found.");
+        }
+        for total in self.weeks.iter() {
+            let (year, week) = total.week;
+            writeln!(f, "{year}-W{week:02} {} to {} {:<22} {} active days, {} per day",
+                total.first, total.last, total.duration.format_long(self.duration_format), total.active_days,
+                total.average().format(self.duration_format))?;
+        }
+        let total = self.weeks.iter().map(|total| total.duration).sum::<time::Duration>();
+        writeln!(f, "\ntotal: {}", total.format_long(self.duration_format))
+    }
+}
+
+/// The total of a calendar month.
+pub struct MonthTotal {
+    /// The first day of the month.
+    pub month: time::Date,
+    pub duration: time::Duration,
+    /// The number of days of the month with pnchs.
+    pub active_days: usize,
+    /// The tag with the longest total in the month, with its total. Pnchs without a tag have no
+    /// name.
+    pub top_tag: Option<(Option<String>, time::Duration)>,
+}
+
+impl MonthTotal {
+    fn new(month: time::Date, pnchs: &[&pnch::Pnch]) -> Self {
+        let active_days = active_days(pnchs);
+        let pnchs = pnch::Pnchs(pnchs.iter().map(|pnch| (*pnch).clone()).collect());
+        Self {
+            month,
+            duration: pnchs.duration(),
+            active_days,
+            top_tag: pnchs.duration_by_tag().into_iter().next(),
+        }
+    }
+
+    /// The name of the top tag, `---` for the pnchs without a tag.
+    fn top_tag_name(&self) -> &str {
+        match &self.top_tag {
+            Some((Some(tag), _)) => tag,
+            Some((None, _)) => "---",
+            None => "",
+        }
+    }
+}
+
+/// The total duration of each calendar month.
+pub struct MonthReport {
+    /// The total of each month, sorted.
+    pub months: Vec<MonthTotal>,
+    /// The total of every month together, its month is `Date::min()`.
+    pub total: MonthTotal,
+    pub duration_format: time::DurationFormat,
+}
+
+impl MonthReport {
+    const CSV_COLUMNS: [&'static str; 5] = ["month", "duration_minutes", "active_days", "top_tag", "top_tag_minutes"];
+
+    /// The report of the months with pnchs. With `empty_months`, the months without pnchs
+    /// between these two dates are added with a duration of zero.
+    pub fn new(pnchs: &pnch::Pnchs, empty_months: Option<(time::Date, time::Date)>, duration_format: time::DurationFormat) -> Self {
+        let mut months = pnchs
+            .group_by(|pnch| pnch.date.start_of_month())
+            .into_iter()
+            .map(|group| MonthTotal::new(group.key, &group.pnchs))
+            .collect::<Vec<_>>();
+        if let Some((from, to)) = empty_months {
+            // 31 days after the first day of a month is always in the next month.
+            let firsts = std::iter::successors(
+                from.month().map(|_| from.start_of_month()),
+                |first| first.add_days(31).map(|day| day.start_of_month()),
+            );
+            for first in firsts.take_while(|first| *first <= to) {
+                if months.iter().all(|total| total.month != first) {
+                    months.push(MonthTotal::new(first, &[]));
+                }
+            }
+            months.sort_by(|a, b| a.month.cmp(&b.month));
+        }
+        Self {
+            months,
+            total: MonthTotal::new(time::Date::min(), &pnchs.0.iter().collect::<Vec<_>>()),
+            duration_format,
+        }
+    }
+
+    /// Format the report as csv, with a first line naming the columns and a last line with the
+    /// total of every month.
+    pub fn to_csv(&self, delimiter: pnch::Delimiter) -> String {
+        let d = delimiter.as_char();
+        let mut csv = Self::CSV_COLUMNS.join(&d.to_string());
+        csv.push('\n');
+        let rows = self.months
+            .iter()
+            .map(|total| (format!("{:04}-{:02}", total.month.year(), total.month.month().map(u8::from).unwrap_or(0)), total))
+            .chain(std::iter::once((String::from("total"), &self.total)));
+        for (month, total) in rows {
+            let top_minutes = total.top_tag.as_ref().map(|(_, duration)| duration.minutes.to_string());
+            csv.push_str(&format!(
+                "{month}{d}{}{d}{}{d}{}{d}{}\n",
+                total.duration.minutes,
+                total.active_days,
+                pnch::csv_escape(total.top_tag_name(), d),
+                top_minutes.unwrap_or_default(),
+            ));
+        }
+        csv
+    }
+}
+
+impl std::fmt::Display for MonthReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.months.is_empty() {
+            return writeln!(f, "No pnchs were found.");
+        }
+        let row = |f: &mut std::fmt::Formatter<'_>, name: String, total: &MonthTotal| {
+            write!(f, "{name:<14} {:<22} {:>3} active days", total.duration.format_long(self.duration_format), total.active_days)?;
+            if let Some((_, duration)) = &total.top_tag {
+                write!(f, ", top tag {} ({})", total.top_tag_name(), duration.format(self.duration_format))?;
+            }
+            writeln!(f)
+        };
+        for total in self.months.iter() {
+            let name = match total.month.month() {
+                Some(month) => format!("{month} {}", total.month.year()),
+                None => total.month.to_string(),
+            };
+            row(f, name, total)?;
+        }
+        writeln!(f)?;
+        row(f, String::from("total"), &self.total)
+    }
+}
@@ -1,4 +1,5 @@
-use crate::{storage, error::GlobalError};
+use crate::{storage, time, pnch, format, error::GlobalError};
+use colored::*;
 
 /// A tag is like a category. pnchs are grouped by tags.
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -21,12 +22,48 @@ impl Tag {
     /// total size of each tag in bytes
     const SIZE: usize = Self::ID_SIZE + Self::TAG_SIZE;
 
+    /// Check that `name` fits in the tags database.
+    pub fn check_name(name: &str) -> Result<(), GlobalError> {
+        if name.len() > Self::TAG_SIZE {
+            return Err(GlobalError::tag_too_long(name, Self::TAG_SIZE));
+        }
+        Ok(())
+    }
+
+    /// The colors given to the tags, they can be told apart on a dark or a light terminal.
+    const PALETTE: [colored::Color; 10] = [
+        colored::Color::Red,
+        colored::Color::Green,
+        colored::Color::Yellow,
+        colored::Color::Blue,
+        colored::Color::Magenta,
+        colored::Color::Cyan,
+        colored::Color::BrightRed,
+        colored::Color::BrightGreen,
+        colored::Color::BrightBlue,
+        colored::Color::BrightMagenta,
+    ];
+
     pub fn none() -> Self {
         Self {
             id: u32::MAX,
             tag: String::new()
         }
     }
+
+    /// The color of the tag, always the same for a name. The name is hashed with FNV-1a, the
+    /// hasher of the standard library may change between versions.
+    pub fn color(&self) -> colored::Color {
+        let hash = self.tag
+            .bytes()
+            .fold(0xcbf29ce484222325u64, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3));
+        Self::PALETTE[(hash % Self::PALETTE.len() as u64) as usize]
+    }
+
+    /// Color a text with the color of the tag.
+    pub fn paint(&self, text: &str) -> colored::ColoredString {
+        text.color(self.color())
+    }
 }
 
 impl std::convert::TryFrom<&[u8]> for Tag {
@@ -67,31 +104,62 @@ impl std::fmt::Display for Tag {
     }
 }
 
+/// How much a tag is used by the pnchs.
+pub struct Usage<'a> {
+    pub tag: &'a Tag,
+    /// Number of pnchs with this tag.
+    pub count: usize,
+    /// Total duration of the pnchs with this tag.
+    pub duration: time::Duration,
+    /// Date of the latest pnch with this tag.
+    pub last_used: Option<time::Date>,
+}
+
+impl std::fmt::Display for Usage<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "  #{} > {} {} entries ({})", self.tag.id, self.tag, self.count, self.duration)?;
+        match &self.last_used {
+            Some(date) => write!(f, ", last used on {date}"),
+            None => write!(f, ", never used"),
+        }
+    }
+}
+
 /// A group of tags
 pub struct Tags(Vec<Tag>);
 
 impl Tags {
-    const TAGS_FILE_NAME: &'static str = "tags.db";
+    pub const TAGS_FILE_NAME: &'static str = "tags.db";
+    /// The format of the tags database.
+    pub const FORMAT: format::Format = format::Format::new(*b"PTAG", 1, Tag::SIZE, "tags", format::Format::same_records);
 
-    pub fn load() -> Result<Self, GlobalError> {
-        let buffer = storage::load(Self::TAGS_FILE_NAME)?;
+    pub fn load(storage: &storage::Storage) -> Result<Self, GlobalError> {
+        let buffer = Self::FORMAT.load(storage, Self::TAGS_FILE_NAME)?;
         Ok(Self(buffer
             .chunks_exact(Tag::SIZE)
-            .into_iter()
-            .map(|chunk| Tag::try_from(chunk))
+            .map(Tag::try_from)
             .collect::<Result<Vec<Tag>, GlobalError>>()?))
     }
 
-    pub fn get_or_insert(&mut self, tag_name: String) -> Tag {
+    /// Number of bytes at the end of the database that do not form a complete tag. They are
+    /// ignored when loading and dropped on the next save.
+    pub fn trailing_bytes(storage: &storage::Storage) -> Result<usize, GlobalError> {
+        Ok(Self::FORMAT.load(storage, Self::TAGS_FILE_NAME)?.len() % Tag::SIZE)
+    }
+
+    /// The tag named `tag_name`, created when it does not exist. A name too long to be saved is
+    /// refused.
+    pub fn get_or_insert(&mut self, tag_name: String) -> Result<Tag, GlobalError> {
         match self.0.iter().find(|tag| tag.tag == tag_name) {
-            Some(tag) => tag.clone(),
+            Some(tag) => Ok(tag.clone()),
             _ => {
+                Tag::check_name(&tag_name)?;
                 let tag = Tag {
                     id: self.0.len() as u32,
                     tag: tag_name
                 };
                 self.0.push(tag.clone());
-                tag
+                Ok(tag)
             }
         }
     }
@@ -100,15 +168,181 @@ impl Tags {
         self.0.get(id as usize).cloned()
     }
 
-    pub fn save(&self) -> Result<(), GlobalError> {
-        let path = storage::build_path(Self::TAGS_FILE_NAME)?;
+    pub fn find(&self, tag_name: &str) -> Option<&Tag> {
+        self.0.iter().find(|tag| tag.tag == tag_name)
+    }
+
+    /// The tags starting with `prefix`, ignoring the case.
+    pub fn starting_with(&self, prefix: &str) -> Vec<&Tag> {
+        let prefix = prefix.to_lowercase();
+        self.0
+            .iter()
+            .filter(|tag| tag.tag.to_lowercase().starts_with(&prefix))
+            .collect()
+    }
+
+    /// The tags with a name close to `name`, from the closest one, to suggest when a tag does not
+    /// exist. At most `count` tags are returned.
+    pub fn closest(&self, name: &str, count: usize) -> Vec<&Tag> {
+        let name = name.to_lowercase();
+        let max_distance = std::cmp::max(name.chars().count() / 3, 2);
+        let mut tags = self.0
+            .iter()
+            .map(|tag| (edit_distance(&name, &tag.tag.to_lowercase()), tag))
+            .filter(|(distance, _)| *distance <= max_distance)
+            .collect::<Vec<_>>();
+        tags.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.tag.cmp(&b.1.tag)));
+        tags.into_iter().take(count).map(|(_, tag)| tag).collect()
+    }
+
+    /// Rename a tag. Because pnchs reference tags by id, every pnch with this tag is renamed.
+    /// Returns `false` when the tag already has the new name, nothing is changed then.
+    pub fn rename(&mut self, old: &str, new: &str) -> Result<bool, GlobalError> {
+        if new.trim().is_empty() {
+            return Err(GlobalError::tag_empty());
+        }
+        Tag::check_name(new)?;
+        let Some(idx) = self.0.iter().position(|tag| tag.tag == old) else {
+            return Err(GlobalError::tag_not_exists(old));
+        };
+        if old == new {
+            return Ok(false);
+        }
+        if self.find(new).is_some() {
+            return Err(GlobalError::tag_already_exists(new));
+        }
+        self.0[idx].tag = new.to_owned();
+        Ok(true)
+    }
+
+    /// Remove a tag and update every pnch that referenced it and return the number of pnchs that
+    /// were updated.
+    ///
+    /// The pnchs of the removed tag are moved to the `reassign` tag or are left without a tag.
+    /// Since the id of a tag is its index, the tags after the removed one are shifted and the
+    /// pnchs are updated accordingly. Every group of pnchs referencing the tags, like the
+    /// archives, must be updated at once. The tags and the pnchs must be saved afterwards.
+    pub fn remove(&mut self, tag_name: &str, reassign: Option<&str>, pnchs: &mut [&mut pnch::Pnchs]) -> Result<usize, GlobalError> {
+        let removed = self.find(tag_name)
+            .cloned()
+            .ok_or_else(|| GlobalError::tag_not_exists(tag_name))?;
+        if let Some(reassign) = reassign {
+            if reassign == tag_name {
+                return Err(GlobalError::tag_same(tag_name));
+            }
+            if self.find(reassign).is_none() {
+                return Err(GlobalError::tag_not_exists(reassign));
+            }
+        }
+        self.0.remove(removed.id as usize);
+        self.0
+            .iter_mut()
+            .enumerate()
+            .for_each(|(id, tag)| tag.id = id as u32);
+
+        let replacement = reassign.and_then(|reassign| self.find(reassign)).cloned();
+        let mut count = 0;
+        for pnch in pnchs.iter_mut().flat_map(|pnchs| pnchs.0.iter_mut()) {
+            if pnch.tag.as_ref() == Some(&removed) {
+                pnch.tag = replacement.clone();
+                count += 1;
+            } else if let Some(tag) = &pnch.tag {
+                pnch.tag = self.find(&tag.tag).cloned();
+            }
+        }
+        Ok(count)
+    }
+
+    /// Cross reference the tags with the pnchs to know how much each tag is used.
+    ///
+    /// The usages are sorted from the most used tag to the least used one.
+    pub fn usage(&self, pnchs: &pnch::Pnchs) -> Vec<Usage<'_>> {
+        let groups = pnchs.group_by(|pnch| pnch.tag.as_ref().map(|tag| tag.id));
+        let mut usages = self.0
+            .iter()
+            .map(|tag| match groups.iter().find(|group| group.key == Some(tag.id)) {
+                Some(group) => Usage {
+                    tag,
+                    count: group.pnchs.len(),
+                    duration: group.duration(),
+                    last_used: group.pnchs.iter().map(|pnch| pnch.date.clone()).max(),
+                },
+                None => Usage { tag, count: 0, duration: time::Duration::zero(), last_used: None },
+            })
+            .collect::<Vec<_>>();
+        usages.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.tag.tag.cmp(&b.tag.tag)));
+        usages
+    }
+
+    /// The tags used by the pnchs, from the most recently used one.
+    pub fn recent(&self, pnchs: &pnch::Pnchs) -> Vec<&Tag> {
+        let mut usages = self.usage(pnchs);
+        usages.retain(|usage| usage.last_used.is_some());
+        usages.sort_by(|a, b| b.last_used.cmp(&a.last_used));
+        usages.into_iter().map(|usage| usage.tag).collect()
+    }
+
+    pub fn save(&self, storage: &storage::Storage) -> Result<(), GlobalError> {
         let content = self.0
             .iter()
-            .map(|tag| Vec::from(tag))
-            .flatten()
+            .flat_map(Vec::from)
             .collect::<Vec<u8>>();
-        std::fs::write(path, content)
-            .map_err(|_| GlobalError::fs("save", "tags"))?;
-        Ok(())
+        storage.save(Self::TAGS_FILE_NAME, &Self::FORMAT.with_header(&content), "tags")
+    }
+}
+
+/// The number of characters to insert, remove or replace to change `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let replace = previous[j] + usize::from(a != *b);
+            current.push(replace.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(names: &[&str]) -> Tags {
+        let mut tags = Tags(Vec::new());
+        for name in names {
+            tags.get_or_insert(name.to_string()).unwrap();
+        }
+        tags
+    }
+
+    #[test]
+    fn rename_to_the_same_name_changes_nothing() {
+        let mut tags = tags(&["PROJ-Alpah", "OTHER"]);
+        assert!(!tags.rename("PROJ-Alpah", "PROJ-Alpah").unwrap());
+        assert!(tags.rename("PROJ-Alpah", "PROJ-Alpha").unwrap());
+        assert_eq!(tags.find("PROJ-Alpha").map(|tag| tag.id), Some(0));
+        assert!(tags.find("PROJ-Alpah").is_none());
+    }
+
+    #[test]
+    fn rename_refuses_invalid_names() {
+        let mut tags = tags(&["PROJ-Alpah", "OTHER"]);
+        for new in ["", "   ", "OTHER", "a name longer than 24 bytes"] {
+            assert!(tags.rename("PROJ-Alpah", new).is_err(), "{new:?}");
+        }
+        assert!(tags.rename("UNKNOWN", "UNKNOWN").is_err());
+        assert!(tags.find("PROJ-Alpah").is_some());
+    }
+
+    #[test]
+    fn tags_too_long_to_be_saved_are_refused() {
+        let mut tags = tags(&["OTHER"]);
+        assert!(tags.get_or_insert(String::from("ABCDEFGHIJKLMNOPQRSTUVWXYZ0123")).is_err());
+        let longest = "ABCDEFGHIJKLMNOPQRSTUVWX";
+        assert_eq!(tags.get_or_insert(String::from(longest)).unwrap().id, 1);
+        assert_eq!(tags.0.iter().map(Vec::from).map(|record| record.len()).collect::<Vec<_>>(), vec![Tag::SIZE; 2]);
     }
 }
@@ -0,0 +1,128 @@
+use std::str;
+use crate::{pnch, time, error::GlobalError};
+
+/// A line of text printed for each pnch, where the placeholders like `{date}` are replaced by the
+/// fields of the pnch. `{{` and `}}` print a brace.
+#[derive(Debug, Clone)]
+pub struct Template {
+    source: String,
+    pieces: Vec<Piece>,
+}
+
+#[derive(Debug, Clone)]
+enum Piece {
+    Text(String),
+    Field(Field),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Field {
+    Id,
+    Date,
+    In,
+    Out,
+    Tag,
+    Description,
+    Duration,
+    Minutes,
+}
+
+impl Field {
+    const FIELDS: [(&'static str, Field); 8] = [
+        ("id", Self::Id),
+        ("date", Self::Date),
+        ("in", Self::In),
+        ("out", Self::Out),
+        ("tag", Self::Tag),
+        ("description", Self::Description),
+        ("duration", Self::Duration),
+        ("minutes", Self::Minutes),
+    ];
+
+    /// Comma separated list of every placeholder, used in hints.
+    fn names() -> String {
+        Self::FIELDS
+            .iter()
+            .map(|(name, _)| format!("`{{{name}}}`"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// The field of the pnch. The out time and the duration are empty for an open pnch, and the
+    /// lines of the description are joined so every pnch stays on one line.
+    fn render(&self, pnch: &pnch::Pnch, duration_format: time::DurationFormat) -> String {
+        match self {
+            Self::Id => pnch.id.to_string(),
+            Self::Date => pnch.date.to_string(),
+            Self::In => pnch._in.to_string(),
+            Self::Out => pnch.out.map(|out| out.to_string()).unwrap_or_default(),
+            Self::Tag => pnch.tag.as_ref().map(|tag| tag.tag.clone()).unwrap_or_default(),
+            Self::Description => pnch.description
+                .as_deref()
+                .unwrap_or_default()
+                .lines()
+                .collect::<Vec<_>>()
+                .join(" "),
+            Self::Duration => pnch.duration().map(|duration| duration.format(duration_format)).unwrap_or_default(),
+            Self::Minutes => pnch.duration().map(|duration| duration.minutes.to_string()).unwrap_or_default(),
+        }
+    }
+}
+
+impl Template {
+    /// The template with every placeholder replaced by the fields of the pnch.
+    pub fn render(&self, pnch: &pnch::Pnch, duration_format: time::DurationFormat) -> String {
+        self.pieces
+            .iter()
+            .map(|piece| match piece {
+                Piece::Text(text) => text.clone(),
+                Piece::Field(field) => field.render(pnch, duration_format),
+            })
+            .collect()
+    }
+}
+
+impl str::FromStr for Template {
+    type Err = GlobalError;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut pieces = Vec::new();
+        let mut text = String::new();
+        let mut chars = value.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.next_if_eq(&'{').is_some() => text.push('{'),
+                '}' if chars.next_if_eq(&'}').is_some() => text.push('}'),
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(GlobalError::template_unclosed_placeholder(value)),
+                        }
+                    }
+                    let field = Field::FIELDS
+                        .iter()
+                        .find(|(field, _)| *field == name)
+                        .map(|(_, field)| *field)
+                        .ok_or_else(|| GlobalError::template_unknown_placeholder(&name, &Field::names()))?;
+                    if !text.is_empty() {
+                        pieces.push(Piece::Text(std::mem::take(&mut text)));
+                    }
+                    pieces.push(Piece::Field(field));
+                }
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            pieces.push(Piece::Text(text));
+        }
+        Ok(Self { source: value.to_owned(), pieces })
+    }
+}
+
+impl std::fmt::Display for Template {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
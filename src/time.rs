@@ -1,12 +1,16 @@
-use std::{str, default};
+use std::str;
 use crate::error::{self, GlobalError};
 
+pub use ::time::Weekday;
+
 /// A period is used to specify a duration of time in term of days, weeks, months or years.
 ///
 /// A period can be created from a string in the format `[n] period[s]` where n is a number and
 /// period is one of `day[s]`, `week[s]`, `month[s]` or `year[s]`. For example, `3 weeks`, `year`
-/// and `56 months` are all periods.
-#[derive(Debug, Clone)]
+/// and `56 months` are all periods. The compact `<n><unit>` format, with a unit of `d`, `w`, `m`
+/// or `y`, doesn't need to be quoted: `2w` is `2 weeks` and a number alone, like `10`, is a number
+/// of days.
+#[derive(Debug, Clone, PartialEq)]
 pub enum Period {
     Days(u32),
     Weeks(u32),
@@ -17,35 +21,46 @@ pub enum Period {
 impl Period {
     /// Hint on how to format a period as a string.
     const FORMAT_HINT: &'static str =
-        "`n <period>` where `n` is a number and `<period>` is one of `days`, `weeks`, `months` or `years`";
+        "`n <period>` where `n` is a number and `<period>` is one of `days`, `weeks`, `months` or `years`, or `<n><unit>` like `2w` where `<unit>` is one of `d`, `w`, `m` or `y`";
+    /// size of the unit field
+    const UNIT_SIZE: usize = 1;
+    /// size of the count field
+    const COUNT_SIZE: usize = 4;
+    /// size of a period in bytes
+    pub const SIZE: usize = Self::UNIT_SIZE + Self::COUNT_SIZE;
 
-    /// Substract the period to the current date, returning the date n period ago.
-    ///
-    /// For example, a period of 2 weeks ago will return the date from today minus 2 weeks.
-    pub fn to_date_since_today(&self) -> Date {
-        let days = match self {
-            Self::Days(days) => *days,
-            Self::Weeks(weeks) => weeks * 7,
-            Self::Months(months) => months * 30,
-            Self::Years(years) => years * 365,
+    /// Substract the period to `today`. Months and years are substracted on the calendar, keeping
+    /// the same day of the month, or the last day of the month when it is shorter.
+    pub fn to_date_since(&self, today: &Date) -> Date {
+        let date = match self {
+            Self::Days(days) => today.add_days(-(*days as i64)),
+            Self::Weeks(weeks) => today.add_days(-(*weeks as i64) * 7),
+            Self::Months(months) => today.sub_months(*months),
+            Self::Years(years) => today.sub_months(years.saturating_mul(12)),
         };
-        let offset = time::Duration::days(days as i64);
-        let date = time::OffsetDateTime::now_local()
-            .unwrap_or(time::OffsetDateTime::now_utc())
-            .date()
-            .checked_sub(offset);
-        match date {
-            Some(date) => Date::from(date),
-            None => Date::min()
-        }
+        date.unwrap_or(Date::min())
     }
 
-    pub fn as_days(&self) -> u32 {
-        match self {
-            Self::Days(count) => *count,
-            Self::Weeks(count) => *count * 7,
-            Self::Months(count) => *count * 30,
-            Self::Years(count) => *count * 365,
+    /// The unit of the period followed by its count.
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let (unit, count) = match self {
+            Self::Days(count) => (0, count),
+            Self::Weeks(count) => (1, count),
+            Self::Months(count) => (2, count),
+            Self::Years(count) => (3, count),
+        };
+        let mut bytes = [unit; Self::SIZE];
+        bytes[Self::UNIT_SIZE..].copy_from_slice(&count.to_le_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: [u8; Self::SIZE]) -> Self {
+        let count = u32::from_le_bytes(bytes[Self::UNIT_SIZE..].try_into().expect("the count is the rest of the bytes"));
+        match bytes[0] {
+            1 => Self::Weeks(count),
+            2 => Self::Months(count),
+            3 => Self::Years(count),
+            _ => Self::Days(count),
         }
     }
 }
@@ -53,6 +68,24 @@ impl Period {
 impl str::FromStr for Period {
     type Err = GlobalError;
     fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let is_count = |count: &str| !count.is_empty() && count.bytes().all(|c| c.is_ascii_digit());
+        if is_count(value) {
+            return Ok(Self::Days(value.parse().map_err(|_| GlobalError::parse(Self::FORMAT_HINT))?));
+        }
+        if let Some((count_str, unit)) = value.split_at_checked(value.len().saturating_sub(1)) {
+            if is_count(count_str) {
+                let count = count_str
+                    .parse()
+                    .map_err(|_| GlobalError::parse(Self::FORMAT_HINT))?;
+                return match unit {
+                    "d" => Ok(Self::Days(count)),
+                    "w" => Ok(Self::Weeks(count)),
+                    "m" => Ok(Self::Months(count)),
+                    "y" => Ok(Self::Years(count)),
+                    _ => Err(GlobalError::parse(Self::FORMAT_HINT)),
+                };
+            }
+        }
         let (count_str, period_str) = value.split_once(" ").unwrap_or(("1", value));
         let count = count_str
             .parse()
@@ -67,35 +100,337 @@ impl str::FromStr for Period {
     }
 }
 
+impl std::fmt::Display for Period {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (count, period) = match self {
+            Self::Days(count) => (count, "day"),
+            Self::Weeks(count) => (count, "week"),
+            Self::Months(count) => (count, "month"),
+            Self::Years(count) => (count, "year"),
+        };
+        match count {
+            1 => write!(f, "{count} {period}"),
+            _ => write!(f, "{count} {period}s"),
+        }
+    }
+}
+
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Duration {
-    pub minutes: i64
+    pub minutes: i64,
+    /// The seconds under a minute, with the same sign as the minutes. They are only set when the
+    /// times are recorded with seconds, see `Precision`.
+    pub seconds: i64,
 }
 
 impl Duration {
+    /// Hint on how to format a duration as a string.
+    const FORMAT_HINT: &'static str
+        = "`h:mm`, `1h30m`, `2h` or `90m` where `h` are hours and `m` are minutes";
+    const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
     pub fn zero() -> Self {
         Self {
             minutes: 0,
+            seconds: 0,
+        }
+    }
+
+    pub fn from_seconds(seconds: i64) -> Self {
+        Self {
+            minutes: seconds / 60,
+            seconds: seconds % 60,
+        }
+    }
+
+    pub fn as_seconds(self) -> i64 {
+        self.minutes * 60 + self.seconds
+    }
+
+    /// The duration in the `h:mm` format, or `h:mm:ss` when it has seconds, which can be parsed
+    /// back.
+    pub fn to_hh_mm(self) -> String {
+        let sign = if self.as_seconds() < 0 { "-" } else { "" };
+        let mut formatted = format!("{sign}{}:{:02}", self.minutes.abs() / 60, self.minutes.abs() % 60);
+        if self.seconds != 0 {
+            formatted.push_str(&format!(":{:02}", self.seconds.abs()));
+        }
+        formatted
+    }
+
+    /// The duration with its sign, like `+1 hours 30 minutes` or `-30 minutes`.
+    pub fn to_signed_string(self) -> String {
+        match self.as_seconds() {
+            seconds if seconds > 0 => format!("+{self}"),
+            _ => self.to_string(),
+        }
+    }
+
+    /// The duration in `format`, like `7:45`, `7.75` or `7h 45m`.
+    pub fn format(self, format: DurationFormat) -> String {
+        let sign = if self.as_seconds() < 0 { "-" } else { "" };
+        let (hours, minutes, seconds) = (self.minutes.abs() / 60, self.minutes.abs() % 60, self.seconds.abs());
+        match format {
+            DurationFormat::Clock | DurationFormat::Hours => self.to_hh_mm(),
+            DurationFormat::Decimal => {
+                // Hundredths of an hour, rounded to the nearest.
+                let hundredths = (self.as_seconds().abs() + 18) / 36;
+                format!("{sign}{}.{:02}", hundredths / 100, hundredths % 100)
+            }
+            DurationFormat::Verbose => {
+                let mut formatted = match hours {
+                    0 => format!("{sign}{minutes}m"),
+                    _ => format!("{sign}{hours}h {minutes}m"),
+                };
+                if seconds != 0 {
+                    formatted.push_str(&format!(" {seconds}s"));
+                }
+                formatted
+            }
+        }
+    }
+
+    /// The duration in a sentence, like `7 hours 45 minutes` with the `clock` format, or `6d 19:25`
+    /// when it lasts a day or more, and like `Duration::format` with the others.
+    pub fn format_long(self, format: DurationFormat) -> String {
+        match format {
+            DurationFormat::Clock => self.to_days_hh_mm().unwrap_or_else(|| self.to_string()),
+            DurationFormat::Hours => self.to_string(),
+            format => self.format(format),
+        }
+    }
+
+    /// The duration in days, hours and minutes, like `6d 19:25`, when it lasts a day or more.
+    fn to_days_hh_mm(self) -> Option<String> {
+        let seconds = self.as_seconds().abs();
+        if seconds < Self::SECONDS_PER_DAY {
+            return None;
+        }
+        let sign = if self.as_seconds() < 0 { "-" } else { "" };
+        let rest = Self::from_seconds(seconds % Self::SECONDS_PER_DAY);
+        Some(format!("{sign}{}d {}", seconds / Self::SECONDS_PER_DAY, rest.to_hh_mm()))
+    }
+
+    /// Like `Duration::format_long`, with a `+` before the positive durations.
+    pub fn format_signed(self, format: DurationFormat) -> String {
+        match self.as_seconds() {
+            seconds if seconds > 0 => format!("+{}", self.format_long(format)),
+            _ => self.format_long(format),
+        }
+    }
+}
+
+/// How the durations are printed.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum DurationFormat {
+    /// Hours and minutes, like `7:45`. The totals of a day or more are in days, like `6d 19:25`.
+    #[default]
+    Clock,
+    /// Like `Clock`, with the totals in hours even when they last more than a day.
+    Hours,
+    /// Hours with two decimals, like `7.75`.
+    Decimal,
+    /// Hours and minutes with their unit, like `7h 45m`.
+    Verbose,
+}
+
+impl DurationFormat {
+    pub fn to_byte(self) -> u8 {
+        match self {
+            Self::Clock => 0,
+            Self::Decimal => 1,
+            Self::Verbose => 2,
+            Self::Hours => 3,
+        }
+    }
+
+    pub fn from_byte(byte: u8) -> Self {
+        match byte {
+            1 => Self::Decimal,
+            2 => Self::Verbose,
+            3 => Self::Hours,
+            _ => Self::Clock,
+        }
+    }
+}
+
+impl str::FromStr for DurationFormat {
+    type Err = GlobalError;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match &value.to_lowercase()[..] {
+            "clock" => Ok(Self::Clock),
+            "decimal" => Ok(Self::Decimal),
+            "verbose" => Ok(Self::Verbose),
+            "hours" => Ok(Self::Hours),
+            _ => Err(GlobalError::parse("`clock`, `hours`, `decimal` or `verbose`")),
+        }
+    }
+}
+
+impl std::fmt::Display for DurationFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Clock => write!(f, "clock"),
+            Self::Decimal => write!(f, "decimal"),
+            Self::Verbose => write!(f, "verbose"),
+            Self::Hours => write!(f, "hours"),
+        }
+    }
+}
+
+impl str::FromStr for Duration {
+    type Err = GlobalError;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let error = || GlobalError::parse(Self::FORMAT_HINT);
+        // A duration printed with its seconds, like `1:30:15`.
+        if let Some((hours_minutes, seconds_str)) = value.rsplit_once(':').filter(|_| value.matches(':').count() == 2) {
+            let seconds = seconds_str
+                .parse::<u32>()
+                .ok()
+                .filter(|seconds| seconds_str.len() == 2 && *seconds < 60)
+                .ok_or_else(error)?;
+            let duration = Self::from_str(hours_minutes)?;
+            return Ok(Self::from_seconds(duration.as_seconds() + seconds as i64));
+        }
+        let (hours_str, minutes_str) = match value.split_once(':') {
+            Some((hours_str, minutes_str)) if minutes_str.len() == 2 => (hours_str, minutes_str),
+            Some(_) => return Err(error()),
+            None => match value.split_once('h') {
+                Some((hours_str, minutes_str)) => (hours_str, minutes_str.trim_end_matches('m')),
+                None => ("0", value.strip_suffix('m').ok_or_else(error)?),
+            },
+        };
+        let hours = hours_str.parse::<u32>().map_err(|_| error())?;
+        let minutes = match minutes_str {
+            "" => 0,
+            _ => minutes_str.parse::<u32>().map_err(|_| error())?,
+        };
+        if value.contains(':') && minutes >= 60 {
+            return Err(error());
         }
+        Ok(Self {
+            minutes: hours as i64 * 60 + minutes as i64,
+            seconds: 0,
+        })
     }
 }
 
 impl std::ops::Add for Duration {
     type Output = Self;
     fn add(self, rhs: Self) -> Self::Output {
-        Duration {
-            minutes: self.minutes + rhs.minutes
-        }
+        Duration::from_seconds(self.as_seconds() + rhs.as_seconds())
+    }
+}
+
+impl std::ops::Sub for Duration {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self::Output {
+        Duration::from_seconds(self.as_seconds() - rhs.as_seconds())
+    }
+}
+
+impl std::iter::Sum for Duration {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::zero(), |total, duration| total + duration)
     }
 }
+
 impl std::fmt::Display for Duration {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let hours = self.minutes / 60;
+        if self.as_seconds() < 0 {
+            write!(f, "-")?;
+        }
+        let hours = self.minutes.abs() / 60;
         let minutes = self.minutes.abs() % 60;
         if hours != 0 {
-            write!(f, "{hours} hours {minutes} minutes")
+            write!(f, "{hours} hours {minutes} minutes")?;
         } else {
-            write!(f, "{minutes} minutes")
+            write!(f, "{minutes} minutes")?;
+        }
+        if self.seconds != 0 {
+            write!(f, " {} seconds", self.seconds.abs())?;
+        }
+        Ok(())
+    }
+}
+
+/// How the durations of the pnchs are rounded, to a multiple of 5, 15 or 30 minutes.
+///
+/// Only the durations are rounded, the times of the pnchs stay the same. With `Nearest`, a
+/// duration half-way between two multiples is rounded up: to 30 minutes, 15 minutes are rounded
+/// to 30 minutes and to 15 minutes, 7 minutes are rounded to 0 minutes but 8 minutes to 15.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Rounding {
+    #[default]
+    None,
+    /// Round to the nearest multiple of the minutes.
+    Nearest(u8),
+    /// Round up to the next multiple of the minutes.
+    Up(u8),
+}
+
+impl Rounding {
+    /// Hint on how to format a rounding as a string.
+    const FORMAT_HINT: &'static str
+        = "`none`, or `5m`, `15m` or `30m` followed by `nearest` or `up`, like `15m up`";
+    /// The multiples of minutes the durations can be rounded to.
+    const STEPS: [u8; 3] = [5, 15, 30];
+
+    pub fn apply(self, duration: Duration) -> Duration {
+        let (step, half) = match self {
+            Self::None => return duration,
+            Self::Nearest(step) => (step as i64 * 60, step as i64 * 30),
+            Self::Up(step) => (step as i64 * 60, step as i64 * 60 - 1),
+        };
+        Duration::from_seconds((duration.as_seconds() + half).div_euclid(step) * step)
+    }
+
+    pub fn to_bytes(self) -> [u8; 2] {
+        match self {
+            Self::None => [0, 0],
+            Self::Nearest(step) => [step, 0],
+            Self::Up(step) => [step, 1],
+        }
+    }
+
+    pub fn from_bytes(bytes: [u8; 2]) -> Self {
+        match bytes {
+            [step, 0] if Self::STEPS.contains(&step) => Self::Nearest(step),
+            [step, 1] if Self::STEPS.contains(&step) => Self::Up(step),
+            _ => Self::None,
+        }
+    }
+}
+
+impl str::FromStr for Rounding {
+    type Err = GlobalError;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let error = || GlobalError::parse(Self::FORMAT_HINT);
+        let value = value.trim().to_lowercase();
+        if value == "none" {
+            return Ok(Self::None);
+        }
+        let (step_str, policy) = value.split_once(' ').unwrap_or((&value, "nearest"));
+        let step = step_str
+            .strip_suffix('m')
+            .and_then(|step| step.parse::<u8>().ok())
+            .filter(|step| Self::STEPS.contains(step))
+            .ok_or_else(error)?;
+        match policy.trim() {
+            "nearest" => Ok(Self::Nearest(step)),
+            "up" => Ok(Self::Up(step)),
+            _ => Err(error()),
+        }
+    }
+}
+
+impl std::fmt::Display for Rounding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::Nearest(step) => write!(f, "{step}m nearest"),
+            Self::Up(step) => write!(f, "{step}m up"),
         }
     }
 }
@@ -115,7 +450,9 @@ impl Date {
     pub const SIZE: usize = Self::YEAR_SIZE + Self::MONTH_SIZE + Self::DAY_SIZE;
     /// Hint on how to format a date as a string.
     const FORMAT_HINT: &'static str
-        = "`dd-mm-yyyy` where `dd` are days, `mm` are months and `yyyy` are years";
+        = "`yyyy-mm-dd` or `dd-mm-yyyy` where `yyyy` are years, `mm` are months and `dd` are days, separated by `-`, `/` or `.`, or `yyyymmdd`";
+    /// The characters that can separate the parts of a date.
+    const SEPARATORS: [char; 3] = ['-', '/', '.'];
 
     /// Minimum valid date
     pub fn min() -> Self {
@@ -135,12 +472,35 @@ impl Date {
         }
     }
 
-    /// Returns today's date
-    pub fn today() -> Self {
-        let today = time::OffsetDateTime::now_local()
-            .unwrap_or(time::OffsetDateTime::now_utc())
-            .date();
-        Self::from(today)
+    pub fn year(&self) -> u16 {
+        self.year
+    }
+
+    /// The month of the date, `None` when the date has no valid month.
+    pub fn month(&self) -> Option<time::Month> {
+        time::Month::try_from(self.month).ok()
+    }
+
+    /// The date in the `yyyymmdd` format of iCalendar.
+    pub fn to_ical(&self) -> String {
+        format!("{:04}{:02}{:02}", self.year, self.month, self.day)
+    }
+
+    /// The moment of `time` on this date, taken with the UTC offset `offset`, in UTC in the
+    /// `yyyymmddThhmmssZ` format of iCalendar. A time on the next day is on the day after this
+    /// date. `None` is returned when the date does not exist in the calendar.
+    pub fn to_ical_utc(&self, time: Time, offset: Offset) -> Option<String> {
+        let date = match time.is_next_day() {
+            true => self.next()?,
+            false => self.clone(),
+        };
+        let time = time.within_day();
+        let local = time::PrimitiveDateTime::new(
+            date.to_calendar_date()?,
+            time::Time::from_hms(time.hours, time.minutes, time.seconds).ok()?,
+        );
+        let offset = time::UtcOffset::from_whole_seconds(offset.minutes as i32 * 60).ok()?;
+        Some(ical_utc(local.assume_offset(offset)))
     }
 
     pub fn to_le_bytes(&self) -> [u8; Self::SIZE] {
@@ -148,6 +508,121 @@ impl Date {
         [year_bytes[0], year_bytes[1], self.month, self.day]
     }
 
+    /// Convert the date to a `time::Date`. Returns `None` when the date does not exist in the
+    /// calendar, which is the case for `Date::min()` and `Date::max()`.
+    fn to_calendar_date(&self) -> Option<time::Date> {
+        let month = time::Month::try_from(self.month).ok()?;
+        time::Date::from_calendar_date(self.year as i32, month, self.day).ok()
+    }
+
+    /// The date exists in the calendar.
+    pub fn is_valid(&self) -> bool {
+        self.to_calendar_date().is_some()
+    }
+
+    /// The date, or an error when it does not exist in the calendar. The dates that are parsed or
+    /// decoded go through this check, only `Date::min()` and `Date::max()` are not in the
+    /// calendar.
+    fn checked(self) -> Result<Self, GlobalError> {
+        match self.is_valid() {
+            true => Ok(self),
+            false => Err(GlobalError::date_not_in_calendar(&self.to_string(), Self::FORMAT_HINT)),
+        }
+    }
+
+    /// Check that the date is not after `today`, unless `force` is set.
+    ///
+    /// A date in the future is most likely a typo, and an entry in the future would never be
+    /// listed by the default period.
+    pub fn check_not_future(&self, today: &Date, force: bool) -> Result<(), GlobalError> {
+        if !force && self > today {
+            return Err(GlobalError::date_in_future(self, today));
+        }
+        Ok(())
+    }
+
+    pub fn weekday(&self) -> Option<Weekday> {
+        self.to_calendar_date().map(|date| date.weekday())
+    }
+
+    /// The first 3 letters of the weekday, like `Sat`.
+    pub fn short_weekday(&self) -> Option<String> {
+        self.weekday().map(|weekday| weekday.to_string()[..3].to_owned())
+    }
+
+    /// The ISO 8601 year and week number of this date. The first days of January can be in the
+    /// last week of the previous year, and the last days of December in the first week of the next
+    /// year.
+    pub fn iso_week(&self) -> Option<(i32, u8)> {
+        let (year, week, _) = self.to_calendar_date()?.to_iso_week_date();
+        Some((year, week))
+    }
+
+    /// Returns the day after this date, with the month and year rollover.
+    pub fn next(&self) -> Option<Date> {
+        self.to_calendar_date()?.next_day().map(Date::from)
+    }
+
+    pub fn previous(&self) -> Option<Date> {
+        self.to_calendar_date()?.previous_day().map(Date::from)
+    }
+
+    /// Returns the date `days` days after this date, or before it when `days` is negative.
+    pub fn add_days(&self, days: i64) -> Option<Date> {
+        self.to_calendar_date()?
+            .checked_add(time::Duration::days(days))
+            .map(Date::from)
+    }
+
+    /// Returns the date `months` months before this date, on the same day of the month or on the
+    /// last day of the month when it is shorter. `None` is returned before the year 0.
+    pub fn sub_months(&self, months: u32) -> Option<Date> {
+        let months = self.year as i64 * 12 + self.month as i64 - 1 - months as i64;
+        if months < 0 {
+            return None;
+        }
+        let year = (months / 12) as u16;
+        let month = time::Month::try_from((months % 12) as u8 + 1).ok()?;
+        let day = std::cmp::min(self.day, time::util::days_in_year_month(year as i32, month));
+        Some(Self { year, month: month.into(), day })
+    }
+
+    /// Returns the first day of the week of this date, for weeks starting on `start`.
+    pub fn start_of_week(&self, start: Weekday) -> Option<Date> {
+        let days = self.weekday()?.number_days_from_monday() as i64
+            - start.number_days_from_monday() as i64;
+        self.add_days(-days.rem_euclid(7))
+    }
+
+    /// Returns the first day of the month of this date.
+    pub fn start_of_month(&self) -> Date {
+        Self { day: 1, ..self.clone() }
+    }
+
+    /// Iterate over every date from `from` to `to`, both included.
+    ///
+    /// Dates that are not in the calendar, like `Date::min()`, do not have a next day so nothing is
+    /// iterated when `from` is one of them.
+    pub fn range(from: &Date, to: &Date) -> impl Iterator<Item = Date> {
+        let to = to.clone();
+        std::iter::successors(from.to_calendar_date().map(Date::from), Date::next)
+            .take_while(move |date| *date <= to)
+    }
+
+    /// Describe the date relatively to `today` as `Today`, `Yesterday` or the name of the weekday.
+    ///
+    /// Only dates from the last 6 days are described, a date from exactly a week ago would have
+    /// the same weekday name as today. `None` is returned for any other date.
+    pub fn relative_to(&self, today: &Date) -> Option<String> {
+        let date = self.to_calendar_date()?;
+        let days = (today.to_calendar_date()? - date).whole_days();
+        match days {
+            0 => Some(String::from("Today")),
+            1 => Some(String::from("Yesterday")),
+            2..=6 => Some(date.weekday().to_string()),
+            _ => None,
+        }
+    }
 }
 
 impl From<time::Date> for Date {
@@ -174,11 +649,11 @@ impl std::convert::TryFrom<&[u8]> for Date {
         let year_bytes = buffer[..2]
             .try_into()
             .expect("buffer len was checked previously");
-        Ok(Self {
+        Self {
             year: u16::from_le_bytes(year_bytes),
             month: buffer[2],
             day: buffer[3],
-        })
+        }.checked()
     }
 }
 
@@ -192,127 +667,1213 @@ impl str::FromStr for Date {
     type Err = GlobalError;
     fn from_str(value: &str) -> Result<Self, Self::Err> {
         let error = GlobalError::parse( Self::FORMAT_HINT);
-        let (year_str, month_and_day_str) = value.split_once("-")
-            .ok_or_else(|| error.clone())?;
-        let (month_str, day_str) = month_and_day_str.split_once("-")
-            .ok_or_else(|| error.clone())?;
+        // The compact `yyyymmdd` format, without separators.
+        if value.len() == 8 && value.bytes().all(|c| c.is_ascii_digit()) {
+            let (year_str, rest) = value.split_at(4);
+            let (month_str, day_str) = rest.split_at(2);
+            return Self {
+                year: year_str.parse::<u16>().map_err(|_| error.clone())?,
+                month: month_str.parse::<u8>().map_err(|_| error.clone())?,
+                day: day_str.parse::<u8>().map_err(|_| error.clone())?,
+            }.checked();
+        }
+        // Every part is separated by the same separator, `2024-05/13` is refused.
+        let Some(separator) = value.chars().find(|c| Self::SEPARATORS.contains(c)) else {
+            return Err(error);
+        };
+        let parts = value.split(separator).collect::<Vec<_>>();
+        let [first, month_str, last] = parts[..] else {
+            return Err(error);
+        };
+        // The year, with its 4 digits, is either first or last.
+        let (year_str, day_str) = match (first.len(), last.len()) {
+            (4, 4) => return Err(GlobalError::date_ambiguous(value, Self::FORMAT_HINT)),
+            (4, _) => (first, last),
+            (_, 4) => (last, first),
+            _ => return Err(GlobalError::date_ambiguous(value, Self::FORMAT_HINT)),
+        };
 
-        Ok(Self {
+        Self {
             year: year_str.parse::<u16>().map_err(|_| error.clone())?,
             month: month_str.parse::<u8>().map_err(|_| error.clone())?,
             day: day_str.parse::<u8>().map_err(|_| error.clone())?,
-        })
+        }.checked()
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-pub struct Time {
-    hours: u8,
-    minutes: u8,
+/// A calendar month of a year, created from a string in the `yyyy-mm` format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct YearMonth {
+    year: u16,
+    month: time::Month,
 }
 
-impl Time {
-    const HOURS_SIZE: usize = 1;
-    const MINUTES_SIZE: usize = 1;
-    /// Number of bytes to represent time when encoded
-    pub const SIZE: usize = Self::HOURS_SIZE + Self::MINUTES_SIZE;
-    /// How a time which is not saved is represented when encoded
-    pub const NONE_DATE: [u8; 2] = [0xFF, 0xFF];
-    /// Hint how to format time as a string.
-    pub const FORMAT_HINT: &'static str
-        = "`hh:mm` where `hh` represents the hours and `mm` represents the minutes";
+impl YearMonth {
+    /// Hint on how to format a month as a string.
+    const FORMAT_HINT: &'static str = "`yyyy-mm` where `yyyy` are years and `mm` are months";
 
-    pub fn now() -> Self {
-        let (hours, minutes, _) = time::OffsetDateTime::now_local()
-            .unwrap_or(time::OffsetDateTime::now_utc())
-            .time()
-            .as_hms();
-        Self {
-            hours,
-            minutes
-        }
+    pub fn first_day(&self) -> Date {
+        Date { year: self.year, month: self.month.into(), day: 1 }
     }
 
-    /// Returns a time instance, which when saved to a file it represent the abscence of time.
-    ///
-    /// When both `hours` and `minutes` are at the maximum value (255), it means the time does not
-    /// exists.
-    pub fn none() -> Self {
-        Self {
-            hours: u8::MAX,
-            minutes: u8::MAX,
-        }
+    /// The last day of the month, the 29th of February on leap years.
+    pub fn last_day(&self) -> Date {
+        let day = time::util::days_in_year_month(self.year as i32, self.month);
+        Date { year: self.year, month: self.month.into(), day }
     }
+}
 
-    pub fn to_le_bytes(&self) -> [u8; Self::SIZE] {
-        [self.hours, self.minutes]
+impl str::FromStr for YearMonth {
+    type Err = GlobalError;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let error = || GlobalError::parse(Self::FORMAT_HINT);
+        let (year_str, month_str) = value.split_once('-').ok_or_else(error)?;
+        let month = month_str.parse::<u8>().map_err(|_| error())?;
+        Ok(Self {
+            year: year_str.parse::<u16>().map_err(|_| error())?,
+            month: time::Month::try_from(month).map_err(|_| error())?,
+        })
     }
 }
 
-impl default::Default for Time {
-    fn default() -> Self {
-        Self::now()
-    }
+/// A date given on the command line, relative to today or not.
+///
+/// The relative dates are `today`, `yesterday`, `tomorrow`, a weekday like `monday`, which is the
+/// last monday and is today on a monday, `last monday`, which is the last monday before today,
+/// and `3 days ago`. Anything else is parsed as a `Date`.
+#[derive(Debug, Clone)]
+pub enum RelativeDate {
+    Today,
+    Yesterday,
+    Tomorrow,
+    /// The last day with this weekday, today included.
+    Weekday(Weekday),
+    /// The last day with this weekday, before today.
+    LastWeekday(Weekday),
+    DaysAgo(u32),
+    Date(Date),
 }
 
-impl std::fmt::Display for Time {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}:{:02}", self.hours, self.minutes)
+impl RelativeDate {
+    /// Hint on how to format a relative date as a string.
+    const FORMAT_HINT: &'static str = concat!(
+        "`yyyy-mm-dd` or `dd-mm-yyyy` where `yyyy` are years, `mm` are months and `dd` are days, ",
+        "separated by `-`, `/` or `.`, `yyyymmdd`, `today`, `yesterday`, `tomorrow`, a weekday like `monday` for the last monday (today on a ",
+        "monday), `last monday` for the last monday before today or `3 days ago`",
+    );
+
+    /// The date, relative to `today`, or an error when it is out of the calendar.
+    pub fn to_date(&self, today: &Date) -> Result<Date, GlobalError> {
+        self.resolve(today).ok_or_else(|| GlobalError::parse(Self::FORMAT_HINT))
     }
-}
 
-impl std::convert::TryFrom<&[u8]> for Time {
-    type Error = GlobalError;
-    fn try_from(buffer: &[u8]) -> Result<Self, Self::Error> {
-        if buffer.len() != Self::SIZE {
-            return Err(GlobalError::wrong_byte_len("time", buffer.len(), Self::SIZE))
+    /// The date, relative to `today`.
+    pub fn resolve(&self, today: &Date) -> Option<Date> {
+        match self {
+            Self::Today => Some(today.clone()),
+            Self::Yesterday => today.previous(),
+            Self::Tomorrow => today.next(),
+            Self::Weekday(weekday) => today.start_of_week(*weekday),
+            Self::LastWeekday(weekday) => today.previous()?.start_of_week(*weekday),
+            Self::DaysAgo(days) => today.add_days(-(*days as i64)),
+            Self::Date(date) => Some(date.clone()),
         }
-        Ok(Self {
-            hours: buffer[0],
-            minutes: buffer[1],
-        })
     }
 }
 
-impl str::FromStr for Time {
-    type Err = error::GlobalError;
-
+impl str::FromStr for RelativeDate {
+    type Err = GlobalError;
     fn from_str(value: &str) -> Result<Self, Self::Err> {
-        if value.len() < 4 {
-            return Err(GlobalError::parse(Time::FORMAT_HINT));
+        let lowercase = value.to_lowercase();
+        let words = lowercase.split_whitespace().collect::<Vec<_>>();
+        let relative = match words[..] {
+            ["today"] => Some(Self::Today),
+            ["yesterday"] => Some(Self::Yesterday),
+            ["tomorrow"] => Some(Self::Tomorrow),
+            [weekday] => find_weekday(weekday).map(Self::Weekday),
+            ["last", weekday] => find_weekday(weekday).map(Self::LastWeekday),
+            [days, "day" | "days", "ago"] => days.parse::<u32>().ok().map(Self::DaysAgo),
+            _ => None,
+        };
+        match relative {
+            Some(relative) => Ok(relative),
+            None => value
+                .parse::<Date>()
+                .map(Self::Date)
+                // Something that looks like a date keeps the error of the date.
+                .map_err(|err| match value.split(Date::SEPARATORS).count() {
+                    3 => err,
+                    _ if value.len() == 8 && value.bytes().all(|c| c.is_ascii_digit()) => err,
+                    _ => GlobalError::parse(Self::FORMAT_HINT),
+                }),
         }
-        let mut offset = 0;
-        if value.to_lowercase().ends_with("pm") {
-            offset = 12;
+    }
+}
+
+/// The value of `--since`, either a date or a period before today, so `--since "3 weeks"` is the
+/// same as `--last "3 weeks"`.
+#[derive(Debug, Clone)]
+pub enum SinceArg {
+    Date(RelativeDate),
+    Period(Period),
+}
+
+impl SinceArg {
+    /// Hint on how to format a date or a period as a string.
+    const FORMAT_HINT: &'static str = concat!(
+        "a date like `yyyy-mm-dd`, `dd-mm-yyyy`, `yesterday`, `last monday` or `3 days ago`, ",
+        "or a period like `3 weeks` or `2w`",
+    );
+
+    /// The first day matched, the date itself or the date the period before `today`.
+    pub fn to_date_since(&self, today: &Date) -> Result<Date, GlobalError> {
+        match self {
+            Self::Date(date) => date.to_date(today),
+            Self::Period(period) => Ok(period.to_date_since(today)),
         }
-        let striped = if value.len() <= 4 {
-            value
-        } else {
-            value.split_at(5).0
-        };
-        let (hours_str, minutes_str) = striped.split_once(":")
-            .ok_or_else(|| GlobalError::parse(Time::FORMAT_HINT))?;
-        let mut hours = offset + hours_str.parse::<u8>()
-            .map_err(|_| GlobalError::parse(Time::FORMAT_HINT))?;
-        if hours > 23 {
-            hours -= 12;
-        }
-        let minutes = minutes_str.parse::<u8>()
-            .map_err(|_| GlobalError::parse(Time::FORMAT_HINT))?;
-        Ok(Self {
-            hours,
-            minutes
-        })
     }
 }
 
-impl std::ops::Sub for Time {
-    type Output = Duration;
-    fn sub(self, rhs: Self) -> Self::Output {
-        let self_minutes = self.hours as i64 * 60 + self.minutes as i64;
-        let other_minutes = rhs.hours as i64 * 60 + rhs.minutes as i64;
-        Duration {
-            minutes: self_minutes - other_minutes
+impl str::FromStr for SinceArg {
+    type Err = GlobalError;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        value
+            .parse::<RelativeDate>()
+            .map(Self::Date)
+            .or_else(|_| value.parse::<Period>().map(Self::Period))
+            .map_err(|_| GlobalError::parse(Self::FORMAT_HINT))
+    }
+}
+
+/// The dates from `from` to `to` (both included) that are one of `weekdays`, are not in
+/// `days_off` and are not `tracked`.
+pub fn untracked_days(from: &Date, to: &Date, weekdays: Weekdays, days_off: &DaysOff, tracked: impl Fn(&Date) -> bool) -> Vec<Date> {
+    Date::range(from, to)
+        .filter(|date| date.weekday().is_some_and(|weekday| weekdays.contains(weekday)))
+        .filter(|date| !days_off.contains(date))
+        .filter(|date| !tracked(date))
+        .collect()
+}
+
+/// A set of weekdays, for example the days of the week that are worked.
+///
+/// A set can be created from a comma separated list of weekdays, either with their full name or
+/// with their first 3 letters. For example `mon,tue,wed,thu,fri`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Weekdays(u8);
+
+impl Weekdays {
+    /// Hint on how to format weekdays as a string.
+    const FORMAT_HINT: &'static str
+        = "a comma separated list of weekdays like `mon,tue,wed,thu,fri`";
+
+    /// Every weekday in the order of their bit in the set.
+    const WEEKDAYS: [Weekday; 7] = [
+        Weekday::Monday, Weekday::Tuesday, Weekday::Wednesday, Weekday::Thursday,
+        Weekday::Friday, Weekday::Saturday, Weekday::Sunday,
+    ];
+
+    /// Monday to friday.
+    pub fn work_week() -> Self {
+        Self(0b0001_1111)
+    }
+
+    pub fn from_byte(byte: u8) -> Self {
+        Self(byte)
+    }
+
+    pub fn to_byte(self) -> u8 {
+        self.0
+    }
+
+    pub fn contains(&self, weekday: Weekday) -> bool {
+        self.0 & (1 << weekday.number_days_from_monday()) != 0
+    }
+}
+
+impl std::fmt::Display for Weekdays {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let weekdays = Self::WEEKDAYS
+            .iter()
+            .filter(|weekday| self.contains(**weekday))
+            .map(|weekday| short_weekday_name(*weekday))
+            .collect::<Vec<_>>();
+        write!(f, "{}", weekdays.join(","))
+    }
+}
+
+impl str::FromStr for Weekdays {
+    type Err = GlobalError;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        value
+            .split(',')
+            .map(|name| {
+                find_weekday(name)
+                    .map(|weekday| 1 << weekday.number_days_from_monday())
+                    .ok_or_else(|| GlobalError::parse(Self::FORMAT_HINT))
+            })
+            .try_fold(0, |set, bit| Ok(set | bit?))
+            .map(Self)
+    }
+}
+
+/// The days that are not worked even though they are work days, like vacations and holidays.
+///
+/// The days off can be created from a comma separated list of dates and of ranges of dates, both
+/// included. For example `2024-05-20..2024-05-24,2024-12-25`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DaysOff(Vec<(Date, Date)>);
+
+impl DaysOff {
+    /// Hint on how to format days off as a string.
+    const FORMAT_HINT: &'static str
+        = "a comma separated list of dates and of ranges of dates like `2024-05-20..2024-05-24,2024-12-25`";
+
+    pub fn contains(&self, date: &Date) -> bool {
+        self.0.iter().any(|(from, to)| from <= date && date <= to)
+    }
+}
+
+impl std::fmt::Display for DaysOff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ranges = self.0
+            .iter()
+            .map(|(from, to)| match from == to {
+                true => from.to_string(),
+                false => format!("{from}..{to}"),
+            })
+            .collect::<Vec<_>>();
+        write!(f, "{}", ranges.join(","))
+    }
+}
+
+impl str::FromStr for DaysOff {
+    type Err = GlobalError;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if value.trim().is_empty() {
+            return Ok(Self::default());
+        }
+        value
+            .split(',')
+            .map(|range| {
+                let (from, to) = range.trim().split_once("..").unwrap_or((range.trim(), range.trim()));
+                let from = from.parse::<Date>().map_err(|_| GlobalError::parse(Self::FORMAT_HINT))?;
+                let to = to.parse::<Date>().map_err(|_| GlobalError::parse(Self::FORMAT_HINT))?;
+                match from <= to {
+                    true => Ok((from, to)),
+                    false => Err(GlobalError::parse(Self::FORMAT_HINT)),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(Self)
+    }
+}
+
+/// The first 3 letters of the name of a weekday in lowercase, like `mon`.
+pub fn short_weekday_name(weekday: Weekday) -> String {
+    weekday.to_string()[..3].to_lowercase()
+}
+
+/// Find a weekday from its full name or its first 3 letters, ignoring the case.
+fn find_weekday(name: &str) -> Option<Weekday> {
+    let name = name.trim().to_lowercase();
+    Weekdays::WEEKDAYS
+        .iter()
+        .copied()
+        .find(|weekday| weekday.to_string().to_lowercase() == name || short_weekday_name(*weekday) == name)
+}
+
+/// Parse a single weekday, like `mon` or `monday`.
+pub fn parse_weekday(value: &str) -> Result<Weekday, GlobalError> {
+    find_weekday(value).ok_or_else(|| GlobalError::parse("a weekday like `mon` or `sunday`"))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Time {
+    hours: u8,
+    minutes: u8,
+    /// The seconds are not saved with the time, see `Precision`.
+    seconds: u8,
+}
+
+impl Time {
+    const HOURS_SIZE: usize = 1;
+    const MINUTES_SIZE: usize = 1;
+    /// Number of bytes to represent time when encoded
+    pub const SIZE: usize = Self::HOURS_SIZE + Self::MINUTES_SIZE;
+    /// How a time which is not saved is represented when encoded
+    pub const NONE_DATE: [u8; 2] = [0xFF, 0xFF];
+    /// Hint how to format time as a string.
+    pub const FORMAT_HINT: &'static str
+        = "`hh:mm` where `hh` represents the hours and `mm` represents the minutes, `hh` alone for `hh:00`, `hh:mm:ss` with the seconds, or like `9:30am` and `9pm` with a 12-hour clock";
+    /// Hint how to format a time relative to now as a string.
+    const RELATIVE_FORMAT_HINT: &'static str
+        = "`-15m`, `-1h` or `-1h30` where `h` are hours and `m` are minutes before now";
+
+    /// Midnight at the end of the day, `24:00`. It is not a valid time, only the end of a
+    /// duration.
+    pub fn end_of_day() -> Self {
+        Self {
+            hours: 24,
+            minutes: 0,
+            seconds: 0,
+        }
+    }
+
+    /// The time `ago` before `now`. The time cannot be before 0:00 of today.
+    pub fn before(now: Self, ago: Duration) -> Result<Self, GlobalError> {
+        let seconds = now.as_seconds() - ago.as_seconds();
+        if seconds < 0 {
+            return Err(GlobalError::time_before_midnight(ago, now));
+        }
+        Ok(Self::from_seconds(seconds))
+    }
+
+    /// The number of seconds since midnight.
+    fn as_seconds(self) -> i64 {
+        self.hours as i64 * 3600 + self.minutes as i64 * 60 + self.seconds as i64
+    }
+
+    /// The time `seconds` after midnight, which must be less than two days.
+    fn from_seconds(seconds: i64) -> Self {
+        Self {
+            hours: (seconds / 3600) as u8,
+            minutes: (seconds / 60 % 60) as u8,
+            seconds: (seconds % 60) as u8,
+        }
+    }
+
+    /// The seconds of the time, which are not saved in the pnchs database.
+    pub fn seconds(self) -> u8 {
+        self.seconds
+    }
+
+    /// The same time with other seconds.
+    pub fn with_seconds(self, seconds: u8) -> Self {
+        Self { seconds, ..self }
+    }
+
+    /// Returns a time instance, which when saved to a file it represent the abscence of time.
+    ///
+    /// When both `hours` and `minutes` are at the maximum value (255), it means the time does not
+    /// exists.
+    pub fn none() -> Self {
+        Self {
+            hours: u8::MAX,
+            minutes: u8::MAX,
+            seconds: 0,
+        }
+    }
+
+    /// The same time on the next day, to end a pnch after midnight. The hours of a time on the
+    /// next day go from 24 to 47, so the durations and the order of the times stay right.
+    pub fn next_day(self) -> Self {
+        Self {
+            hours: self.hours + 24,
+            ..self
+        }
+    }
+
+    /// The time is on the next day, see `Time::next_day`.
+    pub fn is_next_day(self) -> bool {
+        self != Self::none() && self.hours >= 24
+    }
+
+    /// Check that the time, on `date`, is not after `now` on `today`. A time on the next day is on
+    /// the day after `date`.
+    ///
+    /// A time in the future is most likely a typo, and the entry would end before it starts once
+    /// it is closed.
+    pub fn check_not_future(self, date: &Date, today: &Date, now: Time, allow_future: bool) -> Result<(), GlobalError> {
+        let date = match self.is_next_day() {
+            true => date.next(),
+            false => Some(date.clone()),
+        };
+        let is_future = date.is_some_and(|date| date > *today || (date == *today && self.within_day() > now));
+        if !allow_future && is_future {
+            return Err(GlobalError::time_in_future(self, now));
+        }
+        Ok(())
+    }
+
+    /// The time within its own day, without the day of a time on the next day.
+    fn within_day(self) -> Self {
+        match self.is_next_day() {
+            true => Self { hours: self.hours - 24, ..self },
+            false => self,
+        }
+    }
+
+    /// The time with the 24-hour or 12-hour clock, like `21:05` or `9:05 PM`. The seconds are
+    /// printed when the time has some, like `21:05:30`.
+    pub fn format(self, format: TimeFormat) -> String {
+        let time = self.within_day();
+        let seconds = match time.seconds {
+            0 => String::new(),
+            seconds => format!(":{seconds:02}"),
+        };
+        let mut formatted = match format {
+            TimeFormat::H24 => format!("{}:{:02}{seconds}", time.hours, time.minutes),
+            TimeFormat::H12 => {
+                let meridiem = if time.hours < 12 { "AM" } else { "PM" };
+                let hours = match time.hours % 12 {
+                    0 => 12,
+                    hours => hours,
+                };
+                format!("{hours}:{:02}{seconds} {meridiem}", time.minutes)
+            }
+        };
+        if self.is_next_day() {
+            formatted.push_str(" +1");
+        }
+        formatted
+    }
+
+    /// The time in the `hhmmss` format of iCalendar. A time on the next day is written within its
+    /// day, its date must be the next day.
+    pub fn to_ical(self) -> String {
+        let time = self.within_day();
+        format!("{:02}{:02}{:02}", time.hours, time.minutes, time.seconds)
+    }
+
+    pub fn to_le_bytes(self) -> [u8; Self::SIZE] {
+        [self.hours, self.minutes]
+    }
+
+    /// The hours and minutes are within a day.
+    pub fn is_valid(self) -> bool {
+        self.hours < 24 && self.minutes < 60
+    }
+
+    /// The hours and minutes are within a day or the next day, which is valid for the end of a
+    /// pnch.
+    pub fn is_valid_out(self) -> bool {
+        self.hours < 48 && self.minutes < 60
+    }
+
+    /// The closest valid time, the hours and minutes out of range are set to their maximum.
+    pub fn clamped(self) -> Self {
+        Self {
+            hours: self.hours.min(23),
+            minutes: self.minutes.min(59),
+            ..self
+        }
+    }
+
+    /// The closest valid end of a pnch, see `Time::is_valid_out`.
+    pub fn clamped_out(self) -> Self {
+        Self {
+            hours: self.hours.min(47),
+            minutes: self.minutes.min(59),
+            ..self
+        }
+    }
+
+    /// Add a duration to the time. Returns `None` if the result is not within the same day.
+    pub fn checked_add(self, duration: Duration) -> Option<Self> {
+        let seconds = self.as_seconds() + duration.as_seconds();
+        if !(0..24 * 3600).contains(&seconds) {
+            return None;
+        }
+        Some(Self::from_seconds(seconds))
+    }
+}
+
+impl std::fmt::Display for Time {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.format(TimeFormat::H24))
+    }
+}
+
+/// The clock used to print the times.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum TimeFormat {
+    /// `21:05`
+    #[default]
+    H24,
+    /// `9:05 PM`
+    H12,
+}
+
+impl TimeFormat {
+    pub fn to_byte(self) -> u8 {
+        match self {
+            Self::H24 => 0,
+            Self::H12 => 1,
+        }
+    }
+
+    pub fn from_byte(byte: u8) -> Self {
+        match byte {
+            1 => Self::H12,
+            _ => Self::H24,
+        }
+    }
+}
+
+impl str::FromStr for TimeFormat {
+    type Err = GlobalError;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match &value.to_lowercase()[..] {
+            "24h" => Ok(Self::H24),
+            "12h" => Ok(Self::H12),
+            _ => Err(GlobalError::parse("`24h` or `12h`")),
+        }
+    }
+}
+
+impl std::fmt::Display for TimeFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::H24 => write!(f, "24h"),
+            Self::H12 => write!(f, "12h"),
+        }
+    }
+}
+
+/// How precisely the times of the pnchs are recorded.
+///
+/// The pnchs database only has room for the hours and minutes of the times, so the seconds are
+/// saved next to it, see `Pnchs::SECONDS_FILE_SUFFIX`. A database without seconds is read as if
+/// every time had zero seconds.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Precision {
+    #[default]
+    Minutes,
+    Seconds,
+}
+
+impl Precision {
+    pub fn to_byte(self) -> u8 {
+        match self {
+            Self::Minutes => 0,
+            Self::Seconds => 1,
+        }
+    }
+
+    pub fn from_byte(byte: u8) -> Self {
+        match byte {
+            1 => Self::Seconds,
+            _ => Self::Minutes,
+        }
+    }
+
+    /// The time, without its seconds when they are not recorded.
+    pub fn apply(self, time: Time) -> Time {
+        match self {
+            Self::Minutes => time.with_seconds(0),
+            Self::Seconds => time,
+        }
+    }
+}
+
+impl str::FromStr for Precision {
+    type Err = GlobalError;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match &value.to_lowercase()[..] {
+            "minutes" => Ok(Self::Minutes),
+            "seconds" => Ok(Self::Seconds),
+            _ => Err(GlobalError::parse("`minutes` or `seconds`")),
+        }
+    }
+}
+
+impl std::fmt::Display for Precision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Minutes => write!(f, "minutes"),
+            Self::Seconds => write!(f, "seconds"),
+        }
+    }
+}
+
+/// The offset of the local time from UTC, in minutes, when a time was punched.
+///
+/// The times of the pnchs are local times, the offset of each time is saved next to the pnchs
+/// database, see `Pnchs::OFFSETS_FILE_SUFFIX`, so their duration is right even when the offset
+/// changed between the start and the end, like after a flight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Offset {
+    minutes: i16,
+}
+
+impl Offset {
+    pub const SIZE: usize = 2;
+    /// The bytes of a time without an offset.
+    const NONE_BYTES: [u8; Self::SIZE] = i16::MIN.to_le_bytes();
+
+    #[cfg(test)]
+    pub const fn from_minutes(minutes: i16) -> Self {
+        Self { minutes }
+    }
+
+    pub fn to_le_bytes(offset: Option<Self>) -> [u8; Self::SIZE] {
+        match offset {
+            Some(offset) => offset.minutes.to_le_bytes(),
+            None => Self::NONE_BYTES,
+        }
+    }
+
+    pub fn from_le_bytes(bytes: [u8; Self::SIZE]) -> Option<Self> {
+        match bytes {
+            Self::NONE_BYTES => None,
+            bytes => Some(Self { minutes: i16::from_le_bytes(bytes) }),
+        }
+    }
+}
+
+impl std::ops::Sub for Offset {
+    type Output = Duration;
+    fn sub(self, rhs: Self) -> Self::Output {
+        Duration::from_seconds((self.minutes as i64 - rhs.minutes as i64) * 60)
+    }
+}
+
+impl std::fmt::Display for Offset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sign = if self.minutes < 0 { '-' } else { '+' };
+        let minutes = self.minutes.unsigned_abs();
+        write!(f, "{sign}{:02}:{:02}", minutes / 60, minutes % 60)
+    }
+}
+
+/// The timezone of the current time, set by the `timezone` config or by `PNCH_TZ`.
+///
+/// The local timezone cannot always be found, like on some servers, and the current time is then
+/// in UTC. A fixed offset from UTC can be set instead.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Timezone {
+    #[default]
+    Local,
+    Fixed(Offset),
+}
+
+impl Timezone {
+    pub const SIZE: usize = Offset::SIZE;
+    /// The environment variable overriding the `timezone` config.
+    pub const ENV_VAR: &'static str = "PNCH_TZ";
+    /// Hint on how to format a timezone as a string.
+    const FORMAT_HINT: &'static str = "`local`, `utc` or an offset from UTC like `+02:00` or `-05:30`";
+
+    pub fn to_le_bytes(self) -> [u8; Self::SIZE] {
+        match self {
+            Self::Local => Offset::to_le_bytes(None),
+            Self::Fixed(offset) => Offset::to_le_bytes(Some(offset)),
+        }
+    }
+
+    pub fn from_le_bytes(bytes: [u8; Self::SIZE]) -> Self {
+        Offset::from_le_bytes(bytes)
+            .map(Self::Fixed)
+            .unwrap_or(Self::Local)
+    }
+
+    /// The timezone of `PNCH_TZ`, when it is set.
+    pub fn from_env() -> Result<Option<Self>, GlobalError> {
+        match std::env::var(Self::ENV_VAR) {
+            Ok(value) => value.parse().map(Some),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// The offset from UTC of the timezone, `None` when the local timezone cannot be found.
+    fn utc_offset(self) -> Option<time::UtcOffset> {
+        match self {
+            Self::Local => time::UtcOffset::current_local_offset().ok(),
+            Self::Fixed(offset) => time::UtcOffset::from_whole_seconds(offset.minutes as i32 * 60).ok(),
+        }
+    }
+}
+
+/// The current date and time, in the timezone of the config or of `PNCH_TZ`.
+///
+/// The clock is created once the config is loaded and is given to everything that depends on the
+/// current time, instead of reading it. A fixed clock always gives the same time, for the tests.
+#[derive(Debug, Clone, Copy)]
+pub struct Clock {
+    timezone: Timezone,
+    fixed: Option<time::OffsetDateTime>,
+}
+
+impl Clock {
+    /// The clock of the `timezone` config, unless `PNCH_TZ` is set. When the local timezone cannot
+    /// be found, the times are in UTC, see `Clock::is_utc_fallback`.
+    pub fn new(config: Timezone) -> Result<Self, GlobalError> {
+        let timezone = Timezone::from_env()?.unwrap_or(config);
+        Ok(Self { timezone, fixed: None })
+    }
+
+    /// The local timezone could not be found and the times are in UTC.
+    pub fn is_utc_fallback(&self) -> bool {
+        self.fixed.is_none() && self.timezone.utc_offset().is_none()
+    }
+
+    /// A clock stopped at `time` of `date`, `offset` from UTC.
+    #[cfg(test)]
+    pub fn fixed(date: &Date, time: Time, offset: Offset) -> Self {
+        let month = time::Month::try_from(date.month).expect("a valid month");
+        let date = time::Date::from_calendar_date(date.year as i32, month, date.day).expect("a valid date");
+        let offset = time::UtcOffset::from_whole_seconds(offset.minutes as i32 * 60).expect("a valid offset");
+        let now = time::PrimitiveDateTime::new(date, time::Time::from_hms(time.hours, time.minutes, time.seconds).expect("a valid time"))
+            .assume_offset(offset);
+        Self { timezone: Timezone::Fixed(Offset { minutes: offset.whole_minutes() }), fixed: Some(now) }
+    }
+
+    /// The current date and time in the timezone of the clock.
+    fn now_in_timezone(&self) -> time::OffsetDateTime {
+        if let Some(now) = self.fixed {
+            return now;
+        }
+        let now = time::OffsetDateTime::now_utc();
+        match self.timezone.utc_offset() {
+            Some(offset) => now.to_offset(offset),
+            None => now,
+        }
+    }
+
+    pub fn today(&self) -> Date {
+        Date::from(self.now_in_timezone().date())
+    }
+
+    /// The current time, without its seconds.
+    pub fn now(&self) -> Time {
+        Time { seconds: 0, ..self.now_with_seconds() }
+    }
+
+    pub fn now_with_seconds(&self) -> Time {
+        let (hours, minutes, seconds) = self.now_in_timezone().time().as_hms();
+        Time { hours, minutes, seconds }
+    }
+
+    /// The current offset from UTC, `None` when the local timezone cannot be found.
+    pub fn offset(&self) -> Option<Offset> {
+        let offset = match self.fixed {
+            Some(now) => now.offset(),
+            None => self.timezone.utc_offset()?,
+        };
+        Some(Offset { minutes: offset.whole_minutes() })
+    }
+
+    /// The current UTC date and time in the `yyyymmddThhmmssZ` format of iCalendar.
+    pub fn ical_utc_now(&self) -> String {
+        ical_utc(self.now_in_timezone())
+    }
+}
+
+/// The moment in UTC in the `yyyymmddThhmmssZ` format of iCalendar.
+fn ical_utc(moment: time::OffsetDateTime) -> String {
+    let moment = moment.to_offset(time::UtcOffset::UTC);
+    let date = Date::from(moment.date());
+    let (hours, minutes, seconds) = moment.time().as_hms();
+    format!("{}T{hours:02}{minutes:02}{seconds:02}Z", date.to_ical())
+}
+
+impl str::FromStr for Timezone {
+    type Err = GlobalError;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match &value.to_lowercase()[..] {
+            "local" => return Ok(Self::Local),
+            "utc" => return Ok(Self::Fixed(Offset { minutes: 0 })),
+            _ => {}
+        }
+        let (sign, offset) = match value.split_at_checked(1) {
+            Some(("+", offset)) => (1, offset),
+            Some(("-", offset)) => (-1, offset),
+            _ => return Err(GlobalError::parse(Self::FORMAT_HINT)),
+        };
+        let (hours, minutes) = offset.split_once(':').unwrap_or((offset, "0"));
+        let hours = hours.parse::<i16>().ok().filter(|hours| (0..24).contains(hours));
+        let minutes = minutes.parse::<i16>().ok().filter(|minutes| (0..60).contains(minutes));
+        match (hours, minutes) {
+            (Some(hours), Some(minutes)) => Ok(Self::Fixed(Offset { minutes: sign * (hours * 60 + minutes) })),
+            _ => Err(GlobalError::parse(Self::FORMAT_HINT)),
+        }
+    }
+}
+
+impl std::fmt::Display for Timezone {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Local => write!(f, "local"),
+            Self::Fixed(Offset { minutes: 0 }) => write!(f, "utc"),
+            Self::Fixed(offset) => write!(f, "{offset}"),
+        }
+    }
+}
+
+impl std::convert::TryFrom<&[u8]> for Time {
+    type Error = GlobalError;
+    fn try_from(buffer: &[u8]) -> Result<Self, Self::Error> {
+        if buffer.len() != Self::SIZE {
+            return Err(GlobalError::wrong_byte_len("time", buffer.len(), Self::SIZE))
+        }
+        Ok(Self {
+            hours: buffer[0],
+            minutes: buffer[1],
+            seconds: 0,
+        })
+    }
+}
+
+impl str::FromStr for Time {
+    type Err = error::GlobalError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let error = || GlobalError::parse(Time::FORMAT_HINT);
+        let value = value.trim().to_lowercase();
+        let (value, meridiem) = match value.strip_suffix("am").or_else(|| value.strip_suffix("pm")) {
+            Some(time) => (time.trim_end(), value.get(value.len() - 2..)),
+            None => (&value[..], None),
+        };
+        let (hours_str, minutes_str) = match (value.split_once(':'), meridiem) {
+            (Some((hours_str, minutes_str)), _) => (hours_str, minutes_str),
+            // `9pm` is 21:00 and `9` is 9:00.
+            (None, _) => (value, "00"),
+        };
+        let (minutes_str, seconds_str) = minutes_str.split_once(':').unwrap_or((minutes_str, "00"));
+        if minutes_str.len() != 2 || seconds_str.len() != 2 {
+            return Err(error());
+        }
+        let mut hours = hours_str.parse::<u8>().map_err(|_| error())?;
+        let minutes = minutes_str.parse::<u8>().map_err(|_| error())?;
+        let seconds = seconds_str.parse::<u8>().map_err(|_| error())?;
+        match meridiem {
+            _ if minutes > 59 || seconds > 59 => return Err(error()),
+            Some(_) if !(1..=12).contains(&hours) => return Err(error()),
+            // 12am is midnight and 12pm is noon.
+            Some("am") => hours %= 12,
+            Some(_) => hours = hours % 12 + 12,
+            None if hours > 23 => return Err(error()),
+            None => {}
+        }
+        Ok(Self {
+            hours,
+            minutes,
+            seconds,
+        })
+    }
+}
+
+/// A time given on the command line, a time of the day or a duration before now like `-15m`. The
+/// times before now are resolved with the clock, once the config is loaded.
+#[derive(Debug, Clone, Copy)]
+pub enum TimeArg {
+    At(Time),
+    Ago(Duration),
+}
+
+impl TimeArg {
+    /// The time, where the durations are before the current time of `clock` without its seconds.
+    /// A time of the day keeps the seconds it was given with.
+    pub fn resolve(self, clock: &Clock) -> Result<Time, GlobalError> {
+        match self {
+            Self::At(time) => Ok(time),
+            Self::Ago(ago) => Time::before(clock.now(), ago),
+        }
+    }
+}
+
+impl str::FromStr for TimeArg {
+    type Err = GlobalError;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.strip_prefix('-') {
+            Some(ago) => ago
+                .parse::<Duration>()
+                .map(Self::Ago)
+                .map_err(|_| GlobalError::parse(Time::RELATIVE_FORMAT_HINT)),
+            None => value.parse().map(Self::At),
+        }
+    }
+}
+
+impl std::ops::Sub for Time {
+    type Output = Duration;
+    fn sub(self, rhs: Self) -> Self::Output {
+        Duration::from_seconds(self.as_seconds() - rhs.as_seconds())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(value: &str) -> Date {
+        value.parse().expect("a valid date")
+    }
+
+    fn time(value: &str) -> Time {
+        value.parse().expect("a valid time")
+    }
+
+    /// A clock stopped on a wednesday.
+    fn clock(now: &str) -> Clock {
+        Clock::fixed(&date("2024-05-15"), time(now), Offset { minutes: 120 })
+    }
+
+    #[test]
+    fn fixed_clock_gives_its_date_time_and_offset() {
+        let clock = clock("9:30");
+        assert_eq!(clock.today(), date("2024-05-15"));
+        assert_eq!(clock.now(), time("9:30"));
+        assert_eq!(clock.offset(), Some(Offset { minutes: 120 }));
+        assert_eq!(clock.ical_utc_now(), "20240515T073000Z");
+    }
+
+    #[test]
+    fn time_ago_is_before_the_clock() {
+        let clock = clock("9:30");
+        let ago = "-1h15".parse::<TimeArg>().expect("a relative time");
+        assert_eq!(ago.resolve(&clock).expect("a time"), time("8:15"));
+        let at = "14:00".parse::<TimeArg>().expect("a time");
+        assert_eq!(at.resolve(&clock).expect("a time"), time("14:00"));
+    }
+
+    #[test]
+    fn time_ago_before_midnight_fails() {
+        let ago = "-2h".parse::<TimeArg>().expect("a relative time");
+        assert!(ago.resolve(&clock("1:00")).is_err());
+    }
+
+    #[test]
+    fn relative_dates_are_relative_to_the_clock() {
+        let today = clock("9:30").today();
+        let resolve = |value: &str| value.parse::<RelativeDate>().expect("a date").to_date(&today).expect("a date");
+        assert_eq!(resolve("today"), date("2024-05-15"));
+        assert_eq!(resolve("yesterday"), date("2024-05-14"));
+        assert_eq!(resolve("wednesday"), date("2024-05-15"));
+        assert_eq!(resolve("last wednesday"), date("2024-05-08"));
+        assert_eq!(resolve("3 days ago"), date("2024-05-12"));
+        assert_eq!(resolve("2024-01-02"), date("2024-01-02"));
+    }
+
+    #[test]
+    fn weekdays_are_the_last_one_today_included() {
+        // The 13th of may 2024 is a monday.
+        let resolve = |value: &str, today: &str| value.parse::<RelativeDate>().expect("a date").to_date(&date(today)).expect("a date");
+        assert_eq!(resolve("monday", "2024-05-13"), date("2024-05-13"));
+        assert_eq!(resolve("last monday", "2024-05-13"), date("2024-05-06"));
+        assert_eq!(resolve("Mon", "2024-05-14"), date("2024-05-13"));
+        assert_eq!(resolve("last mon", "2024-05-14"), date("2024-05-13"));
+        assert_eq!(resolve("sunday", "2024-05-13"), date("2024-05-12"));
+        assert_eq!(resolve("last sunday", "2024-05-12"), date("2024-05-05"));
+        assert_eq!(resolve("tuesday", "2024-01-01"), date("2023-12-26"));
+        assert_eq!(resolve("1 day ago", "2024-03-01"), date("2024-02-29"));
+        assert_eq!(resolve("0 days ago", "2024-03-01"), date("2024-03-01"));
+        assert_eq!(resolve("tomorrow", "2024-12-31"), date("2025-01-01"));
+        for value in ["next monday", "last", "funday", "3 weeks ago", "-1 days ago"] {
+            assert!(value.parse::<RelativeDate>().is_err(), "{value}");
+        }
+        // A malformed date keeps the error of the date.
+        let error = "2024-02-30".parse::<RelativeDate>().expect_err("not in the calendar").to_string();
+        assert!(error.contains("does not exist in the calendar"), "{error}");
+    }
+
+    #[test]
+    fn since_accepts_dates_and_periods() {
+        let today = date("2024-05-15");
+        let since = |value: &str| value.parse::<SinceArg>().expect("a since").to_date_since(&today).expect("a date");
+        assert_eq!(since("last monday"), date("2024-05-13"));
+        assert_eq!(since("2w"), date("2024-05-01"));
+    }
+
+    #[test]
+    fn months_and_years_are_substracted_on_the_calendar() {
+        let since = |period: &str, today: &str| period.parse::<Period>().expect("a period").to_date_since(&date(today));
+        assert_eq!(since("3m", "2024-05-15"), date("2024-02-15"));
+        assert_eq!(since("1m", "2024-03-31"), date("2024-02-29"));
+        assert_eq!(since("1m", "2023-03-31"), date("2023-02-28"));
+        assert_eq!(since("2m", "2024-01-10"), date("2023-11-10"));
+        assert_eq!(since("1y", "2024-02-29"), date("2023-02-28"));
+        assert_eq!(since("4y", "2024-02-29"), date("2020-02-29"));
+        assert_eq!(since("1y", "2024-05-15"), date("2023-05-15"));
+        assert_eq!(since("10d", "2024-03-05"), date("2024-02-24"));
+        assert_eq!(since("1w", "2024-01-03"), date("2023-12-27"));
+        assert_eq!(since("1m", "0000-01-15"), Date::min());
+    }
+
+    #[test]
+    fn iso_weeks_around_the_year_boundaries() {
+        assert_eq!(date("2024-05-13").iso_week(), Some((2024, 20)));
+        // The 1st of January 2021 is a friday, in the last week of 2020, which has 53 weeks.
+        assert_eq!(date("2020-12-31").iso_week(), Some((2020, 53)));
+        assert_eq!(date("2021-01-03").iso_week(), Some((2020, 53)));
+        assert_eq!(date("2021-01-04").iso_week(), Some((2021, 1)));
+        // The 31st of December 2024 is a tuesday, in the first week of 2025.
+        assert_eq!(date("2024-12-29").iso_week(), Some((2024, 52)));
+        assert_eq!(date("2024-12-30").iso_week(), Some((2025, 1)));
+        assert_eq!(date("2025-01-01").iso_week(), Some((2025, 1)));
+        // The 1st of January 2026 is a thursday, in the first week of 2026.
+        assert_eq!(date("2026-01-01").iso_week(), Some((2026, 1)));
+        assert_eq!(date("2025-12-28").iso_week(), Some((2025, 52)));
+    }
+
+    #[test]
+    fn months_span_from_their_first_to_their_last_day() {
+        let month = |value: &str| value.parse::<YearMonth>().expect("a month");
+        assert_eq!((month("2024-05").first_day(), month("2024-05").last_day()), (date("2024-05-01"), date("2024-05-31")));
+        assert_eq!(month("2024-04").last_day(), date("2024-04-30"));
+        assert_eq!(month("2024-02").last_day(), date("2024-02-29"));
+        assert_eq!(month("2023-02").last_day(), date("2023-02-28"));
+        assert_eq!(month("1900-02").last_day(), date("1900-02-28"));
+        assert_eq!(month("2000-02").last_day(), date("2000-02-29"));
+        assert_eq!(month("2024-12").last_day(), date("2024-12-31"));
+        assert_eq!(month("2024-1"), month("2024-01"));
+        for value in ["2024", "2024-13", "2024-00", "2024-may", "-05", "2024-05-01"] {
+            assert!(value.parse::<YearMonth>().is_err(), "{value}");
+        }
+    }
+
+    #[test]
+    fn half_way_durations_are_rounded_up() {
+        let minutes = |minutes: i64| Duration::from_seconds(minutes * 60);
+        let round = |rounding: &str, duration: Duration| rounding.parse::<Rounding>().expect("a rounding").apply(duration);
+        assert_eq!(round("30m", minutes(15)), minutes(30));
+        assert_eq!(round("30m", minutes(14)), minutes(0));
+        assert_eq!(round("15m", minutes(7)), minutes(0));
+        assert_eq!(round("15m", minutes(8)), minutes(15));
+        assert_eq!(round("15m nearest", minutes(7) + Duration::from_seconds(30)), minutes(15));
+        assert_eq!(round("15m nearest", minutes(7) + Duration::from_seconds(29)), minutes(0));
+        assert_eq!(round("5m", minutes(62)), minutes(60));
+        assert_eq!(round("15m up", Duration::from_seconds(1)), minutes(15));
+        assert_eq!(round("15m up", minutes(15)), minutes(15));
+        assert_eq!(round("30m up", minutes(0)), minutes(0));
+        assert_eq!(round("none", Duration::from_seconds(61)), Duration::from_seconds(61));
+        assert!("10m".parse::<Rounding>().is_err());
+        assert!("15m down".parse::<Rounding>().is_err());
+    }
+
+    #[test]
+    fn durations_are_printed_in_each_format() {
+        let minutes = |minutes: i64| Duration::from_seconds(minutes * 60);
+        let formats = |duration: Duration| [DurationFormat::Clock, DurationFormat::Hours, DurationFormat::Decimal, DurationFormat::Verbose]
+            .map(|format| (duration.format(format), duration.format_long(format)));
+        assert_eq!(formats(minutes(0)).map(|(short, _)| short), ["0:00", "0:00", "0.00", "0m"]);
+        assert_eq!(formats(minutes(45)).map(|(short, _)| short), ["0:45", "0:45", "0.75", "45m"]);
+        assert_eq!(formats(minutes(25 * 60 + 30)).map(|(short, _)| short), ["25:30", "25:30", "25.50", "25h 30m"]);
+        assert_eq!(formats(minutes(25 * 60 + 30)).map(|(_, long)| long)[..2], ["1d 1:30", "25 hours 30 minutes"]);
+        assert_eq!(formats(minutes(45)).map(|(_, long)| long), ["45 minutes", "45 minutes", "0.75", "45m"]);
+        assert_eq!(minutes(-20).format(DurationFormat::Decimal), "-0.33");
+        assert_eq!(Duration::from_seconds(61).format(DurationFormat::Verbose), "1m 1s");
+    }
+
+    #[test]
+    fn long_totals_are_in_days_from_24_hours() {
+        let minutes = |minutes: i64| Duration::from_seconds(minutes * 60);
+        assert_eq!(minutes(24 * 60 - 1).format_long(DurationFormat::Clock), "23 hours 59 minutes");
+        assert_eq!(minutes(24 * 60).format_long(DurationFormat::Clock), "1d 0:00");
+        assert_eq!(minutes(24 * 60 + 1).format_long(DurationFormat::Clock), "1d 0:01");
+        assert_eq!(minutes(2 * 24 * 60).format_long(DurationFormat::Clock), "2d 0:00");
+        assert_eq!(minutes(163 * 60 + 25).format_long(DurationFormat::Clock), "6d 19:25");
+        assert_eq!(minutes(-25 * 60).format_long(DurationFormat::Clock), "-1d 1:00");
+        assert_eq!(minutes(25 * 60).format_signed(DurationFormat::Clock), "+1d 1:00");
+        // The days are turned off with the `hours` format, and the entries keep their hours.
+        assert_eq!(minutes(163 * 60 + 25).format_long(DurationFormat::Hours), "163 hours 25 minutes");
+        assert_eq!(minutes(24 * 60).format(DurationFormat::Clock), "24:00");
+    }
+
+    #[test]
+    fn weekdays_around_the_leap_days() {
+        assert_eq!(date("2024-05-11").short_weekday().as_deref(), Some("Sat"));
+        assert_eq!(date("2024-02-28").weekday(), Some(Weekday::Wednesday));
+        assert_eq!(date("2024-02-29").weekday(), Some(Weekday::Thursday));
+        assert_eq!(date("2024-03-01").weekday(), Some(Weekday::Friday));
+        assert_eq!(date("2023-02-28").weekday(), Some(Weekday::Tuesday));
+        assert_eq!(date("2023-03-01").weekday(), Some(Weekday::Wednesday));
+        assert_eq!(date("2000-02-29").weekday(), Some(Weekday::Tuesday));
+        assert_eq!(date("1900-03-01").weekday(), Some(Weekday::Thursday));
+        assert_eq!(date("2024-12-31").short_weekday().as_deref(), Some("Tue"));
+        assert_eq!(date("2025-01-01").short_weekday().as_deref(), Some("Wed"));
+    }
+
+    #[test]
+    fn untracked_days_skip_the_weekends_and_the_days_off() {
+        let days_off = "2024-05-15..2024-05-17,2024-05-22".parse::<DaysOff>().unwrap();
+        let days = untracked_days(&date("2024-05-13"), &date("2024-05-26"), Weekdays::work_week(), &days_off, |day| {
+            *day == date("2024-05-13")
+        });
+        assert_eq!(days, ["2024-05-14", "2024-05-20", "2024-05-21", "2024-05-23", "2024-05-24"].map(date));
+    }
+
+    #[test]
+    fn days_off_are_dates_and_ranges() {
+        let days_off = " 2024-05-15..2024-05-17, 2024-12-25".parse::<DaysOff>().unwrap();
+        assert_eq!(days_off.to_string(), "2024-05-15..2024-05-17,2024-12-25");
+        assert!(days_off.contains(&date("2024-05-17")) && days_off.contains(&date("2024-12-25")));
+        assert!(!days_off.contains(&date("2024-05-18")));
+        assert_eq!("".parse::<DaysOff>().unwrap(), DaysOff::default());
+        assert!("2024-05-17..2024-05-15".parse::<DaysOff>().is_err());
+        assert!("2024-05-15..".parse::<DaysOff>().is_err());
+    }
+
+    #[test]
+    fn future_times_are_refused_unless_allowed() {
+        let (today, now) = (date("2024-05-15"), time("12:00"));
+        assert!(time("12:30").check_not_future(&today, &today, now, false).is_err());
+        assert!(time("12:30").check_not_future(&today, &today, now, true).is_ok());
+        assert!(time("11:30").check_not_future(&today, &today, now, false).is_ok());
+        assert!(time("9:00").check_not_future(&date("2024-05-16"), &today, now, false).is_err());
+        // A time on the next day is on the day after the date of the entry.
+        let next_day = time("1:00").next_day();
+        assert!(next_day.check_not_future(&date("2024-05-14"), &today, now, false).is_ok());
+        assert!(next_day.check_not_future(&today, &today, now, false).is_err());
+    }
+
+    #[test]
+    fn bare_hours_are_whole_hours() {
+        assert_eq!(time("9"), time("9:00"));
+        assert_eq!(time("09"), time("9:00"));
+        assert_eq!(time("23"), time("23:00"));
+        assert!("24".parse::<Time>().is_err());
+        // The minutes always have 2 digits.
+        assert!("9:5".parse::<Time>().is_err());
+        assert_eq!(time("9:05"), Time { hours: 9, minutes: 5, seconds: 0 });
+    }
+
+    #[test]
+    fn meridiem_times_use_the_12_hour_clock() {
+        assert_eq!(time("9pm"), time("21:00"));
+        assert_eq!(time("9:30 AM"), time("9:30"));
+        assert_eq!(time("12am"), time("0:00"));
+        assert_eq!(time("12pm"), time("12:00"));
+        assert!("13pm".parse::<Time>().is_err());
+        assert!("0am".parse::<Time>().is_err());
+    }
+
+    #[test]
+    fn time_args_are_times_or_durations_before_now() {
+        let clock = clock("12:00");
+        let resolve = |value: &str| value.parse::<TimeArg>().expect("a time").resolve(&clock).expect("a time");
+        assert_eq!(resolve("-1h30"), time("10:30"));
+        assert_eq!(resolve("-15m"), time("11:45"));
+        assert_eq!(resolve("9"), time("9:00"));
+        assert!("-9:5".parse::<TimeArg>().is_err());
+        assert!("24".parse::<TimeArg>().is_err());
+    }
+
+    #[test]
+    fn dates_accept_other_separators_and_orders() {
+        let expected = Date { year: 2024, month: 5, day: 13 };
+        for value in ["2024-05-13", "2024/05/13", "2024.05.13", "13-05-2024", "13/05/2024", "13.05.2024", "20240513"] {
+            assert_eq!(date(value), expected, "{value}");
+        }
+    }
+
+    #[test]
+    fn malformed_dates_are_refused() {
+        for value in ["2024-05/13", "2024.05-13", "20241301", "20240230", "2024-13-01", "2024-02-30", "2024-05", "240513", "05-13-24"] {
+            assert!(value.parse::<Date>().is_err(), "{value}");
+        }
+    }
+
+    #[test]
+    fn date_errors_explain_the_format() {
+        let error = |value: &str| value.parse::<Date>().expect_err("a malformed date").to_string();
+        assert_eq!(date("2024-05-13"), date("13-05-2024"));
+        for value in ["5-13", "garbage", "2024-05-13-01"] {
+            assert!(error(value).contains("`yyyy-mm-dd` or `dd-mm-yyyy`"), "{value}");
         }
+        assert!(error("13-05-24").contains("The year of the date 13-05-24 cannot be found"));
+        assert!(error("2024-05-2024").contains("The year of the date 2024-05-2024 cannot be found"));
+        assert!(error("2024-02-30").contains("The date 2024-02-30 does not exist in the calendar."));
     }
 }